@@ -0,0 +1,16 @@
+//! Interface for calling marinade by cross-program invocation, see Docs/CPI-Interface.md.
+//! Re-exports the instruction functions and account structs generated for the program
+//! (built with the `cpi` feature, so without its entrypoint) and adds the PDA addresses
+//! and the return data reader CPI callers need
+
+pub mod pda;
+pub mod return_data;
+
+pub use marinade_finance::{
+    cpi,
+    state::{
+        price_history::{PriceAverage, PriceSample},
+        quote::{DepositQuote, LiquidUnstakeQuote, RemoveLiquidityQuote},
+    },
+    State, ID,
+};
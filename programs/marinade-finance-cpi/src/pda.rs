@@ -0,0 +1,57 @@
+//! Addresses of the PDAs of a marinade instance, derived from the program it runs on
+//! (`marinade_finance::ID` for the main deployment).
+//! `state` is State::instance_address: the state account address, unless the state was migrated
+
+use anchor_lang::prelude::*;
+use marinade_finance::{epoch_stats::EpochStats, liq_pool::LiqPool, State};
+
+pub fn reserve(state: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    State::find_reserve_address(state, program_id).0
+}
+
+pub fn msol_mint_authority(state: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    State::find_msol_mint_authority(state, program_id).0
+}
+
+pub fn liq_pool_sol_leg(state: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    LiqPool::find_sol_leg_address(state, program_id).0
+}
+
+pub fn liq_pool_msol_leg_authority(state: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    LiqPool::find_msol_leg_authority(state, program_id).0
+}
+
+pub fn lp_mint_authority(state: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    LiqPool::find_lp_mint_authority(state, program_id).0
+}
+
+/// temporary token account of deposit_wsol and add_liquidity_wsol funded from wsol_account
+pub fn temp_wsol_account(state: &Pubkey, wsol_account: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    State::find_temp_wsol_account(state, wsol_account, program_id).0
+}
+
+/// optional last remaining account of the instructions updating the epoch statistics
+pub fn epoch_stats(state: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    EpochStats::find_address(state, program_id).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ID;
+
+    #[test]
+    fn test_temp_wsol_account() {
+        let state = Pubkey::new_unique();
+        let wsol_account = Pubkey::new_unique();
+        let temp = temp_wsol_account(&state, &wsol_account, &ID);
+        assert_eq!(temp, temp_wsol_account(&state, &wsol_account, &ID));
+        // one per funding account and per instance
+        assert_ne!(temp, temp_wsol_account(&state, &Pubkey::new_unique(), &ID));
+        assert_ne!(
+            temp,
+            temp_wsol_account(&Pubkey::new_unique(), &wsol_account, &ID)
+        );
+        assert_ne!(temp, reserve(&state, &ID));
+    }
+}
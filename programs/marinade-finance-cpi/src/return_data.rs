@@ -0,0 +1,58 @@
+//! Results of the marinade view instructions (quote_*, get_price_at_epoch, get_average_price)
+//! called by CPI, read from the program return data
+
+use anchor_lang::prelude::*;
+
+/// data longer than that is truncated by the runtime
+pub const MAX_RETURN_DATA: usize = 1024;
+
+#[cfg(target_arch = "bpf")]
+extern "C" {
+    fn sol_get_return_data(data: *mut u8, length: u64, program_id: *mut Pubkey) -> u64;
+}
+
+/// Returns the last return data set in the transaction with the program that set it.
+/// solana-program 1.7 has no wrapper for this syscall yet, so it is declared here
+#[cfg(target_arch = "bpf")]
+pub fn get_return_data() -> Option<(Pubkey, Vec<u8>)> {
+    let mut data = vec![0u8; MAX_RETURN_DATA];
+    let mut program_id = Pubkey::default();
+    let size = unsafe { sol_get_return_data(data.as_mut_ptr(), data.len() as u64, &mut program_id) }
+        as usize;
+    if size == 0 {
+        return None;
+    }
+    data.truncate(size.min(MAX_RETURN_DATA));
+    Some((program_id, data))
+}
+
+/// Off-chain builds (tests, clients) have no return data
+#[cfg(not(target_arch = "bpf"))]
+pub fn get_return_data() -> Option<(Pubkey, Vec<u8>)> {
+    None
+}
+
+/// Reads the result of the view instruction just called, e.g. a DepositQuote after quote_deposit.
+/// Fails if the return data was not set by marinade (program_id)
+pub fn read<T: AnchorDeserialize>(program_id: &Pubkey) -> Result<T, ProgramError> {
+    match get_return_data() {
+        Some((returned_by, data)) if returned_by == *program_id => {
+            T::try_from_slice(&data).map_err(|_| ProgramError::InvalidAccountData)
+        }
+        _ => {
+            msg!("No return data set by {}", program_id);
+            Err(ProgramError::InvalidAccountData)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DepositQuote;
+
+    #[test]
+    fn test_read_without_return_data() {
+        assert!(read::<DepositQuote>(&crate::ID).is_err());
+    }
+}
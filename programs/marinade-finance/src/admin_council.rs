@@ -0,0 +1,482 @@
+use crate::{
+    checks::{check_address, check_owner_program},
+    roles::Role,
+    ChangeAuthorityData, ConfigMarinadeParams, Fee, LpParams, State,
+};
+use anchor_lang::prelude::*;
+
+pub mod approve_proposal;
+pub mod create_proposal;
+pub mod execute_proposal;
+pub mod init_admin_council;
+
+/// M-of-N admin. When State.admin_authority is set to this account,
+/// admin actions are proposals approved by members and executed by execute_proposal.
+/// PDA [state, "admin_council", council_id]
+#[account]
+#[derive(Debug, Default)]
+pub struct AdminCouncil {
+    pub state_address: Pubkey, // instance of marinade state this council belongs to
+    pub council_id: u64,
+    /// approvals needed to execute a proposal
+    pub threshold: u8,
+    /// index of the next proposal
+    pub proposal_count: u64,
+    pub members: Vec<Pubkey>,
+}
+
+impl AdminCouncil {
+    pub const SEED: &'static [u8] = b"admin_council";
+    pub const MAX_MEMBERS: usize = 16;
+
+    /// space for MAX_MEMBERS
+    pub fn serialized_len() -> usize {
+        Self {
+            members: vec![Pubkey::default(); Self::MAX_MEMBERS],
+            ..Default::default()
+        }
+        .try_to_vec()
+        .unwrap()
+        .len()
+            + 8
+    }
+
+    pub fn find_address(state: &Pubkey, council_id: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                &state.to_bytes()[..32],
+                Self::SEED,
+                &council_id.to_le_bytes(),
+            ],
+            program_id,
+        )
+    }
+
+    pub fn check_state_address(&self, state_address: &Pubkey) -> ProgramResult {
+        check_address(state_address, &self.state_address, "admin council state")
+    }
+
+    pub fn check_members(members: &[Pubkey], threshold: u8) -> ProgramResult {
+        if members.is_empty() || members.len() > Self::MAX_MEMBERS {
+            msg!(
+                "Council must have 1 to {} members. Got {}",
+                Self::MAX_MEMBERS,
+                members.len()
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        for (i, member) in members.iter().enumerate() {
+            if members[..i].contains(member) {
+                msg!("Duplicated council member {}", member);
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+        if threshold == 0 || threshold as usize > members.len() {
+            msg!(
+                "Threshold {} must be from 1 to {}",
+                threshold,
+                members.len()
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    pub fn member_index(&self, member: &Pubkey) -> Result<usize, ProgramError> {
+        self.members
+            .iter()
+            .position(|key| key == member)
+            .ok_or_else(|| {
+                msg!("{} is not a council member", member);
+                ProgramError::InvalidArgument
+            })
+    }
+}
+
+/// Admin action carried by a council proposal.
+/// execute_proposal runs the first ones. The others are admin-only instructions
+/// run with the executed proposal passed as admin_authority
+#[derive(Clone, Copy, Debug, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub enum CouncilAction {
+    ConfigMarinade(ConfigMarinadeParams),
+    ChangeAuthority(ChangeAuthorityData),
+    SetLpParams(LpParams),
+    Unpause {
+        operations: u64,
+    },
+    AcknowledgeCircuitBreaker,
+    SetRole {
+        role: Role,
+        authority: Pubkey,
+    },
+    InitPendingConfig,
+    InitEpochStats,
+    MigrateState,
+    MigrateList {
+        new_list: Pubkey,
+        additional_record_space: u32,
+    },
+    InitReferralAccount {
+        partner_account: Pubkey,
+        msol_token_partner_account: Pubkey,
+        partner_share: Fee,
+    },
+    UpdateReferral {
+        referral_state: Pubkey,
+        msol_token_partner_account: Pubkey,
+        partner_share: Fee,
+    },
+}
+
+impl CouncilAction {
+    /// run by its own instruction, not by execute_proposal
+    pub fn is_admin_instruction(&self) -> bool {
+        !matches!(
+            self,
+            Self::ConfigMarinade(_) | Self::ChangeAuthority(_) | Self::SetLpParams(_)
+        )
+    }
+}
+
+/// PDA [council, "proposal", index]
+#[account]
+#[derive(Debug)]
+pub struct CouncilProposal {
+    pub council: Pubkey,
+    pub index: u64,
+    pub proposer: Pubkey,
+    pub action: CouncilAction,
+    /// bit i is set when members[i] approved
+    pub approvals: u64,
+    /// NOT_EXECUTED, EXECUTED or USED
+    pub executed: u8,
+    /// can not be executed starting from this epoch
+    pub expiry_epoch: u64,
+}
+
+impl CouncilProposal {
+    pub const SEED: &'static [u8] = b"proposal";
+    pub const NOT_EXECUTED: u8 = 0;
+    pub const EXECUTED: u8 = 1;
+    /// an admin instruction action was run
+    pub const USED: u8 = 2;
+    /// epochs to collect the approvals and execute
+    pub const LIFETIME_EPOCHS: u64 = 10;
+
+    /// space for the largest action
+    pub fn serialized_len() -> usize {
+        let full_config = ConfigMarinadeParams {
+            rewards_fee: Some(Default::default()),
+            slots_for_stake_delta: Some(0),
+            min_stake: Some(0),
+            min_deposit: Some(0),
+            min_withdraw: Some(0),
+            staking_sol_cap: Some(0),
+            liquidity_sol_cap: Some(0),
+            auto_add_validator_enabled: Some(false),
+            max_msol_price_increase: Some(Default::default()),
+            max_msol_price_decrease: Some(Default::default()),
+            directed_stake_epochs: Some(0),
+        };
+        let full_authority = ChangeAuthorityData {
+            admin: Some(Pubkey::default()),
+            validator_manager: Some(Pubkey::default()),
+            operational_sol_account: Some(Pubkey::default()),
+            treasury_msol_account: Some(Pubkey::default()),
+            pause_authority: Some(Pubkey::default()),
+            timelock_slots: Some(0),
+        };
+        [
+            CouncilAction::ConfigMarinade(full_config),
+            CouncilAction::ChangeAuthority(full_authority),
+            CouncilAction::SetLpParams(LpParams::default()),
+        ]
+        .iter()
+        .map(|action| {
+            Self {
+                council: Pubkey::default(),
+                index: 0,
+                proposer: Pubkey::default(),
+                action: *action,
+                approvals: 0,
+                executed: 0,
+                expiry_epoch: 0,
+            }
+            .try_to_vec()
+            .unwrap()
+            .len()
+        })
+        .max()
+        .unwrap()
+            + 8
+    }
+
+    pub fn find_address(council: &Pubkey, index: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&council.to_bytes()[..32], Self::SEED, &index.to_le_bytes()],
+            program_id,
+        )
+    }
+
+    pub fn approval_count(&self) -> u32 {
+        self.approvals.count_ones()
+    }
+
+    /// not executed, not expired and approved by threshold members
+    pub fn check_executable(&self, threshold: u8, epoch: u64) -> ProgramResult {
+        if self.executed != Self::NOT_EXECUTED {
+            msg!("Proposal #{} is executed already", self.index);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if epoch >= self.expiry_epoch {
+            msg!(
+                "Proposal #{} expired at epoch {}",
+                self.index,
+                self.expiry_epoch
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        if self.approval_count() < threshold as u32 {
+            msg!(
+                "Proposal #{} has {} approvals. Needs {}",
+                self.index,
+                self.approval_count(),
+                threshold
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+}
+
+impl State {
+    /// Admin-only instructions accept the admin signature or,
+    /// when the admin is a council, its executed proposal of `action` in place of admin_authority.
+    /// The proposal can be used once, before its expiry epoch
+    pub fn check_admin_or_council(
+        &self,
+        admin_authority: &AccountInfo,
+        program_id: &Pubkey,
+        action: CouncilAction,
+    ) -> ProgramResult {
+        if admin_authority.is_signer {
+            return self.check_admin_authority(admin_authority.key);
+        }
+        self.use_council_proposal(admin_authority, program_id, action, Clock::get()?.epoch)
+    }
+
+    /// checks the executed and not expired proposal of `action` and marks it USED
+    fn use_council_proposal(
+        &self,
+        proposal_account: &AccountInfo,
+        program_id: &Pubkey,
+        action: CouncilAction,
+        epoch: u64,
+    ) -> ProgramResult {
+        check_owner_program(proposal_account, program_id, "admin_authority")?;
+        let mut proposal =
+            CouncilProposal::try_deserialize(&mut &proposal_account.data.borrow()[..])?;
+        check_address(&proposal.council, &self.admin_authority, "proposal council")?;
+        if proposal.executed != CouncilProposal::EXECUTED {
+            msg!(
+                "Proposal #{} is not executed or is used already",
+                proposal.index
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if proposal.action != action {
+            msg!(
+                "Proposal #{} is {:?}. Expected {:?}",
+                proposal.index,
+                proposal.action,
+                action
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        if epoch >= proposal.expiry_epoch {
+            msg!(
+                "Proposal #{} expired at epoch {}",
+                proposal.index,
+                proposal.expiry_epoch
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        msg!("Approved by council proposal #{}", proposal.index);
+        proposal.executed = CouncilProposal::USED;
+        let mut data = proposal_account.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        proposal.try_serialize(&mut std::io::Cursor::new(dst))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    fn proposal(council: Pubkey, action: CouncilAction) -> CouncilProposal {
+        CouncilProposal {
+            council,
+            index: 0,
+            proposer: Pubkey::default(),
+            action,
+            approvals: 0,
+            executed: CouncilProposal::NOT_EXECUTED,
+            expiry_epoch: 10 + CouncilProposal::LIFETIME_EPOCHS,
+        }
+    }
+
+    #[test]
+    fn test_check_members() {
+        let members: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        assert!(AdminCouncil::check_members(&members, 1).is_ok());
+        assert!(AdminCouncil::check_members(&members, 3).is_ok());
+        assert!(AdminCouncil::check_members(&members, 0).is_err());
+        assert!(AdminCouncil::check_members(&members, 4).is_err());
+        assert!(AdminCouncil::check_members(&[], 1).is_err());
+        assert!(AdminCouncil::check_members(&[members[0], members[1], members[0]], 2).is_err());
+        let too_many: Vec<Pubkey> = (0..AdminCouncil::MAX_MEMBERS + 1)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        assert!(AdminCouncil::check_members(&too_many, 2).is_err());
+    }
+
+    #[test]
+    fn test_threshold() {
+        let mut proposal = proposal(Pubkey::new_unique(), CouncilAction::InitEpochStats);
+        proposal.approvals = 1 << 2;
+        assert_eq!(proposal.approval_count(), 1);
+        assert!(proposal.check_executable(2, 10).is_err());
+        // approving twice counts once
+        proposal.approvals |= 1 << 2;
+        assert!(proposal.check_executable(2, 10).is_err());
+        proposal.approvals |= 1 << 0;
+        assert_eq!(proposal.approval_count(), 2);
+        assert!(proposal.check_executable(2, 10).is_ok());
+        assert!(proposal.check_executable(3, 10).is_err());
+        // expired
+        assert!(proposal
+            .check_executable(2, 10 + CouncilProposal::LIFETIME_EPOCHS - 1)
+            .is_ok());
+        assert!(proposal
+            .check_executable(2, 10 + CouncilProposal::LIFETIME_EPOCHS)
+            .is_err());
+        proposal.executed = CouncilProposal::EXECUTED;
+        assert!(proposal.check_executable(2, 10).is_err());
+    }
+
+    #[test]
+    fn test_check_admin_or_council() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+        let council = Pubkey::new_unique();
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.admin_authority = council;
+
+        let action = CouncilAction::SetRole {
+            role: Role::FeeManager,
+            authority: Pubkey::new_unique(),
+        };
+        let mut executed = proposal(council, action);
+        executed.executed = CouncilProposal::EXECUTED;
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![0; CouncilProposal::serialized_len()];
+        executed.try_serialize(&mut std::io::Cursor::new(&mut data[..]))?;
+        let proposal_account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+
+        // other action
+        assert!(state
+            .use_council_proposal(
+                &proposal_account,
+                &program_id,
+                CouncilAction::AcknowledgeCircuitBreaker,
+                10
+            )
+            .is_err());
+        // other program
+        assert!(state
+            .use_council_proposal(&proposal_account, &Pubkey::new_unique(), action, 10)
+            .is_err());
+        // expired
+        assert!(state
+            .use_council_proposal(
+                &proposal_account,
+                &program_id,
+                action,
+                10 + CouncilProposal::LIFETIME_EPOCHS
+            )
+            .is_err());
+        state.use_council_proposal(&proposal_account, &program_id, action, 10)?;
+        assert_eq!(
+            CouncilProposal::try_deserialize(&mut &proposal_account.data.borrow()[..])?.executed,
+            CouncilProposal::USED
+        );
+        // used once
+        assert!(state
+            .use_council_proposal(&proposal_account, &program_id, action, 10)
+            .is_err());
+
+        // the council itself can not sign
+        let mut council_lamports = 0;
+        let mut council_data = vec![];
+        let council_account = AccountInfo::new(
+            &council,
+            false,
+            false,
+            &mut council_lamports,
+            &mut council_data,
+            &program_id,
+            false,
+            0,
+        );
+        assert!(state
+            .use_council_proposal(&council_account, &program_id, action, 10)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_list_used_once() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+        let council = Pubkey::new_unique();
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.admin_authority = council;
+
+        let action = CouncilAction::MigrateList {
+            new_list: Pubkey::new_unique(),
+            additional_record_space: 8,
+        };
+        let mut executed = proposal(council, action);
+        executed.executed = CouncilProposal::EXECUTED;
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![0; CouncilProposal::serialized_len()];
+        executed.try_serialize(&mut std::io::Cursor::new(&mut data[..]))?;
+        let proposal_account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+        // every migrate_list call of a council needs its own proposal
+        state.use_council_proposal(&proposal_account, &program_id, action, 10)?;
+        assert!(state
+            .use_council_proposal(&proposal_account, &program_id, action, 10)
+            .is_err());
+        Ok(())
+    }
+}
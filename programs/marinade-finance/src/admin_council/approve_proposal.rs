@@ -0,0 +1,36 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    admin_council::CouncilProposal, checks::check_address, events::ProposalApprovedEvent,
+    ApproveProposal,
+};
+
+impl<'info> ApproveProposal<'info> {
+    // fn approve_proposal()
+    pub fn process(&mut self) -> ProgramResult {
+        check_address(
+            &self.proposal.council,
+            self.admin_council.to_account_info().key,
+            "proposal council",
+        )?;
+        if self.proposal.executed != CouncilProposal::NOT_EXECUTED {
+            msg!("Proposal #{} is executed already", self.proposal.index);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let member_index = self.admin_council.member_index(self.member.key)?;
+        self.proposal.approvals |= 1 << member_index;
+        emit!(ProposalApprovedEvent {
+            admin_council: self.proposal.council,
+            proposal: *self.proposal.to_account_info().key,
+            member: *self.member.key,
+            approval_count: self.proposal.approval_count(),
+        });
+        msg!(
+            "Proposal #{} approved by {}/{}",
+            self.proposal.index,
+            self.proposal.approval_count(),
+            self.admin_council.threshold
+        );
+        Ok(())
+    }
+}
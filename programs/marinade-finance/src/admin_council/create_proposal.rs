@@ -0,0 +1,81 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::system_program;
+
+use crate::{
+    admin_council::{CouncilAction, CouncilProposal},
+    checks::{check_address, check_owner_program},
+    events::ProposalCreatedEvent,
+    pda::create_pda_account,
+    CreateProposal,
+};
+
+impl<'info> CreateProposal<'info> {
+    // fn create_proposal()
+    // a member creates the proposal [council, "proposal", council.proposal_count] approving it.
+    // Only while the council is admin
+    pub fn process(&mut self, action: CouncilAction) -> ProgramResult {
+        self.admin_council
+            .check_state_address(&self.state.instance_address)?;
+        self.state
+            .check_admin_authority(self.admin_council.to_account_info().key)?;
+        let member_index = self.admin_council.member_index(self.proposer.key)?;
+        check_owner_program(&self.rent_payer, &system_program::ID, "rent_payer")?;
+        check_address(
+            self.system_program.key,
+            &system_program::ID,
+            "system_program",
+        )?;
+
+        let council_address = *self.admin_council.to_account_info().key;
+        let program_id = *self.state.to_account_info().owner;
+        let index = self.admin_council.proposal_count;
+        let (proposal_address, bump_seed) =
+            CouncilProposal::find_address(&council_address, index, &program_id);
+        check_address(self.proposal.key, &proposal_address, "proposal")?;
+        check_owner_program(&self.proposal, &system_program::ID, "proposal")?;
+
+        create_pda_account(
+            &self.rent_payer,
+            &self.proposal,
+            CouncilProposal::serialized_len(),
+            &program_id,
+            &self.system_program,
+            &self.rent,
+            &[
+                &council_address.to_bytes()[..32],
+                CouncilProposal::SEED,
+                &index.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+        self.admin_council.proposal_count += 1;
+
+        let expiry_epoch = self.clock.epoch + CouncilProposal::LIFETIME_EPOCHS;
+        msg!(
+            "Proposal #{} {:?} expires at epoch {}",
+            index,
+            action,
+            expiry_epoch
+        );
+        emit!(ProposalCreatedEvent {
+            admin_council: council_address,
+            proposal: *self.proposal.key,
+            index,
+            proposer: *self.proposer.key,
+            action,
+            expiry_epoch,
+        });
+        let proposal = CouncilProposal {
+            council: council_address,
+            index,
+            proposer: *self.proposer.key,
+            action,
+            approvals: 1 << member_index,
+            executed: CouncilProposal::NOT_EXECUTED,
+            expiry_epoch,
+        };
+        let mut data = self.proposal.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        proposal.try_serialize(&mut std::io::Cursor::new(dst))
+    }
+}
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    admin_council::{CouncilAction, CouncilProposal},
+    checks::check_address,
+    events::{AuthorityProposedEvent, ProposalExecutedEvent},
+    ExecuteProposal,
+};
+
+impl<'info> ExecuteProposal<'info> {
+    // fn execute_proposal()
+    // permissionless once the council is admin and the threshold is met before expiry_epoch.
+    // The actions go through the same queues as the admin ones.
+    // Admin instruction actions can be run once with the executed proposal as admin_authority
+    pub fn process(&mut self) -> ProgramResult {
+        let council_address = *self.admin_council.to_account_info().key;
+        self.admin_council
+            .check_state_address(&self.state.instance_address)?;
+        self.state.check_admin_authority(&council_address)?;
+        check_address(&self.proposal.council, &council_address, "proposal council")?;
+        self.pending_config
+            .check_state_address(&self.state.instance_address)?;
+        self.proposal
+            .check_executable(self.admin_council.threshold, self.clock.epoch)?;
+
+        match self.proposal.action {
+            CouncilAction::ConfigMarinade(params) => {
+                self.pending_config
+                    .queue_config(&mut self.state, params, self.clock.epoch)?
+            }
+            CouncilAction::ChangeAuthority(data) => {
+                self.state.propose_authority(data, self.clock.slot)?;
+                emit!(AuthorityProposedEvent {
+                    state: self.state.instance_address,
+                    pending: self.state.pending_authority,
+                });
+            }
+            CouncilAction::SetLpParams(lp_params) => {
+                self.pending_config
+                    .queue_lp_params(&mut self.state, lp_params, self.clock.epoch)?
+            }
+            action => {
+                msg!(
+                    "Run {:?} with proposal {} as admin_authority",
+                    action,
+                    self.proposal.to_account_info().key
+                );
+            }
+        }
+        self.proposal.executed = CouncilProposal::EXECUTED;
+        emit!(ProposalExecutedEvent {
+            state: self.state.instance_address,
+            admin_council: council_address,
+            proposal: *self.proposal.to_account_info().key,
+            index: self.proposal.index,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::system_program;
+
+use crate::{
+    admin_council::AdminCouncil,
+    checks::{check_address, check_owner_program},
+    events::InitAdminCouncilEvent,
+    pda::create_pda_account,
+    InitAdminCouncil,
+};
+
+impl<'info> InitAdminCouncil<'info> {
+    // fn init_admin_council()
+    // permissionless. The council has no power until it is accepted as admin_authority
+    pub fn process(
+        &mut self,
+        council_id: u64,
+        members: Vec<Pubkey>,
+        threshold: u8,
+    ) -> ProgramResult {
+        AdminCouncil::check_members(&members, threshold)?;
+        check_owner_program(&self.rent_payer, &system_program::ID, "rent_payer")?;
+        check_address(
+            self.system_program.key,
+            &system_program::ID,
+            "system_program",
+        )?;
+
+        let state_address = self.state.instance_address;
+        let program_id = *self.state.to_account_info().owner;
+        let (council_address, bump_seed) =
+            AdminCouncil::find_address(&state_address, council_id, &program_id);
+        check_address(self.admin_council.key, &council_address, "admin_council")?;
+        check_owner_program(&self.admin_council, &system_program::ID, "admin_council")?;
+
+        create_pda_account(
+            &self.rent_payer,
+            &self.admin_council,
+            AdminCouncil::serialized_len(),
+            &program_id,
+            &self.system_program,
+            &self.rent,
+            &[
+                &state_address.to_bytes()[..32],
+                AdminCouncil::SEED,
+                &council_id.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+
+        msg!(
+            "Admin council {}: {}-of-{}",
+            self.admin_council.key,
+            threshold,
+            members.len()
+        );
+        emit!(InitAdminCouncilEvent {
+            state: state_address,
+            admin_council: *self.admin_council.key,
+            threshold,
+            members: members.clone(),
+        });
+        let council = AdminCouncil {
+            state_address,
+            council_id,
+            threshold,
+            proposal_count: 0,
+            members,
+        };
+        let mut data = self.admin_council.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        council.try_serialize(&mut std::io::Cursor::new(dst))
+    }
+}
@@ -17,6 +17,20 @@ pub fn check_min_amount(amount: u64, min_amount: u64, action_name: &str) -> Prog
     }
 }
 
+pub fn check_min_out(amount_out: u64, min_amount_out: u64, field_name: &str) -> ProgramResult {
+    if amount_out >= min_amount_out {
+        Ok(())
+    } else {
+        msg!(
+            "{}: got {} but requested at least {}",
+            field_name,
+            amount_out,
+            min_amount_out,
+        );
+        Err(CommonError::SlippageExceeded.into())
+    }
+}
+
 pub fn check_address(
     actual_address: &Pubkey,
     reference_address: &Pubkey,
@@ -148,3 +162,48 @@ pub fn check_stake_amount_and_validator(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Fee, State};
+    use std::mem::MaybeUninit;
+
+    const SOL: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_check_min_out() {
+        // 0 = no check
+        assert!(check_min_out(0, 0, "out").is_ok());
+        assert!(check_min_out(SOL, SOL, "out").is_ok());
+        assert_eq!(
+            check_min_out(SOL - 1, SOL, "out"),
+            Err(CommonError::SlippageExceeded.into())
+        );
+    }
+
+    #[test]
+    fn test_min_out_of_deposit_and_liquid_unstake() -> ProgramResult {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.available_reserve_balance = 1_000 * SOL;
+        state.msol_supply = 800 * SOL; // msol_price = 1.25
+        state.liq_pool.lp_liquidity_target = 100 * SOL;
+        state.liq_pool.lp_min_fee = Fee::from_basis_points(30);
+        state.liq_pool.lp_max_fee = Fee::from_basis_points(300);
+
+        // deposit checks the mSOL from the liq-pool and the minted mSOL together
+        let (swap_msol, _, minted_msol) = state.calc_deposit(10 * SOL, 2 * SOL)?;
+        assert_eq!(swap_msol, 2 * SOL);
+        let msol_out = swap_msol + minted_msol;
+        assert_eq!(msol_out, 8 * SOL);
+        assert!(check_min_out(msol_out, 8 * SOL, "deposit mSOL").is_ok());
+        assert!(check_min_out(msol_out, 8 * SOL + 1, "deposit mSOL").is_err());
+
+        // liquid unstake checks the lamports after the fee
+        let (msol_fee, lamports_out) = state.calc_liquid_unstake(8 * SOL, 1_000 * SOL)?;
+        assert_eq!(msol_fee, Fee::from_basis_points(30).apply(8 * SOL));
+        assert!(check_min_out(lamports_out, 10 * SOL, "liquid unstake SOL").is_err());
+        assert!(check_min_out(lamports_out, lamports_out, "liquid unstake SOL").is_ok());
+        Ok(())
+    }
+}
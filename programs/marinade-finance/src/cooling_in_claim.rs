@@ -0,0 +1,169 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program::invoke_signed, program_pack::Pack, system_program};
+
+use crate::{
+    checks::{check_address, check_owner_program},
+    pda::create_pda_account,
+    CommonError,
+};
+
+/// mSOL of a stake account deposited while activating (cooling in).
+/// The mSOL is minted into the cooling-in escrow token account when the stake gets active
+/// (or is unstaked because its validator was removed) and the beneficiary claims it from there.
+/// PDA [state, "cooling_in", stake_account]
+#[account]
+#[derive(Debug, Default)]
+pub struct CoolingInClaim {
+    pub state_address: Pubkey, // instance of marinade state this claim belongs to
+    pub stake_account: Pubkey,
+    pub beneficiary: Pubkey, // owner of the mSOL account of the deposit
+    pub lamports: u64,       // deposited stake
+    pub msol_amount: u64,    // 0 while the stake is cooling in
+}
+
+impl CoolingInClaim {
+    pub const SEED: &'static [u8] = b"cooling_in";
+    /// Seed of the mSOL token account holding the minted and not claimed mSOL.
+    /// PDA [state, "cooling_in_escrow"] owned by itself
+    pub const ESCROW_SEED: &'static [u8] = b"cooling_in_escrow";
+
+    pub fn serialized_len() -> usize {
+        Self::default().try_to_vec().unwrap().len() + 8
+    }
+
+    pub fn find_address(
+        state: &Pubkey,
+        stake_account: &Pubkey,
+        program_id: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                &state.to_bytes()[..32],
+                Self::SEED,
+                &stake_account.to_bytes()[..32],
+            ],
+            program_id,
+        )
+    }
+
+    pub fn find_escrow_address(state: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::ESCROW_SEED], program_id)
+    }
+
+    pub fn with_escrow_seeds<R, F: FnOnce(&[&[u8]]) -> R>(
+        state: &Pubkey,
+        program_id: &Pubkey,
+        escrow: &Pubkey,
+        f: F,
+    ) -> Result<R, ProgramError> {
+        let (escrow_address, bump_seed) = Self::find_escrow_address(state, program_id);
+        check_address(escrow, &escrow_address, "cooling_in_escrow")?;
+        Ok(f(&[
+            &state.to_bytes()[..32],
+            Self::ESCROW_SEED,
+            &[bump_seed],
+        ]))
+    }
+
+    /// Creates the claim PDA for (self.state_address, self.stake_account)
+    /// paid by rent_payer and writes self into it
+    pub fn create_pda<'info>(
+        &self,
+        program_id: &Pubkey,
+        claim_account: &AccountInfo<'info>,
+        rent_payer: &AccountInfo<'info>,
+        system_program: &AccountInfo<'info>,
+        rent: &Rent,
+    ) -> ProgramResult {
+        let (claim_address, bump_seed) =
+            Self::find_address(&self.state_address, &self.stake_account, program_id);
+        check_address(claim_account.key, &claim_address, "cooling_in_claim")?;
+        check_owner_program(claim_account, &system_program::ID, "cooling_in_claim")?;
+        create_pda_account(
+            rent_payer,
+            claim_account,
+            Self::serialized_len(),
+            program_id,
+            system_program,
+            rent,
+            &[
+                &self.state_address.to_bytes()[..32],
+                Self::SEED,
+                &self.stake_account.to_bytes()[..32],
+                &[bump_seed],
+            ],
+        )?;
+        self.write(claim_account)
+    }
+
+    /// Creates the escrow token account at the first cooling-in deposit
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_escrow_if_needed<'info>(
+        state: &Pubkey,
+        program_id: &Pubkey,
+        escrow: &AccountInfo<'info>,
+        msol_mint: &AccountInfo<'info>,
+        rent_payer: &AccountInfo<'info>,
+        system_program: &AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+        rent: &Sysvar<'info, Rent>,
+    ) -> ProgramResult {
+        if escrow.owner == &spl_token::ID {
+            return Self::with_escrow_seeds(state, program_id, escrow.key, |_| ());
+        }
+        Self::with_escrow_seeds(state, program_id, escrow.key, |seeds| {
+            create_pda_account(
+                rent_payer,
+                escrow,
+                spl_token::state::Account::LEN,
+                &spl_token::ID,
+                system_program,
+                rent,
+                seeds,
+            )?;
+            invoke_signed(
+                &spl_token::instruction::initialize_account(
+                    &spl_token::ID,
+                    escrow.key,
+                    msol_mint.key,
+                    escrow.key,
+                )?,
+                &[
+                    token_program.clone(),
+                    escrow.clone(),
+                    msol_mint.clone(),
+                    escrow.clone(),
+                    rent.to_account_info(),
+                ],
+                &[],
+            )
+        })?
+    }
+
+    pub fn read(claim_account: &AccountInfo) -> Result<Self, ProgramError> {
+        Self::try_deserialize(&mut &claim_account.data.borrow()[..])
+    }
+
+    pub fn write(&self, claim_account: &AccountInfo) -> ProgramResult {
+        let mut data = claim_account.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        self.try_serialize(&mut std::io::Cursor::new(dst))
+    }
+
+    /// Checks the claim is an activated claim of this marinade instance held by beneficiary
+    pub fn check_held_by(&self, state_address: &Pubkey, beneficiary: &Pubkey) -> ProgramResult {
+        if &self.state_address != state_address {
+            msg!("Claim has wrong marinade instance {}", self.state_address);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if self.msol_amount == 0 {
+            msg!("Stake {} is cooling in", self.stake_account);
+            return Err(CommonError::StakeAccountIsCoolingIn.into());
+        }
+        if &self.beneficiary != beneficiary {
+            msg!("wrong beneficiary");
+            return Err(CommonError::WrongBeneficiary.into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+use crate::{
+    checks::{check_address, check_owner_program},
+    error::CommonError,
+};
+use anchor_lang::prelude::*;
+
+pub mod init_epoch_stats;
+
+/// Totals of one epoch, in lamports unless named as mSOL
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct EpochStatsRecord {
+    pub epoch: u64,
+    pub deposited: u64, // deposit SOL (including the part swapped in the liq pool) and stake accounts
+    pub liquid_unstaked: u64, // SOL paid by liquid unstakes
+    pub delayed_unstaked: u64, // SOL of the tickets created by order_unstake and not cancelled
+    pub staked: u64,    // moved from the reserve into new stake accounts
+    pub deactivated: u64,
+    pub rewards: u64,
+    pub slashed: u64,
+    pub treasury_msol_fees: u64, // mSOL minted to treasury for reward_fee
+    pub lp_msol_fees: u64,       // liquid unstake fees kept by the liq pool
+    // msol_price at the first and at the last record of the epoch
+    pub msol_price_start: u64,
+    pub msol_price_end: u64,
+}
+
+/// Ring buffer of the last CAPACITY epochs statistics, for dashboards and APY.
+/// PDA [state, "epoch_stats"]
+#[account]
+#[derive(Debug, Default)]
+pub struct EpochStats {
+    pub state_address: Pubkey, // instance of marinade state these stats belong to
+    /// index of the current epoch in records
+    pub head: u32,
+    pub records: Vec<EpochStatsRecord>,
+}
+
+impl EpochStats {
+    pub const SEED: &'static [u8] = b"epoch_stats";
+    pub const CAPACITY: usize = 32;
+
+    /// space for the full ring buffer
+    pub fn serialized_len() -> usize {
+        Self {
+            records: vec![EpochStatsRecord::default(); Self::CAPACITY],
+            ..Default::default()
+        }
+        .try_to_vec()
+        .unwrap()
+        .len()
+            + 8
+    }
+
+    pub fn find_address(state: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::SEED], program_id)
+    }
+
+    pub fn check_state_address(&self, state_address: &Pubkey) -> ProgramResult {
+        check_address(state_address, &self.state_address, "epoch_stats state")
+    }
+
+    /// Applies `on` (with the current epoch) to the stats in the epoch_stats account
+    /// if the client passed it. It is an optional trailing remaining account of the instructions,
+    /// so clients not knowing it and instances without epoch stats are not affected
+    pub fn record<F>(
+        epoch_stats: Option<&AccountInfo>,
+        state_address: &Pubkey,
+        program_id: &Pubkey,
+        on: F,
+    ) -> ProgramResult
+    where
+        F: FnOnce(&mut Self, u64) -> Result<(), CommonError>,
+    {
+        let epoch_stats = match epoch_stats {
+            Some(epoch_stats) => epoch_stats,
+            None => return Ok(()),
+        };
+        check_owner_program(epoch_stats, program_id, "epoch_stats")?;
+        let mut stats = Self::try_deserialize(&mut &epoch_stats.data.borrow()[..])?;
+        stats.check_state_address(state_address)?;
+        on(&mut stats, Clock::get()?.epoch)?;
+        let mut data = epoch_stats.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        stats.try_serialize(&mut std::io::Cursor::new(dst))
+    }
+
+    /// record of the epoch. The first record of a new epoch replaces the oldest one when full
+    fn current(&mut self, epoch: u64, msol_price: u64) -> &mut EpochStatsRecord {
+        let head = self.head as usize;
+        if self.records.is_empty() || self.records[head].epoch != epoch {
+            let record = EpochStatsRecord {
+                epoch,
+                msol_price_start: msol_price,
+                ..Default::default()
+            };
+            if self.records.len() < Self::CAPACITY {
+                self.records.push(record);
+                self.head = (self.records.len() - 1) as u32;
+            } else {
+                self.head = ((head + 1) % Self::CAPACITY) as u32;
+                self.records[self.head as usize] = record;
+            }
+        }
+        let record = &mut self.records[self.head as usize];
+        record.msol_price_end = msol_price;
+        record
+    }
+
+    fn add(total: &mut u64, amount: u64) -> Result<(), CommonError> {
+        *total = total
+            .checked_add(amount)
+            .ok_or(CommonError::CalculationFailure)?;
+        Ok(())
+    }
+
+    pub fn on_deposit(
+        &mut self,
+        epoch: u64,
+        msol_price: u64,
+        lamports: u64,
+    ) -> Result<(), CommonError> {
+        Self::add(&mut self.current(epoch, msol_price).deposited, lamports)
+    }
+
+    pub fn on_liquid_unstake(
+        &mut self,
+        epoch: u64,
+        msol_price: u64,
+        lamports: u64,
+        lp_msol_fee: u64,
+    ) -> Result<(), CommonError> {
+        let record = self.current(epoch, msol_price);
+        Self::add(&mut record.liquid_unstaked, lamports)?;
+        Self::add(&mut record.lp_msol_fees, lp_msol_fee)
+    }
+
+    pub fn on_order_unstake(
+        &mut self,
+        epoch: u64,
+        msol_price: u64,
+        lamports: u64,
+    ) -> Result<(), CommonError> {
+        Self::add(
+            &mut self.current(epoch, msol_price).delayed_unstaked,
+            lamports,
+        )
+    }
+
+    /// cancel_ticket is only possible in the epoch the ticket was ordered
+    pub fn on_cancel_ticket(
+        &mut self,
+        epoch: u64,
+        msol_price: u64,
+        lamports: u64,
+    ) -> Result<(), CommonError> {
+        let record = self.current(epoch, msol_price);
+        // saturating: the ticket could have been ordered before epoch_stats was initialized
+        record.delayed_unstaked = record.delayed_unstaked.saturating_sub(lamports);
+        Ok(())
+    }
+
+    pub fn on_stake_reserve(
+        &mut self,
+        epoch: u64,
+        msol_price: u64,
+        lamports: u64,
+    ) -> Result<(), CommonError> {
+        Self::add(&mut self.current(epoch, msol_price).staked, lamports)
+    }
+
+    pub fn on_deactivate_stake(
+        &mut self,
+        epoch: u64,
+        msol_price: u64,
+        lamports: u64,
+    ) -> Result<(), CommonError> {
+        Self::add(&mut self.current(epoch, msol_price).deactivated, lamports)
+    }
+
+    /// update_active and update_deactivated, msol_price is the updated one
+    pub fn on_update(
+        &mut self,
+        epoch: u64,
+        msol_price: u64,
+        rewards: u64,
+        slashed: u64,
+        treasury_msol_fees: u64,
+    ) -> Result<(), CommonError> {
+        let record = self.current(epoch, msol_price);
+        Self::add(&mut record.rewards, rewards)?;
+        Self::add(&mut record.slashed, slashed)?;
+        Self::add(&mut record.treasury_msol_fees, treasury_msol_fees)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_new_epoch() {
+        let mut stats = EpochStats::default();
+        stats.on_deposit(10, 100, 5).unwrap();
+        stats.on_deposit(10, 101, 7).unwrap();
+        assert_eq!(stats.records.len(), 1);
+        assert_eq!(stats.head, 0);
+        assert_eq!(stats.records[0].deposited, 12);
+        assert_eq!(stats.records[0].msol_price_start, 100);
+        assert_eq!(stats.records[0].msol_price_end, 101);
+
+        stats.on_order_unstake(11, 102, 3).unwrap();
+        assert_eq!(stats.records.len(), 2);
+        assert_eq!(stats.head, 1);
+        assert_eq!(stats.records[1].epoch, 11);
+        assert_eq!(stats.records[1].deposited, 0);
+        assert_eq!(stats.records[1].delayed_unstaked, 3);
+        assert_eq!(stats.records[1].msol_price_start, 102);
+        // the previous epoch is kept
+        assert_eq!(stats.records[0].msol_price_end, 101);
+    }
+
+    #[test]
+    fn test_current_wrap_around() {
+        let mut stats = EpochStats::default();
+        let capacity = EpochStats::CAPACITY as u64;
+        for epoch in 0..capacity {
+            stats.on_deposit(epoch, 1, epoch).unwrap();
+        }
+        assert_eq!(stats.records.len(), EpochStats::CAPACITY);
+        assert_eq!(stats.head as usize, EpochStats::CAPACITY - 1);
+
+        // the oldest epoch (0) is replaced
+        stats.on_deposit(capacity, 1, 1).unwrap();
+        assert_eq!(stats.records.len(), EpochStats::CAPACITY);
+        assert_eq!(stats.head, 0);
+        assert_eq!(
+            stats.records[0],
+            EpochStatsRecord {
+                epoch: capacity,
+                deposited: 1,
+                msol_price_start: 1,
+                msol_price_end: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stats.records[1].epoch, 1);
+
+        // same epoch keeps the head
+        stats.on_deposit(capacity, 1, 1).unwrap();
+        assert_eq!(stats.head, 0);
+        assert_eq!(stats.records[0].deposited, 2);
+
+        stats.on_deposit(capacity + 1, 1, 1).unwrap();
+        assert_eq!(stats.head, 1);
+        assert_eq!(stats.records[1].epoch, capacity + 1);
+        assert_eq!(stats.records[1].deposited, 1);
+        assert_eq!(stats.records[2].epoch, 2);
+    }
+
+    #[test]
+    fn test_cancel_ticket() {
+        let mut stats = EpochStats::default();
+        stats.on_order_unstake(5, 1, 10).unwrap();
+        stats.on_cancel_ticket(5, 1, 4).unwrap();
+        assert_eq!(stats.records[0].delayed_unstaked, 6);
+        stats.on_cancel_ticket(5, 1, 100).unwrap();
+        assert_eq!(stats.records[0].delayed_unstaked, 0);
+    }
+
+    #[test]
+    fn test_serialized_len() {
+        let stats = EpochStats {
+            records: vec![EpochStatsRecord::default(); EpochStats::CAPACITY],
+            ..Default::default()
+        };
+        let mut data = vec![0u8; EpochStats::serialized_len()];
+        stats
+            .try_serialize(&mut std::io::Cursor::new(&mut data[..]))
+            .unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::system_program;
+
+use crate::{
+    admin_council::CouncilAction,
+    checks::{check_address, check_owner_program},
+    epoch_stats::EpochStats,
+    pda::create_pda_account,
+    InitEpochStats,
+};
+
+impl<'info> InitEpochStats<'info> {
+    // fn init_epoch_stats()
+    pub fn process(&mut self) -> ProgramResult {
+        self.state.check_admin_or_council(
+            &self.admin_authority,
+            self.state.to_account_info().owner,
+            CouncilAction::InitEpochStats,
+        )?;
+        check_owner_program(&self.rent_payer, &system_program::ID, "rent_payer")?;
+        check_address(
+            self.system_program.key,
+            &system_program::ID,
+            "system_program",
+        )?;
+
+        let state_address = self.state.instance_address;
+        let program_id = *self.state.to_account_info().owner;
+        let (epoch_stats_address, bump_seed) =
+            EpochStats::find_address(&state_address, &program_id);
+        check_address(self.epoch_stats.key, &epoch_stats_address, "epoch_stats")?;
+        check_owner_program(&self.epoch_stats, &system_program::ID, "epoch_stats")?;
+
+        create_pda_account(
+            &self.rent_payer,
+            &self.epoch_stats,
+            EpochStats::serialized_len(),
+            &program_id,
+            &self.system_program,
+            &self.rent,
+            &[
+                &state_address.to_bytes()[..32],
+                EpochStats::SEED,
+                &[bump_seed],
+            ],
+        )?;
+
+        let epoch_stats = EpochStats {
+            state_address,
+            ..Default::default()
+        };
+        let mut data = self.epoch_stats.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        epoch_stats.try_serialize(&mut std::io::Cursor::new(dst))
+    }
+}
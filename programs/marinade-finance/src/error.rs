@@ -77,6 +77,27 @@ pub enum CommonError {
     #[msg("1108 Stake Account is emergency unstaking")]
     StakeAccountIsEmergencyUnstaking = 4060,
 
+    #[msg("1109 Ticket can not be cancelled anymore")]
+    TicketNotCancellable = 4061,
+
+    #[msg("1110 Slippage exceeded. Output lower than requested minimum")]
+    SlippageExceeded = 4062,
+
+    #[msg("1111 Stake Account is cooling in")]
+    StakeAccountIsCoolingIn = 4063,
+
+    #[msg("1112 Operation is paused")]
+    OperationPaused = 4064,
+
+    #[msg("1113 Restricted mode. Circuit breaker not acknowledged")]
+    RestrictedMode = 4065,
+
+    #[msg("1114 mSOL price not recorded for the epoch")]
+    PriceNotRecorded = 4066,
+
+    #[msg("1115 No change requested")]
+    NoChangeRequested = 4067,
+
     #[msg("1199 Insufficient Liquidity in the Liquidity Pool")]
     InsufficientLiquidity = 4205,
 
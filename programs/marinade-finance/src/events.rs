@@ -0,0 +1,519 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    admin_council::CouncilAction, roles::Role, state::change_authority::PendingAuthority,
+    validator_system::ScoreFormula, ConfigMarinadeParams, Fee, LpParams,
+};
+
+//-----------------------------------------------------
+// admin
+//-----------------------------------------------------
+
+#[event]
+pub struct InitializeEvent {
+    pub state: Pubkey,
+    pub msol_mint: Pubkey,
+    pub admin_authority: Pubkey,
+    pub reward_fee: Fee,
+}
+
+#[event]
+pub struct MigrateStateEvent {
+    pub state: Pubkey,
+    /// account holding the state from now on. The same as state when it was migrated in place
+    pub new_state: Pubkey,
+    pub old_layout_version: u8,
+    pub new_layout_version: u8,
+}
+
+#[event]
+pub struct MigrateListEvent {
+    pub state: Pubkey,
+    pub old_list: Pubkey,
+    pub new_list: Pubkey,
+    pub item_size: u32,
+    /// migration is done when copied_count = count
+    pub copied_count: u32,
+    pub count: u32,
+}
+
+#[event]
+pub struct AuthorityProposedEvent {
+    pub state: Pubkey,
+    /// is_proposed = 0 when the proposal was cancelled
+    pub pending: PendingAuthority,
+}
+
+#[event]
+pub struct AuthorityAcceptedEvent {
+    pub state: Pubkey,
+    pub accepted: PendingAuthority,
+}
+
+#[event]
+pub struct RoleChangedEvent {
+    pub state: Pubkey,
+    pub role: Role,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+/// pause and unpause
+#[event]
+pub struct PausedOperationsChangedEvent {
+    pub state: Pubkey,
+    pub authority: Pubkey,
+    pub old_paused_operations: u64,
+    pub new_paused_operations: u64,
+}
+
+/// fee increases of config_marinade and set_lp_params (including the ones executed by the admin council)
+#[event]
+pub struct ConfigQueuedEvent {
+    pub state: Pubkey,
+    pub config: ConfigMarinadeParams,
+    pub lp_params: Option<LpParams>,
+    /// effective epoch of all the queued changes
+    pub effective_epoch: u64,
+}
+
+/// config_marinade and set_lp_params changes applied immediately, or apply_pending_config
+#[event]
+pub struct ConfigChangedEvent {
+    pub state: Pubkey,
+    pub config: ConfigMarinadeParams,
+    pub lp_params: Option<LpParams>,
+}
+
+#[event]
+pub struct InitPendingConfigEvent {
+    pub state: Pubkey,
+    pub pending_config: Pubkey,
+}
+
+#[event]
+pub struct InitAdminCouncilEvent {
+    pub state: Pubkey,
+    pub admin_council: Pubkey,
+    pub threshold: u8,
+    pub members: Vec<Pubkey>,
+}
+
+#[event]
+pub struct ProposalCreatedEvent {
+    pub admin_council: Pubkey,
+    pub proposal: Pubkey,
+    pub index: u64,
+    pub proposer: Pubkey,
+    pub action: CouncilAction,
+    pub expiry_epoch: u64,
+}
+
+#[event]
+pub struct ProposalApprovedEvent {
+    pub admin_council: Pubkey,
+    pub proposal: Pubkey,
+    pub member: Pubkey,
+    pub approval_count: u32,
+}
+
+#[event]
+pub struct ProposalExecutedEvent {
+    pub state: Pubkey,
+    pub admin_council: Pubkey,
+    pub proposal: Pubkey,
+    pub index: u64,
+}
+
+/// The update crank entered the restricted mode: deposits and liquid unstakes are rejected
+/// until admin calls acknowledge_circuit_breaker
+#[event]
+pub struct CircuitBreakerAlert {
+    pub state: Pubkey,
+    pub epoch: u64,
+    /// msol_price at the start of the epoch
+    pub price_band_reference: u64,
+    pub msol_price: u64,
+    /// mSOL found in the mint supply but not minted by marinade
+    pub msol_minted_outside: u64,
+}
+
+#[event]
+pub struct CircuitBreakerAcknowledgedEvent {
+    pub state: Pubkey,
+    pub msol_price: u64,
+}
+
+//-----------------------------------------------------
+// validators
+//-----------------------------------------------------
+
+#[event]
+pub struct AddValidatorEvent {
+    pub state: Pubkey,
+    pub validator: Pubkey,
+    pub index: u32,
+    pub score: u32,
+}
+
+#[event]
+pub struct RemoveValidatorEvent {
+    pub state: Pubkey,
+    pub validator: Pubkey,
+    pub index: u32,
+    /// duplication flag rent returned to operational_sol_account
+    pub rent_returned: u64,
+}
+
+/// set_validator_score and refresh_validator_score
+#[event]
+pub struct SetValidatorScoreEvent {
+    pub state: Pubkey,
+    pub validator: Pubkey,
+    pub index: u32,
+    pub score_change: ScoreChange,
+}
+
+#[derive(Clone, Copy, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct ScoreChange {
+    pub old: u32,
+    pub new: u32,
+}
+
+#[event]
+pub struct ScoreOverrideClearedEvent {
+    pub state: Pubkey,
+    pub validator: Pubkey,
+    pub index: u32,
+}
+
+#[event]
+pub struct ConfigValidatorSystemEvent {
+    pub state: Pubkey,
+    pub extra_runs: u32,
+}
+
+#[event]
+pub struct ConfigScoreFormulaEvent {
+    pub state: Pubkey,
+    pub score_formula: ScoreFormula,
+}
+
+//-----------------------------------------------------
+// user
+//-----------------------------------------------------
+
+/// deposit, deposit_with_min_out, deposit_with_referral and deposit_directed
+#[event]
+pub struct DepositEvent {
+    pub state: Pubkey,
+    pub sol_owner: Pubkey,
+    pub user_msol_account: Pubkey,
+    pub lamports: u64,
+    /// mSOL bought from the liq pool for lamports_to_liq_pool
+    pub msol_swapped: u64,
+    pub lamports_to_liq_pool: u64,
+    /// mSOL minted for the lamports sent to the reserve
+    pub msol_minted: u64,
+    pub msol_price: u64,
+}
+
+/// deposit_wsol and add_liquidity_wsol, before the DepositEvent or AddLiquidityEvent
+#[event]
+pub struct UnwrapWsolEvent {
+    pub state: Pubkey,
+    pub wsol_account: Pubkey,
+    /// reserve or liq-pool SOL leg receiving the SOL
+    pub unwrapped_to: Pubkey,
+    pub lamports: u64,
+}
+
+#[event]
+pub struct DirectedDepositEvent {
+    pub state: Pubkey,
+    pub validator: Pubkey,
+    pub validator_index: u32,
+    pub lamports: u64,
+    /// lamports that reached the reserve (not swapped with the liq-pool)
+    pub directed_lamports: u64,
+    pub expiry_epoch: u64,
+}
+
+#[event]
+pub struct DirectedStakeExpiredEvent {
+    pub state: Pubkey,
+    pub validator: Pubkey,
+    pub validator_index: u32,
+    pub lamports: u64,
+}
+
+/// deposit_stake_account and deposit_stake_account_merge
+#[event]
+pub struct DepositStakeAccountEvent {
+    pub state: Pubkey,
+    pub stake: Pubkey,
+    /// stake the deposited one was merged into, Pubkey::default() if not merged
+    pub merged_into: Pubkey,
+    pub validator: Pubkey,
+    pub delegated: u64,
+    /// lamports above delegation + rent moved to the reserve
+    pub extra_lamports: u64,
+    /// mSOL is minted into the cooling-in escrow by update_active once the stake is active
+    pub is_cooling_in: bool,
+    pub msol_minted: u64,
+    pub msol_price: u64,
+}
+
+#[event]
+pub struct WithdrawStakeAccountEvent {
+    pub state: Pubkey,
+    pub stake: Pubkey,
+    pub split_stake: Pubkey,
+    pub beneficiary: Pubkey,
+    pub msol_burned: u64,
+    pub lamports: u64,
+    pub msol_price: u64,
+}
+
+/// liquid_unstake, liquid_unstake_with_min_out, liquid_unstake_exact_out
+/// and liquid_unstake_with_referral
+#[event]
+pub struct LiquidUnstakeEvent {
+    pub state: Pubkey,
+    pub msol_owner: Pubkey,
+    pub sol_destination: Pubkey,
+    pub msol_amount: u64,
+    pub lamports: u64,
+    pub msol_fee: u64,
+    /// part of msol_fee sent to treasury (including partner_msol_cut)
+    pub treasury_msol_cut: u64,
+    pub partner_msol_cut: u64,
+    pub msol_price: u64,
+}
+
+#[event]
+pub struct AddLiquidityEvent {
+    pub state: Pubkey,
+    pub sol_owner: Pubkey,
+    pub user_lp_account: Pubkey,
+    pub lamports: u64,
+    pub lp_minted: u64,
+}
+
+#[event]
+pub struct RemoveLiquidityEvent {
+    pub state: Pubkey,
+    pub user_lp_account: Pubkey,
+    pub lp_burned: u64,
+    pub lamports_out: u64,
+    pub msol_out: u64,
+}
+
+#[event]
+pub struct InitReferralAccountEvent {
+    pub state: Pubkey,
+    pub referral_state: Pubkey,
+    pub partner_account: Pubkey,
+    pub partner_share: Fee,
+}
+
+#[event]
+pub struct UpdateReferralEvent {
+    pub state: Pubkey,
+    pub referral_state: Pubkey,
+    pub partner_share: Fee,
+}
+
+/// deposit_with_referral, after its DepositEvent
+#[event]
+pub struct ReferralDepositEvent {
+    pub state: Pubkey,
+    pub referral_state: Pubkey,
+    pub partner_account: Pubkey,
+    pub lamports: u64,
+}
+
+/// liquid_unstake_with_referral, after its LiquidUnstakeEvent
+#[event]
+pub struct ReferralLiquidUnstakeEvent {
+    pub state: Pubkey,
+    pub referral_state: Pubkey,
+    pub partner_account: Pubkey,
+    pub msol_amount: u64,
+    pub msol_fee: u64,
+    /// paid to msol_token_partner_account
+    pub partner_msol_cut: u64,
+}
+
+//-----------------------------------------------------
+// delayed unstake
+//-----------------------------------------------------
+
+/// order_unstake and order_unstake_pda
+#[event]
+pub struct OrderUnstakeEvent {
+    pub state: Pubkey,
+    pub ticket: Pubkey,
+    pub beneficiary: Pubkey,
+    pub msol_burned: u64,
+    pub lamports: u64,
+    pub created_epoch: u64,
+    pub msol_price: u64,
+}
+
+/// claim, partial_claim and claim_many (one event per ticket)
+#[event]
+pub struct ClaimEvent {
+    pub state: Pubkey,
+    pub ticket: Pubkey,
+    pub beneficiary: Pubkey,
+    pub lamports: u64,
+    /// left in the ticket after a partial claim. 0 = ticket closed
+    pub remaining_lamports: u64,
+}
+
+/// claim_cooling_in
+#[event]
+pub struct CoolingInClaimedEvent {
+    pub state: Pubkey,
+    pub claim: Pubkey,
+    pub stake_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub msol_amount: u64,
+}
+
+#[event]
+pub struct TransferTicketEvent {
+    pub state: Pubkey,
+    pub ticket: Pubkey,
+    pub old_beneficiary: Pubkey,
+    pub new_beneficiary: Pubkey,
+}
+
+#[event]
+pub struct SplitTicketEvent {
+    pub state: Pubkey,
+    pub ticket: Pubkey,
+    pub new_ticket: Pubkey,
+    pub lamports: u64,
+}
+
+#[event]
+pub struct CancelTicketEvent {
+    pub state: Pubkey,
+    pub ticket: Pubkey,
+    pub beneficiary: Pubkey,
+    pub lamports: u64,
+    pub msol_minted: u64,
+    pub msol_price: u64,
+}
+
+//-----------------------------------------------------
+// crank
+//-----------------------------------------------------
+
+#[event]
+pub struct StakeReserveEvent {
+    pub state: Pubkey,
+    pub stake_account: Pubkey,
+    pub stake_index: u32,
+    pub validator: Pubkey,
+    pub validator_index: u32,
+    pub amount: u64,
+    pub reserve_balance: u64,
+}
+
+#[event]
+pub struct UpdateActiveEvent {
+    pub state: Pubkey,
+    pub stake_account: Pubkey,
+    pub stake_index: u32,
+    pub validator: Pubkey,
+    pub validator_index: u32,
+    pub delegation_change: DelegationChange,
+    /// mSOL minted to treasury for reward_fee of the rewards
+    pub msol_fees: u64,
+    /// lamports above delegation + rent moved to the reserve
+    pub extra_lamports: u64,
+    /// mSOL minted into the cooling-in escrow when the cooling-in stake became active
+    pub cooling_in_msol_minted: u64,
+    pub msol_price_change: PriceChange,
+}
+
+#[event]
+pub struct UpdateDeactivatedEvent {
+    pub state: Pubkey,
+    pub stake_account: Pubkey,
+    pub stake_index: u32,
+    pub delegation_change: DelegationChange,
+    pub msol_fees: u64,
+    pub extra_lamports: u64,
+    /// lamports withdrawn to the reserve
+    pub withdrawn: u64,
+    pub msol_price_change: PriceChange,
+}
+
+#[derive(Clone, Copy, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct DelegationChange {
+    pub rewards: u64,
+    pub slashed: u64,
+}
+
+#[derive(Clone, Copy, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct PriceChange {
+    pub old: u64,
+    pub new: u64,
+}
+
+#[event]
+pub struct DeactivateStakeEvent {
+    pub state: Pubkey,
+    pub stake_account: Pubkey,
+    pub stake_index: u32,
+    /// Pubkey::default() when the whole stake account was deactivated
+    pub split_stake_account: Pubkey,
+    pub lamports: u64,
+}
+
+#[event]
+pub struct EmergencyUnstakeEvent {
+    pub state: Pubkey,
+    pub stake_account: Pubkey,
+    pub stake_index: u32,
+    pub validator: Pubkey,
+    pub lamports: u64,
+}
+
+/// unstake_cooling_in
+#[event]
+pub struct CoolingInUnstakedEvent {
+    pub state: Pubkey,
+    pub stake_account: Pubkey,
+    pub stake_index: u32,
+    pub validator: Pubkey,
+    pub lamports: u64,
+    /// credited to the claim of the stake
+    pub msol_amount: u64,
+}
+
+#[event]
+pub struct PartialUnstakeEvent {
+    pub state: Pubkey,
+    pub stake_account: Pubkey,
+    pub stake_index: u32,
+    /// Pubkey::default() when the whole stake account was deactivated
+    pub split_stake_account: Pubkey,
+    pub validator: Pubkey,
+    pub lamports: u64,
+}
+
+#[event]
+pub struct MergeStakesEvent {
+    pub state: Pubkey,
+    pub destination_stake: Pubkey,
+    pub source_stake: Pubkey,
+    pub validator: Pubkey,
+    pub merged_lamports: u64,
+    /// source rent sent to operational_sol_account
+    pub to_operational_sol_account: u64,
+}
@@ -1,7 +1,9 @@
 #![cfg_attr(not(debug_assertions), deny(warnings))]
 
 use anchor_lang::prelude::*;
+use admin_council::{AdminCouncil, CouncilAction, CouncilProposal};
 use anchor_spl::token::{Mint, TokenAccount};
+use cooling_in_claim::CoolingInClaim;
 use error::CommonError;
 use stake_wrapper::StakeWrapper;
 use std::{
@@ -10,19 +12,32 @@ use std::{
     ops::{Deref, DerefMut},
     str::FromStr,
 };
+use referral::ReferralState;
+use roles::Role;
+use state::pending_config::PendingConfig;
 use ticket_account::TicketAccountData;
+use validator_system::ScoreFormula;
 
+pub mod admin_council;
 pub mod calc;
 pub mod checks;
+pub mod cooling_in_claim;
+pub mod epoch_stats;
 pub mod error;
+pub mod events;
 pub mod liq_pool;
 pub mod list;
 pub mod located;
+pub mod pda;
+pub mod referral;
+pub mod roles;
+pub mod return_data;
 pub mod stake_system;
 pub mod stake_wrapper;
 pub mod state;
 pub mod ticket_account;
 pub mod validator_system;
+pub mod vote_account;
 
 pub use state::State;
 
@@ -53,11 +68,10 @@ fn test_id() {
 }
 
 pub const MAX_REWARD_FEE: u32 = 1_000; //basis points, 10% max reward fee
+pub const MAX_MSOL_PRICE_INCREASE: u32 = 1_000; //basis points, 10% max per epoch price increase band
 
 fn check_context<T>(ctx: &Context<T>) -> ProgramResult {
-    if !check_id(ctx.program_id) {
-        return Err(CommonError::InvalidProgramId.into());
-    }
+    check_program_id(ctx)?;
     //make sure there are no extra accounts
     if !ctx.remaining_accounts.is_empty() {
         return Err(CommonError::UnexpectedAccount.into());
@@ -66,6 +80,44 @@ fn check_context<T>(ctx: &Context<T>) -> ProgramResult {
     Ok(())
 }
 
+/// for instructions receiving a variable list of accounts in remaining_accounts
+/// Only `ID` is accepted unless built with the `no-program-id-check` feature
+/// (PDAs are derived from the actual program id, so test deployments work with it)
+fn check_program_id<T>(ctx: &Context<T>) -> ProgramResult {
+    if !cfg!(feature = "no-program-id-check") && !check_id(ctx.program_id) {
+        return Err(CommonError::InvalidProgramId.into());
+    }
+
+    Ok(())
+}
+
+/// for instructions recording EpochStats: splits the optional epoch_stats account
+/// off the end of remaining_accounts, after the `other_count` accounts (or none) of the instruction
+fn split_epoch_stats<'c, 'info>(
+    remaining_accounts: &'c [AccountInfo<'info>],
+    other_count: usize,
+) -> Result<(&'c [AccountInfo<'info>], Option<&'c AccountInfo<'info>>), ProgramError> {
+    let len = remaining_accounts.len();
+    let others = if len == 0 || len == other_count {
+        len
+    } else if len == 1 || len == other_count + 1 {
+        len - 1
+    } else {
+        return Err(CommonError::UnexpectedAccount.into());
+    };
+    Ok((&remaining_accounts[..others], remaining_accounts.get(others)))
+}
+
+/// check_context for instructions recording EpochStats:
+/// the only accepted remaining account is the optional epoch_stats
+fn check_context_epoch_stats<'c, 'info, T>(
+    ctx: &Context<'_, '_, 'c, 'info, T>,
+) -> Result<Option<&'c AccountInfo<'info>>, ProgramError> {
+    check_program_id(ctx)?;
+    let (_, epoch_stats) = split_epoch_stats(ctx.remaining_accounts, 0)?;
+    Ok(epoch_stats)
+}
+
 //-----------------------------------------------------
 #[program]
 pub mod marinade_finance {
@@ -87,14 +139,50 @@ pub mod marinade_finance {
         Ok(())
     }
 
-    pub fn change_authority(
-        ctx: Context<ChangeAuthority>,
+    pub fn migrate_state(ctx: Context<MigrateState>) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(ctx.program_id)
+    }
+
+    // additional_record_space: bytes after the record, for future fields
+    // max_copy_count: records copied by this call (the list is moved in several transactions)
+    pub fn migrate_list(
+        ctx: Context<MigrateList>,
+        additional_record_space: u32,
+        max_copy_count: u32,
+    ) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(additional_record_space, max_copy_count)
+    }
+
+    pub fn propose_authority(
+        ctx: Context<ProposeAuthority>,
         data: ChangeAuthorityData,
     ) -> ProgramResult {
         check_context(&ctx)?;
         ctx.accounts.process(data)
     }
 
+    pub fn set_role(ctx: Context<SetRole>, role: Role, authority: Pubkey) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(role, authority)
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> ProgramResult {
+        check_program_id(&ctx)?;
+        ctx.accounts.process(ctx.remaining_accounts)
+    }
+
+    pub fn pause(ctx: Context<Pause>, operations: u64) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(operations)
+    }
+
+    pub fn unpause(ctx: Context<Unpause>, operations: u64) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(operations)
+    }
+
     pub fn add_validator(ctx: Context<AddValidator>, score: u32) -> ProgramResult {
         check_context(&ctx)?;
         ctx.accounts.process(score)
@@ -119,6 +207,28 @@ pub mod marinade_finance {
         ctx.accounts.process(index, validator_vote, score)
     }
 
+    pub fn clear_validator_score_override(
+        ctx: Context<SetValidatorScore>,
+        index: u32,
+        validator_vote: Pubkey,
+    ) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.clear_override(index, validator_vote)
+    }
+
+    pub fn refresh_validator_score(
+        ctx: Context<RefreshValidatorScore>,
+        index: u32,
+    ) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(index)
+    }
+
+    pub fn expire_directed_stake(ctx: Context<ExpireDirectedStake>, index: u32) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(index)
+    }
+
     pub fn config_validator_system(
         ctx: Context<ConfigValidatorSystem>,
         extra_runs: u32,
@@ -127,24 +237,183 @@ pub mod marinade_finance {
         ctx.accounts.process(extra_runs)
     }
 
+    pub fn config_score_formula(
+        ctx: Context<ConfigScoreFormula>,
+        score_formula: ScoreFormula,
+    ) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(score_formula)
+    }
+
     // deposit AKA stake, AKA deposit_sol
     pub fn deposit(ctx: Context<Deposit>, lamports: u64) -> ProgramResult {
-        check_context(&ctx)?;
-        ctx.accounts.process(lamports)
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(lamports, 0, epoch_stats)
+    }
+
+    // same as deposit but fails if less than min_msol_out mSOL are received
+    pub fn deposit_with_min_out(
+        ctx: Context<Deposit>,
+        lamports: u64,
+        min_msol_out: u64,
+    ) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(lamports, min_msol_out, epoch_stats)
+    }
+
+    // deposit directing the stake to the validator at validator_index
+    // min_msol_out: fail if the user gets less mSOL (0 = no check)
+    pub fn deposit_directed(
+        ctx: Context<DepositDirected>,
+        lamports: u64,
+        validator_index: u32,
+        min_msol_out: u64,
+    ) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(lamports, validator_index, min_msol_out, epoch_stats)
+    }
+
+    // deposit funded with wrapped SOL, unwrapped into the reserve
+    // fails if less than min_msol_out mSOL are received (0 = no check)
+    pub fn deposit_wsol(
+        ctx: Context<DepositWsol>,
+        lamports: u64,
+        min_msol_out: u64,
+    ) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(lamports, min_msol_out, epoch_stats)
     }
 
     // SPL stake pool like
-    pub fn deposit_stake_account(
-        ctx: Context<DepositStakeAccount>,
+    pub fn deposit_stake_account<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositStakeAccount<'info>>,
+        validator_index: u32,
+    ) -> ProgramResult {
+        check_program_id(&ctx)?;
+        // remaining_accounts: [cooling_in_claim, cooling_in_escrow], only for an activating stake,
+        // then the optional epoch_stats
+        let (cooling_in_accounts, epoch_stats) = split_epoch_stats(ctx.remaining_accounts, 2)?;
+        ctx.accounts
+            .process(validator_index, cooling_in_accounts, epoch_stats)
+    }
+
+    // deposit_stake_account merging into an existing stake instead of adding a new one
+    pub fn deposit_stake_account_merge(
+        ctx: Context<DepositStakeAccountMerge>,
+        validator_index: u32,
+        destination_stake_index: u32,
+    ) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts
+            .process(validator_index, destination_stake_index, epoch_stats)
+    }
+
+    // inverse of deposit_stake_account: burn mSOL and get a stake account split from ours
+    pub fn withdraw_stake_account(
+        ctx: Context<WithdrawStakeAccount>,
+        stake_index: u32,
         validator_index: u32,
+        msol_amount: u64,
+        beneficiary: Pubkey,
     ) -> ProgramResult {
         check_context(&ctx)?;
-        ctx.accounts.process(validator_index)
+        ctx.accounts
+            .process(stake_index, validator_index, msol_amount, beneficiary)
     }
 
     pub fn liquid_unstake(ctx: Context<LiquidUnstake>, msol_amount: u64) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(msol_amount, 0, epoch_stats)
+    }
+
+    // same as liquid_unstake but fails if less than min_lamports_out SOL are received
+    pub fn liquid_unstake_with_min_out(
+        ctx: Context<LiquidUnstake>,
+        msol_amount: u64,
+        min_lamports_out: u64,
+    ) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(msol_amount, min_lamports_out, epoch_stats)
+    }
+
+    // liquid_unstake getting exactly `lamports` SOL for at most max_msol_in mSOL
+    pub fn liquid_unstake_exact_out(
+        ctx: Context<LiquidUnstake>,
+        lamports: u64,
+        max_msol_in: u64,
+    ) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process_exact_out(lamports, max_msol_in, epoch_stats)
+    }
+
+    // read-only quotes, results are set as borsh-serialized return data
+    pub fn quote_deposit(ctx: Context<Quote>, lamports: u64) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.quote_deposit(lamports)
+    }
+
+    pub fn quote_liquid_unstake(ctx: Context<Quote>, msol_amount: u64) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.quote_liquid_unstake(msol_amount)
+    }
+
+    pub fn quote_order_unstake(ctx: Context<Quote>, msol_amount: u64) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.quote_order_unstake(msol_amount)
+    }
+
+    pub fn quote_add_liquidity(ctx: Context<Quote>, lamports: u64) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.quote_add_liquidity(lamports)
+    }
+
+    pub fn quote_remove_liquidity(ctx: Context<Quote>, tokens: u64) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.quote_remove_liquidity(tokens)
+    }
+
+    // read-only, the msol_price sample is set as borsh-serialized return data
+    pub fn get_price_at_epoch(ctx: Context<GetPriceAtEpoch>, epoch: u64) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(epoch)
+    }
+
+    pub fn get_average_price(ctx: Context<GetPriceAtEpoch>, epochs: u64) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.get_average_price(epochs)
+    }
+
+    pub fn init_referral_account(
+        ctx: Context<InitReferralAccount>,
+        partner_share: Fee,
+    ) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(partner_share)
+    }
+
+    pub fn update_referral(ctx: Context<UpdateReferral>, partner_share: Fee) -> ProgramResult {
         check_context(&ctx)?;
-        ctx.accounts.process(msol_amount)
+        ctx.accounts.process(partner_share)
+    }
+
+    // fails if less than min_msol_out mSOL are received (0 = no check)
+    pub fn deposit_with_referral(
+        ctx: Context<DepositWithReferral>,
+        lamports: u64,
+        min_msol_out: u64,
+    ) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(lamports, min_msol_out, epoch_stats)
+    }
+
+    // fails if less than min_lamports_out SOL are received (0 = no check)
+    pub fn liquid_unstake_with_referral(
+        ctx: Context<LiquidUnstakeWithReferral>,
+        msol_amount: u64,
+        min_lamports_out: u64,
+    ) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(msol_amount, min_lamports_out, epoch_stats)
     }
 
     pub fn add_liquidity(ctx: Context<AddLiquidity>, lamports: u64) -> ProgramResult {
@@ -152,6 +421,12 @@ pub mod marinade_finance {
         ctx.accounts.process(lamports)
     }
 
+    // add_liquidity funded with wrapped SOL, unwrapped into the liq-pool SOL leg
+    pub fn add_liquidity_wsol(ctx: Context<AddLiquidityWsol>, lamports: u64) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(lamports)
+    }
+
     pub fn remove_liquidity(ctx: Context<RemoveLiquidity>, tokens: u64) -> ProgramResult {
         check_context(&ctx)?;
         ctx.accounts.process(tokens)
@@ -175,6 +450,51 @@ pub mod marinade_finance {
         ctx.accounts.process(params)
     }
 
+    pub fn init_pending_config(ctx: Context<InitPendingConfig>) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    pub fn apply_pending_config(ctx: Context<ApplyPendingConfig>) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    pub fn init_epoch_stats(ctx: Context<InitEpochStats>) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    pub fn init_admin_council(
+        ctx: Context<InitAdminCouncil>,
+        council_id: u64,
+        members: Vec<Pubkey>,
+        threshold: u8,
+    ) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(council_id, members, threshold)
+    }
+
+    pub fn create_proposal(ctx: Context<CreateProposal>, action: CouncilAction) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(action)
+    }
+
+    pub fn approve_proposal(ctx: Context<ApproveProposal>) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    pub fn acknowledge_circuit_breaker(ctx: Context<AcknowledgeCircuitBreaker>) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
     //-------------------------------------------------------------------------------------
     // WIP Instructions, wil be part of devnet-MVP-2 beta-test release at marinade.finance
     //-------------------------------------------------------------------------------------
@@ -186,31 +506,85 @@ pub mod marinade_finance {
     //-------------------------------------------------------------------------------------
 
     pub fn order_unstake(ctx: Context<OrderUnstake>, msol_amount: u64) -> ProgramResult {
-        check_context(&ctx)?;
-        ctx.accounts.process(msol_amount)
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(msol_amount, epoch_stats)
+    }
+
+    pub fn order_unstake_pda(
+        ctx: Context<OrderUnstakePda>,
+        msol_amount: u64,
+        ticket_nonce: u64,
+    ) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(msol_amount, ticket_nonce, epoch_stats)
     }
 
     pub fn claim(ctx: Context<Claim>) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(false)
+    }
+
+    // pays what the reserve can cover now and keeps the ticket open for the rest
+    pub fn partial_claim(ctx: Context<Claim>) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(true)
+    }
+
+    pub fn transfer_ticket(ctx: Context<TransferTicket>) -> ProgramResult {
         check_context(&ctx)?;
         ctx.accounts.process()
     }
 
-    pub fn stake_reserve(ctx: Context<StakeReserve>, validator_index: u32) -> ProgramResult {
+    pub fn split_ticket(
+        ctx: Context<SplitTicket>,
+        lamports_amount: u64,
+        new_ticket_nonce: u64,
+    ) -> ProgramResult {
+        check_context(&ctx)?;
+        ctx.accounts.process(lamports_amount, new_ticket_nonce)
+    }
+
+    pub fn cancel_ticket(ctx: Context<CancelTicket>) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(epoch_stats)
+    }
+
+    // mSOL of an activated cooling-in stake deposit
+    pub fn claim_cooling_in(ctx: Context<ClaimCoolingIn>) -> ProgramResult {
         check_context(&ctx)?;
-        ctx.accounts.process(validator_index)
+        ctx.accounts.process()
+    }
+
+    // ticket accounts are passed in remaining_accounts
+    pub fn claim_many(ctx: Context<ClaimMany>) -> ProgramResult {
+        check_program_id(&ctx)?;
+        ctx.accounts.process(ctx.remaining_accounts)
+    }
+
+    pub fn stake_reserve(ctx: Context<StakeReserve>, validator_index: u32) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(validator_index, epoch_stats)
     }
 
-    pub fn update_active(
-        ctx: Context<UpdateActive>,
+    pub fn update_active<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdateActive<'info>>,
         stake_index: u32,
         validator_index: u32,
     ) -> ProgramResult {
-        check_context(&ctx)?;
-        ctx.accounts.process(stake_index, validator_index)
+        check_program_id(&ctx)?;
+        // remaining_accounts: [cooling_in_claim, cooling_in_escrow], only for a cooling-in stake,
+        // then the optional epoch_stats
+        let (cooling_in_accounts, epoch_stats) = split_epoch_stats(ctx.remaining_accounts, 2)?;
+        ctx.accounts.process(
+            stake_index,
+            validator_index,
+            cooling_in_accounts,
+            epoch_stats,
+        )
     }
     pub fn update_deactivated(ctx: Context<UpdateDeactivated>, stake_index: u32) -> ProgramResult {
-        check_context(&ctx)?;
-        ctx.accounts.process(stake_index)
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(stake_index, epoch_stats)
     }
 
     pub fn deactivate_stake(
@@ -218,8 +592,14 @@ pub mod marinade_finance {
         stake_index: u32,
         validator_index: u32,
     ) -> ProgramResult {
+        let epoch_stats = check_context_epoch_stats(&ctx)?;
+        ctx.accounts.process(stake_index, validator_index, epoch_stats)
+    }
+
+    // unstakes a cooling-in stake of a removed validator, minting the mSOL for its depositor
+    pub fn unstake_cooling_in(ctx: Context<UnstakeCoolingIn>, stake_index: u32) -> ProgramResult {
         check_context(&ctx)?;
-        ctx.accounts.process(stake_index, validator_index)
+        ctx.accounts.process(stake_index)
     }
 
     pub fn emergency_unstake(
@@ -385,78 +765,186 @@ pub struct LiqPoolInitializeData {
 
 //-----------------------------------------------------
 #[derive(Accounts)]
-pub struct ChangeAuthority<'info> {
+pub struct MigrateState<'info> {
+    // not a ProgramAccount: it can not be deserialized when it is too small for the layout
     #[account(mut)]
 	///CHECK: many
-    pub state: ProgramAccount<'info, State>,
-    #[account(signer)]
+    ///CHECK: stf anchor
+	pub state: AccountInfo<'info>,
+    // receives a state smaller than the current layout (zeroed, owned by the program),
+    // the old account is closed. The state account itself otherwise
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub new_state: AccountInfo<'info>,
+    // admin signer, or the executed council proposal of the action
+    #[account(mut)]
 	///CHECK: many
     ///CHECK: stf anchor
 	pub admin_authority: AccountInfo<'info>,
+    // receives the rent of the moved state account
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub operational_sol_account: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
-pub struct ChangeAuthorityData {
-    pub admin: Option<Pubkey>,
-    pub validator_manager: Option<Pubkey>,
-    pub operational_sol_account: Option<Pubkey>,
-    pub treasury_msol_account: Option<Pubkey>,
-}
-
-//-----------------------------------------------------
 #[derive(Accounts)]
-pub struct AddLiquidity<'info> {
+pub struct MigrateList<'info> {
     #[account(mut)]
 	///CHECK: many
     pub state: ProgramAccount<'info, State>,
-
+    // admin signer, or the executed council proposal of the action
     #[account(mut)]
 	///CHECK: many
-    pub lp_mint: CpiAccount<'info, Mint>,
-
     ///CHECK: stf anchor
-	pub lp_mint_authority: AccountInfo<'info>,
-
-    // msol_mint to be able to compute current msol value in liq_pool
-    // not needed because we use memorized value
-    // pub msol_mint: CpiAccount<'info, Mint>,
-    // liq_pool_msol_leg to be able to compute current msol value in liq_pool
-    pub liq_pool_msol_leg: CpiAccount<'info, TokenAccount>,
-
+	pub admin_authority: AccountInfo<'info>,
+    // validator_list or stake_list, closed when the migration is done
     #[account(mut)]
 	///CHECK: many
-    // seeds = [&state.to_account_info().key.to_bytes()[..32], LiqPool::SOL_ACCOUNT_SEED], bump = state.liq_pool.sol_account_bump_seed)]
-    // #[account(owner = "11111111111111111111111111111111")]
+    ///CHECK: stf anchor
+	pub old_list: AccountInfo<'info>,
+    #[account(mut)]
 	///CHECK: many
     ///CHECK: stf anchor
-	pub liq_pool_sol_leg_pda: AccountInfo<'info>,
-
-    // #[check_owner_program("11111111111111111111111111111111")]
-    #[account(mut, signer)] //, owner = "11111111111111111111111111111111")]
+	pub new_list: AccountInfo<'info>,
+    #[account(mut)]
 	///CHECK: many
     ///CHECK: stf anchor
-	pub transfer_from: AccountInfo<'info>,
+	pub operational_sol_account: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+}
 
-    // #[check_owner_program("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
-    #[account(mut)] // , owner = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(mut)]
 	///CHECK: many
-    pub mint_to: CpiAccount<'info, TokenAccount>,
-
-    // #[account(address = "11111111111111111111111111111111")]
+    pub state: ProgramAccount<'info, State>,
+    #[account(signer)]
 	///CHECK: many
-    // #[check_address("11111111111111111111111111111111")]
     ///CHECK: stf anchor
-	pub system_program: AccountInfo<'info>,
+	pub admin_authority: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
 
-    // #[account(address = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
+#[derive(Accounts)]
+pub struct SetRole<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    // admin signer, or the executed council proposal of the action
+    #[account(mut)]
 	///CHECK: many
-    // #[check_address("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
     ///CHECK: stf anchor
-	pub token_program: AccountInfo<'info>,
+	pub admin_authority: AccountInfo<'info>,
 }
-//-----------------------------------------------------
+
+// remaining accounts: the new authorities signing
 #[derive(Accounts)]
-pub struct RemoveLiquidity<'info> {
+pub struct AcceptAuthority<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct ChangeAuthorityData {
+    pub admin: Option<Pubkey>,
+    pub validator_manager: Option<Pubkey>,
+    pub operational_sol_account: Option<Pubkey>,
+    pub treasury_msol_account: Option<Pubkey>,
+    pub pause_authority: Option<Pubkey>,
+    pub timelock_slots: Option<u64>,
+}
+
+#[derive(Accounts)]
+pub struct Pause<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    // admin or pause_authority
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Unpause<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    // admin signer, or the executed council proposal of the action
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub admin_authority: AccountInfo<'info>,
+}
+
+//-----------------------------------------------------
+#[derive(Accounts)]
+pub struct AddLiquidity<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+
+    #[account(mut)]
+	///CHECK: many
+    pub lp_mint: CpiAccount<'info, Mint>,
+
+    ///CHECK: stf anchor
+	pub lp_mint_authority: AccountInfo<'info>,
+
+    // msol_mint to be able to compute current msol value in liq_pool
+    // not needed because we use memorized value
+    // pub msol_mint: CpiAccount<'info, Mint>,
+    // liq_pool_msol_leg to be able to compute current msol value in liq_pool
+    pub liq_pool_msol_leg: CpiAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+	///CHECK: many
+    // seeds = [&state.to_account_info().key.to_bytes()[..32], LiqPool::SOL_ACCOUNT_SEED], bump = state.liq_pool.sol_account_bump_seed)]
+    // #[account(owner = "11111111111111111111111111111111")]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub liq_pool_sol_leg_pda: AccountInfo<'info>,
+
+    // #[check_owner_program("11111111111111111111111111111111")]
+    #[account(mut, signer)] //, owner = "11111111111111111111111111111111")]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub transfer_from: AccountInfo<'info>,
+
+    // #[check_owner_program("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
+    #[account(mut)] // , owner = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
+	///CHECK: many
+    pub mint_to: CpiAccount<'info, TokenAccount>,
+
+    // #[account(address = "11111111111111111111111111111111")]
+	///CHECK: many
+    // #[check_address("11111111111111111111111111111111")]
+    ///CHECK: stf anchor
+	pub system_program: AccountInfo<'info>,
+
+    // #[account(address = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
+	///CHECK: many
+    // #[check_address("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
+    ///CHECK: stf anchor
+	pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddLiquidityWsol<'info> {
+    // add_liquidity.transfer_from only signs (any key, e.g. transfer_wsol_authority)
+    pub add_liquidity: AddLiquidity<'info>,
+    pub unwrap_wsol: UnwrapWsol<'info>,
+}
+//-----------------------------------------------------
+#[derive(Accounts)]
+pub struct RemoveLiquidity<'info> {
     #[account(mut)]
 	///CHECK: many
     pub state: ProgramAccount<'info, State>,
@@ -544,6 +1032,47 @@ pub struct Deposit<'info> {
 	pub token_program: AccountInfo<'info>,
 }
 
+#[derive(Accounts)]
+pub struct DepositDirected<'info> {
+    pub deposit: Deposit<'info>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub validator_list: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub validator_vote: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DepositWsol<'info> {
+    // deposit.transfer_from only signs (any key, e.g. transfer_wsol_authority)
+    pub deposit: Deposit<'info>,
+    pub unwrap_wsol: UnwrapWsol<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnwrapWsol<'info> {
+    // token account of the native mint
+    #[account(mut)]
+	///CHECK: many
+    pub transfer_wsol_from: CpiAccount<'info, TokenAccount>,
+    // owner or delegate of transfer_wsol_from
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub transfer_wsol_authority: AccountInfo<'info>,
+
+    // PDA [state, "temp_wsol", transfer_wsol_from]. Created and closed by the instruction
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub temp_wsol_account: AccountInfo<'info>,
+
+    ///CHECK: stf anchor
+	pub wsol_mint: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
 //-----------------------------------------------------
 #[derive(Accounts)]
 pub struct DepositStakeAccount<'info> {
@@ -589,6 +1118,79 @@ pub struct DepositStakeAccount<'info> {
     pub clock: Sysvar<'info, Clock>,
     pub rent: Sysvar<'info, Rent>,
 
+    ///CHECK: stf anchor
+	pub system_program: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub token_program: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub stake_program: AccountInfo<'info>,
+
+    // receives the stake account lamports above delegation + rent
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub reserve_pda: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub stake_history: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DepositStakeAccountMerge<'info> {
+    pub deposit: DepositStakeAccount<'info>,
+    // updated active stake of the same validator in stake_list
+    #[account(mut)]
+	///CHECK: many
+    pub destination_stake: CpiAccount<'info, StakeWrapper>,
+    ///CHECK: stf anchor
+	pub stake_deposit_authority: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub stake_withdraw_authority: AccountInfo<'info>,
+}
+
+//-----------------------------------------------------
+#[derive(Accounts)]
+pub struct WithdrawStakeAccount<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(mut)]
+	///CHECK: many
+    pub msol_mint: CpiAccount<'info, Mint>,
+    #[account(mut)]
+	///CHECK: many
+    pub burn_msol_from: CpiAccount<'info, TokenAccount>,
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub burn_msol_authority: AccountInfo<'info>, // burn_msol_from owner or delegate
+
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub validator_list: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub stake_list: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    pub stake_account: CpiAccount<'info, StakeWrapper>,
+    ///CHECK: stf anchor
+	pub stake_deposit_authority: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub stake_withdraw_authority: AccountInfo<'info>,
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub split_stake_account: AccountInfo<'info>,
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub split_stake_rent_payer: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+
     ///CHECK: stf anchor
 	pub system_program: AccountInfo<'info>,
     ///CHECK: stf anchor
@@ -639,6 +1241,86 @@ pub struct LiquidUnstake<'info> {
     ///CHECK: stf anchor
 	pub token_program: AccountInfo<'info>,
 }
+
+#[derive(Accounts)]
+pub struct Quote<'info> {
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    ///CHECK: stf anchor
+	pub liq_pool_sol_leg_pda: AccountInfo<'info>,
+	///CHECK: many
+    pub liq_pool_msol_leg: CpiAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct GetPriceAtEpoch<'info> {
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+}
+
+#[derive(Accounts)]
+pub struct InitReferralAccount<'info> {
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    // admin signer, or the executed council proposal of the action
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub admin_authority: AccountInfo<'info>,
+
+    // PDA [state, "referral", partner_account], created by the instruction
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub referral_state: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub partner_account: AccountInfo<'info>,
+	///CHECK: many
+    pub msol_token_partner_account: CpiAccount<'info, TokenAccount>,
+
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub rent_payer: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    ///CHECK: stf anchor
+	pub system_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateReferral<'info> {
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    // admin signer, or the executed council proposal of the action
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub admin_authority: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    pub referral_state: ProgramAccount<'info, ReferralState>,
+	///CHECK: many
+    pub msol_token_partner_account: CpiAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct DepositWithReferral<'info> {
+    pub deposit: Deposit<'info>,
+    #[account(mut)]
+	///CHECK: many
+    pub referral_state: ProgramAccount<'info, ReferralState>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidUnstakeWithReferral<'info> {
+    pub liquid_unstake: LiquidUnstake<'info>,
+    #[account(mut)]
+	///CHECK: many
+    pub referral_state: ProgramAccount<'info, ReferralState>,
+    #[account(mut)]
+	///CHECK: many
+    pub msol_token_partner_account: CpiAccount<'info, TokenAccount>,
+}
 //-----------------------------------------------------
 #[derive(Accounts)]
 pub struct AddValidator<'info> {
@@ -647,134 +1329,325 @@ pub struct AddValidator<'info> {
     pub state: ProgramAccount<'info, State>,
     #[account(signer)]
 	///CHECK: many
-    ///CHECK: stf anchor
-	pub manager_authority: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub manager_authority: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub validator_list: AccountInfo<'info>,
+
+    ///CHECK: stf anchor
+	pub validator_vote: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub duplication_flag: AccountInfo<'info>,
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub rent_payer: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+
+    ///CHECK: stf anchor
+	pub system_program: AccountInfo<'info>,
+}
+
+//-----------------------------------------------------
+#[derive(Accounts)]
+pub struct RemoveValidator<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub manager_authority: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub validator_list: AccountInfo<'info>,
+
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub duplication_flag: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub operational_sol_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetValidatorScore<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub manager_authority: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub validator_list: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshValidatorScore<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub validator_list: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub validator_vote: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireDirectedStake<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub validator_list: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigValidatorSystem<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub manager_authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigScoreFormula<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub manager_authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct OrderUnstake<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(mut)]
+	///CHECK: many
+    pub msol_mint: CpiAccount<'info, Mint>,
+
+    // Note: Ticket beneficiary is burn_msol_from.owner
+    #[account(mut)]
+	///CHECK: many
+    pub burn_msol_from: CpiAccount<'info, TokenAccount>,
+
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub burn_msol_authority: AccountInfo<'info>, // burn_msol_from acc must be pre-delegated with enough amount to this key or input owner signature here
+
+    #[account(zero, rent_exempt = enforce)]
+	///CHECK: many
+    pub new_ticket_account: ProgramAccount<'info, TicketAccountData>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+    ///CHECK: stf anchor
+	pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct OrderUnstakePda<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(mut)]
+	///CHECK: many
+    pub msol_mint: CpiAccount<'info, Mint>,
+
+    // Note: Ticket beneficiary is burn_msol_from.owner
+    #[account(mut)]
+	///CHECK: many
+    pub burn_msol_from: CpiAccount<'info, TokenAccount>,
+
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub burn_msol_authority: AccountInfo<'info>, // burn_msol_from acc must be pre-delegated with enough amount
+
+    // PDA [state, "ticket", beneficiary, ticket_nonce], created by the instruction
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub new_ticket_account: AccountInfo<'info>,
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub rent_payer: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+    ///CHECK: stf anchor
+	pub system_program: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Claim<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
     #[account(mut)]
 	///CHECK: many
     ///CHECK: stf anchor
-	pub validator_list: AccountInfo<'info>,
+	pub reserve_pda: AccountInfo<'info>,
 
-    ///CHECK: stf anchor
-	pub validator_vote: AccountInfo<'info>,
     #[account(mut)]
 	///CHECK: many
-    ///CHECK: stf anchor
-	pub duplication_flag: AccountInfo<'info>,
-    #[account(mut, signer)]
+    pub ticket_account: ProgramAccount<'info, TicketAccountData>,
+
+    #[account(mut)]
 	///CHECK: many
     ///CHECK: stf anchor
-	pub rent_payer: AccountInfo<'info>,
+	pub transfer_sol_to: AccountInfo<'info>,
 
     pub clock: Sysvar<'info, Clock>,
-    pub rent: Sysvar<'info, Rent>,
 
     ///CHECK: stf anchor
 	pub system_program: AccountInfo<'info>,
 }
 
-//-----------------------------------------------------
 #[derive(Accounts)]
-pub struct RemoveValidator<'info> {
-    #[account(mut)]
+pub struct ClaimCoolingIn<'info> {
 	///CHECK: many
     pub state: ProgramAccount<'info, State>,
-    #[account(signer)]
-	///CHECK: many
-    ///CHECK: stf anchor
-	pub manager_authority: AccountInfo<'info>,
+    // PDA [state, "cooling_in", stake_account], closed by the claim
     #[account(mut)]
 	///CHECK: many
+    pub claim: ProgramAccount<'info, CoolingInClaim>,
+    #[account(mut, signer)]
+	///CHECK: many
     ///CHECK: stf anchor
-	pub validator_list: AccountInfo<'info>,
-
+	pub beneficiary: AccountInfo<'info>,
+    // PDA [state, "cooling_in_escrow"]
     #[account(mut)]
 	///CHECK: many
     ///CHECK: stf anchor
-	pub duplication_flag: AccountInfo<'info>,
+	pub cooling_in_escrow: AccountInfo<'info>,
     #[account(mut)]
 	///CHECK: many
+    pub transfer_msol_to: CpiAccount<'info, TokenAccount>,
+
     ///CHECK: stf anchor
-	pub operational_sol_account: AccountInfo<'info>,
+	pub token_program: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
-pub struct SetValidatorScore<'info> {
+pub struct ClaimMany<'info> {
     #[account(mut)]
 	///CHECK: many
     pub state: ProgramAccount<'info, State>,
-    #[account(signer)]
+    #[account(mut)]
 	///CHECK: many
     ///CHECK: stf anchor
-	pub manager_authority: AccountInfo<'info>,
+	pub reserve_pda: AccountInfo<'info>,
+
+    // all tickets (passed as remaining accounts) must have this beneficiary
     #[account(mut)]
 	///CHECK: many
     ///CHECK: stf anchor
-	pub validator_list: AccountInfo<'info>,
+	pub transfer_sol_to: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    ///CHECK: stf anchor
+	pub system_program: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
-pub struct ConfigValidatorSystem<'info> {
-    #[account(mut)]
+pub struct TransferTicket<'info> {
 	///CHECK: many
     pub state: ProgramAccount<'info, State>,
+    #[account(mut)]
+	///CHECK: many
+    pub ticket_account: ProgramAccount<'info, TicketAccountData>,
     #[account(signer)]
 	///CHECK: many
     ///CHECK: stf anchor
-	pub manager_authority: AccountInfo<'info>,
+	pub beneficiary: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub new_beneficiary: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
-pub struct OrderUnstake<'info> {
+pub struct SplitTicket<'info> {
     #[account(mut)]
 	///CHECK: many
     pub state: ProgramAccount<'info, State>,
     #[account(mut)]
 	///CHECK: many
-    pub msol_mint: CpiAccount<'info, Mint>,
-
-    // Note: Ticket beneficiary is burn_msol_from.owner
-    #[account(mut)]
-	///CHECK: many
-    pub burn_msol_from: CpiAccount<'info, TokenAccount>,
-
+    pub ticket_account: ProgramAccount<'info, TicketAccountData>,
     #[account(signer)]
 	///CHECK: many
     ///CHECK: stf anchor
-	pub burn_msol_authority: AccountInfo<'info>, // burn_msol_from acc must be pre-delegated with enough amount to this key or input owner signature here
+	pub beneficiary: AccountInfo<'info>,
 
-    #[account(zero, rent_exempt = enforce)]
+    // PDA [state, "ticket", beneficiary, new_ticket_nonce], created by the instruction
+    #[account(mut)]
 	///CHECK: many
-    pub new_ticket_account: ProgramAccount<'info, TicketAccountData>,
+    ///CHECK: stf anchor
+	pub new_ticket_account: AccountInfo<'info>,
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub rent_payer: AccountInfo<'info>,
 
-    pub clock: Sysvar<'info, Clock>,
     pub rent: Sysvar<'info, Rent>,
     ///CHECK: stf anchor
-	pub token_program: AccountInfo<'info>,
+	pub system_program: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
-pub struct Claim<'info> {
+pub struct CancelTicket<'info> {
     #[account(mut)]
 	///CHECK: many
     pub state: ProgramAccount<'info, State>,
     #[account(mut)]
 	///CHECK: many
-    ///CHECK: stf anchor
-	pub reserve_pda: AccountInfo<'info>,
-
+    pub msol_mint: CpiAccount<'info, Mint>,
     #[account(mut)]
 	///CHECK: many
     pub ticket_account: ProgramAccount<'info, TicketAccountData>,
-
+    // receives the ticket rent
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub beneficiary: AccountInfo<'info>,
     #[account(mut)]
 	///CHECK: many
+    pub mint_to: CpiAccount<'info, TokenAccount>,
     ///CHECK: stf anchor
-	pub transfer_sol_to: AccountInfo<'info>,
+	pub msol_mint_authority: AccountInfo<'info>,
 
     pub clock: Sysvar<'info, Clock>,
-
     ///CHECK: stf anchor
-	pub system_program: AccountInfo<'info>,
+	pub token_program: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
@@ -914,7 +1787,19 @@ pub struct SetLpParams<'info> {
     #[account(signer)]
 	///CHECK: many
     ///CHECK: stf anchor
-	pub admin_authority: AccountInfo<'info>,
+    // admin or fee manager
+	pub authority: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    pub pending_config: ProgramAccount<'info, PendingConfig>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct LpParams {
+    pub min_fee: Fee,
+    pub max_fee: Fee,
+    pub liquidity_target: u64,
 }
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
@@ -927,6 +1812,9 @@ pub struct ConfigMarinadeParams {
     pub staking_sol_cap: Option<u64>,
     pub liquidity_sol_cap: Option<u64>,
     pub auto_add_validator_enabled: Option<bool>,
+    pub max_msol_price_increase: Option<Fee>,
+    pub max_msol_price_decrease: Option<Fee>,
+    pub directed_stake_epochs: Option<u64>,
 }
 
 #[derive(Accounts)]
@@ -936,6 +1824,164 @@ pub struct ConfigMarinade<'info> {
     pub state: ProgramAccount<'info, State>,
     #[account(signer)]
 	///CHECK: many
+    ///CHECK: stf anchor
+    // admin, fee manager or cap manager
+	pub authority: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    pub pending_config: ProgramAccount<'info, PendingConfig>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct InitPendingConfig<'info> {
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    // admin signer, or the executed council proposal of the action
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub admin_authority: AccountInfo<'info>,
+
+    // PDA [state, "pending_config"], created by the instruction
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub pending_config: AccountInfo<'info>,
+
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub rent_payer: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    ///CHECK: stf anchor
+	pub system_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyPendingConfig<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(mut)]
+	///CHECK: many
+    pub pending_config: ProgramAccount<'info, PendingConfig>,
+    // must sign when the crank operator role is assigned
+    ///CHECK: stf anchor
+	pub crank_operator: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct InitEpochStats<'info> {
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    // admin signer, or the executed council proposal of the action
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub admin_authority: AccountInfo<'info>,
+
+    // PDA [state, "epoch_stats"], created by the instruction
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub epoch_stats: AccountInfo<'info>,
+
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub rent_payer: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    ///CHECK: stf anchor
+	pub system_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitAdminCouncil<'info> {
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+
+    // PDA [state, "admin_council", council_id], created by the instruction
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub admin_council: AccountInfo<'info>,
+
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub rent_payer: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    ///CHECK: stf anchor
+	pub system_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateProposal<'info> {
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(mut)]
+	///CHECK: many
+    pub admin_council: ProgramAccount<'info, AdminCouncil>,
+
+    // PDA [admin_council, "proposal", admin_council.proposal_count], created by the instruction
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub proposal: AccountInfo<'info>,
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub proposer: AccountInfo<'info>,
+
+    #[account(mut, signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub rent_payer: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+    ///CHECK: stf anchor
+	pub system_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveProposal<'info> {
+	///CHECK: many
+    pub admin_council: ProgramAccount<'info, AdminCouncil>,
+    #[account(mut)]
+	///CHECK: many
+    pub proposal: ProgramAccount<'info, CouncilProposal>,
+    #[account(signer)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub member: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+	///CHECK: many
+    pub admin_council: ProgramAccount<'info, AdminCouncil>,
+    #[account(mut)]
+	///CHECK: many
+    pub proposal: ProgramAccount<'info, CouncilProposal>,
+    #[account(mut)]
+	///CHECK: many
+    pub pending_config: ProgramAccount<'info, PendingConfig>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct AcknowledgeCircuitBreaker<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    // admin signer, or the executed council proposal of the action
+    #[account(mut)]
+	///CHECK: many
     ///CHECK: stf anchor
 	pub admin_authority: AccountInfo<'info>,
 }
@@ -1011,6 +2057,46 @@ pub struct EmergencyUnstake<'info> {
 	pub stake_program: AccountInfo<'info>,
 }
 
+#[derive(Accounts)]
+pub struct UnstakeCoolingIn<'info> {
+    #[account(mut)]
+	///CHECK: many
+    pub state: ProgramAccount<'info, State>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub stake_list: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    pub stake_account: CpiAccount<'info, StakeWrapper>,
+    ///CHECK: stf anchor
+	pub stake_deposit_authority: AccountInfo<'info>,
+    // PDA [state, "unique_validator", voter] of the stake validator, closed when it was removed
+    ///CHECK: stf anchor
+	pub duplication_flag: AccountInfo<'info>,
+
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub cooling_in_claim: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    ///CHECK: stf anchor
+	pub cooling_in_escrow: AccountInfo<'info>,
+    #[account(mut)]
+	///CHECK: many
+    pub msol_mint: CpiAccount<'info, Mint>,
+    ///CHECK: stf anchor
+	pub msol_mint_authority: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    ///CHECK: stf anchor
+	pub stake_program: AccountInfo<'info>,
+    ///CHECK: stf anchor
+	pub token_program: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 pub struct PartialUnstake<'info> {
     #[account(mut)]
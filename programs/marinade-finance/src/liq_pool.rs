@@ -1,9 +1,10 @@
 use crate::{
-    calc::proportional, checks::check_address, error::CommonError, located::Located, Fee, State, ID,
+    calc::proportional, checks::check_address, error::CommonError, located::Located, Fee, State,
 };
 use anchor_lang::prelude::*;
 
 pub mod add_liquidity;
+pub mod add_liquidity_wsol;
 pub mod initialize;
 pub mod remove_liquidity;
 pub mod set_lp_params;
@@ -38,21 +39,21 @@ impl LiqPool {
     pub const MSOL_LEG_AUTHORITY_SEED: &'static [u8] = b"liq_st_sol_authority";
     pub const MSOL_LEG_SEED: &'static str = "liq_st_sol";
 
-    pub fn find_lp_mint_authority(state: &Pubkey) -> (Pubkey, u8) {
+    pub fn find_lp_mint_authority(state: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
         Pubkey::find_program_address(
             &[&state.to_bytes()[..32], Self::LP_MINT_AUTHORITY_SEED],
-            &ID,
+            program_id,
         )
     }
 
-    pub fn find_sol_leg_address(state: &Pubkey) -> (Pubkey, u8) {
-        Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::SOL_LEG_SEED], &ID)
+    pub fn find_sol_leg_address(state: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::SOL_LEG_SEED], program_id)
     }
 
-    pub fn find_msol_leg_authority(state: &Pubkey) -> (Pubkey, u8) {
+    pub fn find_msol_leg_authority(state: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
         Pubkey::find_program_address(
             &[&state.to_bytes()[..32], Self::MSOL_LEG_AUTHORITY_SEED],
-            &ID,
+            program_id,
         )
     }
 
@@ -87,6 +88,27 @@ impl LiqPool {
         }
     }
 
+    /// (lamports, mSOL) for burning `tokens` LP
+    /// from a liq-pool with sol_leg_lamports (without rent) and msol_leg_balance
+    pub fn calc_remove_liquidity(
+        &self,
+        tokens: u64,
+        sol_leg_lamports: u64,
+        msol_leg_balance: u64,
+    ) -> Result<(u64, u64), CommonError> {
+        let sol_out_amount = proportional(
+            tokens,
+            sol_leg_lamports,
+            self.lp_supply, // Use virtual amount
+        )?;
+        let msol_out_amount = proportional(
+            tokens,
+            msol_leg_balance,
+            self.lp_supply, // Use virtual amount
+        )?;
+        Ok((sol_out_amount, msol_out_amount))
+    }
+
     pub fn on_lp_mint(&mut self, amount: u64) {
         self.lp_supply = self
             .lp_supply
@@ -150,7 +172,7 @@ where
     // call a function adding lp_mint_authority_seeds
     fn with_lp_mint_authority_seeds<R, F: FnOnce(&[&[u8]]) -> R>(&self, f: F) -> R {
         f(&[
-            &self.key().to_bytes()[..32],
+            &self.as_ref().instance_address.to_bytes()[..32],
             LiqPool::LP_MINT_AUTHORITY_SEED,
             &[self.as_ref().liq_pool.lp_mint_authority_bump_seed],
         ])
@@ -158,13 +180,13 @@ where
 
     fn lp_mint_authority(&self) -> Pubkey {
         self.with_lp_mint_authority_seeds(|seeds| {
-            Pubkey::create_program_address(seeds, &ID).unwrap()
+            Pubkey::create_program_address(seeds, &self.owner()).unwrap()
         })
     }
 
     fn with_liq_pool_sol_leg_seeds<R, F: FnOnce(&[&[u8]]) -> R>(&self, f: F) -> R {
         f(&[
-            &self.key().to_bytes()[..32],
+            &self.as_ref().instance_address.to_bytes()[..32],
             LiqPool::SOL_LEG_SEED,
             &[self.as_ref().liq_pool.sol_leg_bump_seed],
         ])
@@ -172,13 +194,13 @@ where
 
     fn liq_pool_sol_leg_address(&self) -> Pubkey {
         self.with_liq_pool_sol_leg_seeds(|seeds| {
-            Pubkey::create_program_address(seeds, &ID).unwrap()
+            Pubkey::create_program_address(seeds, &self.owner()).unwrap()
         })
     }
 
     fn with_liq_pool_msol_leg_authority_seeds<R, F: FnOnce(&[&[u8]]) -> R>(&self, f: F) -> R {
         f(&[
-            &self.key().to_bytes()[..32],
+            &self.as_ref().instance_address.to_bytes()[..32],
             LiqPool::MSOL_LEG_AUTHORITY_SEED,
             &[self.as_ref().liq_pool.msol_leg_authority_bump_seed],
         ])
@@ -186,7 +208,7 @@ where
 
     fn liq_pool_msol_leg_authority(&self) -> Pubkey {
         self.with_liq_pool_msol_leg_authority_seeds(|seeds| {
-            Pubkey::create_program_address(seeds, &ID).unwrap()
+            Pubkey::create_program_address(seeds, &self.owner()).unwrap()
         })
     }
 
@@ -1,7 +1,7 @@
-use crate::AddLiquidity;
+use crate::{events::AddLiquidityEvent, AddLiquidity, State, UnwrapWsol};
 
 use super::LiqPoolHelpers;
-use crate::{calc::shares_from_value, checks::*};
+use crate::checks::*;
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{program::invoke, system_instruction, system_program};
 use anchor_spl::token::{mint_to, MintTo};
@@ -28,6 +28,17 @@ impl<'info> AddLiquidity<'info> {
 
     // fn add_liquidity()
     pub fn process(&mut self, lamports: u64) -> ProgramResult {
+        self.add_liquidity(lamports, None)
+    }
+
+    /// add liquidity paid by transfer_from, or by the wSOL of unwrap_wsol
+    /// (then transfer_from only signs and is not debited)
+    pub fn add_liquidity(
+        &mut self,
+        lamports: u64,
+        unwrap_wsol: Option<&UnwrapWsol<'info>>,
+    ) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_ADD_LIQUIDITY)?;
         msg!("add-liq pre check");
         check_min_amount(lamports, self.state.min_deposit, "add_liquidity")?;
         self.state
@@ -42,7 +53,9 @@ impl<'info> AddLiquidity<'info> {
             .check_liq_pool_msol_leg(self.liq_pool_msol_leg.to_account_info().key)?;
         self.state
             .check_liq_pool_sol_leg_pda(self.liq_pool_sol_leg_pda.key)?;
-        self.check_transfer_from(lamports)?;
+        if unwrap_wsol.is_none() {
+            self.check_transfer_from(lamports)?;
+        }
         self.state
             .liq_pool
             .check_liquidity_cap(lamports, self.liq_pool_sol_leg_pda.lamports())?;
@@ -72,40 +85,42 @@ impl<'info> AddLiquidity<'info> {
             .lamports()
             .checked_sub(self.state.rent_exempt_for_token_acc)
             .expect("sol_leg_lamports");
-        let msol_leg_value = self
-            .state
-            .calc_lamports_from_msol_amount(self.liq_pool_msol_leg.amount)
-            .expect("msol_leg_value");
-        let total_liq_pool_value = sol_leg_lamports + msol_leg_value;
-        msg!(
-            "liq_pool SOL:{}, liq_pool mSOL value:{} liq_pool_value:{}",
-            sol_leg_lamports,
-            msol_leg_value,
-            total_liq_pool_value
-        );
-
-        let shares_for_user = shares_from_value(
+        let shares_for_user = self.state.calc_lp_from_lamports(
             lamports,
-            total_liq_pool_value,
-            self.state.liq_pool.lp_supply,
+            sol_leg_lamports,
+            self.liq_pool_msol_leg.amount,
         )?;
 
         msg!("LP for user {}", shares_for_user);
 
         //we start with a transfer instruction so the user can verify the SOL amount they're staking while approving the transaction
         //transfer sol into liq-pool sol leg
-        invoke(
-            &system_instruction::transfer(
-                self.transfer_from.key,
-                self.liq_pool_sol_leg_pda.key,
-                lamports,
-            ),
-            &[
-                self.transfer_from.clone(),
-                self.liq_pool_sol_leg_pda.clone(),
-                self.system_program.clone(),
-            ],
-        )?;
+        if let Some(unwrap_wsol) = unwrap_wsol {
+            self.state.with_liq_pool_sol_leg_seeds(|sol_leg_seeds| {
+                unwrap_wsol.process(
+                    &self.state.instance_address,
+                    self.state.to_account_info().owner,
+                    lamports,
+                    &self.liq_pool_sol_leg_pda,
+                    sol_leg_seeds,
+                    &self.system_program,
+                    &self.token_program,
+                )
+            })?;
+        } else {
+            invoke(
+                &system_instruction::transfer(
+                    self.transfer_from.key,
+                    self.liq_pool_sol_leg_pda.key,
+                    lamports,
+                ),
+                &[
+                    self.transfer_from.clone(),
+                    self.liq_pool_sol_leg_pda.clone(),
+                    self.system_program.clone(),
+                ],
+            )?;
+        }
 
         //mint liq-pool shares (mSOL-SOL-LP tokens) for the user
         self.state.with_lp_mint_authority_seeds(|mint_seeds| {
@@ -124,6 +139,13 @@ impl<'info> AddLiquidity<'info> {
         })?;
         self.state.liq_pool.on_lp_mint(shares_for_user);
 
+        emit!(AddLiquidityEvent {
+            state: self.state.instance_address,
+            sol_owner: *self.transfer_from.key,
+            user_lp_account: *self.mint_to.to_account_info().key,
+            lamports,
+            lp_minted: shares_for_user,
+        });
         Ok(())
     }
 }
@@ -0,0 +1,12 @@
+use anchor_lang::prelude::*;
+
+use crate::AddLiquidityWsol;
+
+impl<'info> AddLiquidityWsol<'info> {
+    // fn add_liquidity_wsol()
+    // add_liquidity funded with wrapped SOL, see UnwrapWsol
+    pub fn process(&mut self, lamports: u64) -> ProgramResult {
+        self.add_liquidity
+            .add_liquidity(lamports, Some(&self.unwrap_wsol))
+    }
+}
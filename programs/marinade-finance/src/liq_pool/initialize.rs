@@ -17,7 +17,7 @@ impl<'info> LiqPoolInitialize<'info> {
             return Err(ProgramError::InvalidAccountData);
         }
         let (authority_address, authority_bump_seed) =
-            LiqPool::find_lp_mint_authority(parent.state_address());
+            LiqPool::find_lp_mint_authority(parent.state_address(), parent.program_id());
 
         check_mint_authority(&parent.liq_pool.lp_mint, authority_address, "lp_mint")?;
 
@@ -35,7 +35,8 @@ impl<'info> LiqPoolInitialize<'info> {
             &system_program::ID,
             "liq_sol_account_pda",
         )?;
-        let (address, bump) = LiqPool::find_sol_leg_address(parent.state_address());
+        let (address, bump) =
+            LiqPool::find_sol_leg_address(parent.state_address(), parent.program_id());
         check_address(
             parent.liq_pool.sol_leg_pda.key,
             &address,
@@ -64,7 +65,7 @@ impl<'info> LiqPoolInitialize<'info> {
             "liq_msol",
         )?;
         let (msol_authority, msol_authority_bump_seed) =
-            LiqPool::find_msol_leg_authority(parent.state_address());
+            LiqPool::find_msol_leg_authority(parent.state_address(), parent.program_id());
         check_token_owner(&parent.liq_pool.msol_leg, &msol_authority, "liq_msol_leg")?;
         parent.state.liq_pool.msol_leg_authority_bump_seed = msol_authority_bump_seed;
         Ok(())
@@ -98,7 +99,7 @@ impl<'info> LiqPoolInitialize<'info> {
         parent.state.liq_pool.lp_liquidity_target = data.lp_liquidity_target; //10_000 SOL
         parent.state.liq_pool.lp_min_fee = data.lp_min_fee; // Fee { basis_points: 30 }; //0.3%
         parent.state.liq_pool.lp_max_fee = data.lp_max_fee; // Fee { basis_points: 300 }; //3%
-        parent.state.liq_pool.liquidity_sol_cap = std::u64::MAX; // Unlimited
+        parent.state.liq_pool.liquidity_sol_cap = u64::MAX; // Unlimited
 
         Ok(())
     }
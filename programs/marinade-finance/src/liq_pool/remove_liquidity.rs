@@ -1,8 +1,8 @@
 use crate::{
-    calc::proportional,
     checks::{check_address, check_min_amount, check_owner_program, check_token_mint},
+    events::RemoveLiquidityEvent,
     liq_pool::LiqPoolHelpers,
-    RemoveLiquidity,
+    RemoveLiquidity, State,
 };
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{program::invoke_signed, system_instruction, system_program};
@@ -65,6 +65,7 @@ impl<'info> RemoveLiquidity<'info> {
     }
 
     pub fn process(&mut self, tokens: u64) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_REMOVE_LIQUIDITY)?;
         msg!("rem-liq pre check");
         self.state
             .liq_pool
@@ -99,18 +100,13 @@ impl<'info> RemoveLiquidity<'info> {
 
         msg!("mSOL-SOL-LP total supply:{}", self.lp_mint.supply);
 
-        let sol_out_amount = proportional(
+        let (sol_out_amount, msol_out_amount) = self.state.liq_pool.calc_remove_liquidity(
             tokens,
             self.liq_pool_sol_leg_pda
                 .lamports()
                 .checked_sub(self.state.rent_exempt_for_token_acc)
                 .unwrap(),
-            self.state.liq_pool.lp_supply, // Use virtual amount
-        )?;
-        let msol_out_amount = proportional(
-            tokens,
             self.liq_pool_msol_leg.amount,
-            self.state.liq_pool.lp_supply, // Use virtual amount
         )?;
 
         check_min_amount(
@@ -181,6 +177,13 @@ impl<'info> RemoveLiquidity<'info> {
         )?;
         self.state.liq_pool.on_lp_burn(tokens)?;
 
+        emit!(RemoveLiquidityEvent {
+            state: self.state.instance_address,
+            user_lp_account: *self.burn_from.to_account_info().key,
+            lp_burned: tokens,
+            lamports_out: sol_out_amount,
+            msol_out: msol_out_amount,
+        });
         msg!("end instruction rem-liq");
         Ok(())
     }
@@ -1,9 +1,9 @@
-use crate::{error::CommonError, Fee, SetLpParams};
-use anchor_lang::prelude::ProgramResult;
+use crate::{error::CommonError, liq_pool::LiqPool, roles::Role, Fee, LpParams, SetLpParams};
+use anchor_lang::prelude::*;
 use anchor_lang::solana_program::native_token::sol_to_lamports;
 
-impl<'info> SetLpParams<'info> {
-    fn check_fees(&self, min_fee: Fee, max_fee: Fee) -> ProgramResult {
+impl LpParams {
+    fn check_fees(min_fee: Fee, max_fee: Fee) -> ProgramResult {
         min_fee.check()?;
         max_fee.check()?;
         //hard-limit, max liquid unstake-fee of 10%
@@ -16,7 +16,7 @@ impl<'info> SetLpParams<'info> {
         Ok(())
     }
 
-    fn check_liquidity_target(&self, liquidity_target: u64) -> ProgramResult {
+    fn check_liquidity_target(liquidity_target: u64) -> ProgramResult {
         if liquidity_target < sol_to_lamports(50.0) {
             Err(CommonError::LiquidityTargetTooLow.into())
         } else {
@@ -24,14 +24,41 @@ impl<'info> SetLpParams<'info> {
         }
     }
 
-    pub fn process(&mut self, min_fee: Fee, max_fee: Fee, liquidity_target: u64) -> ProgramResult {
-        self.state.check_admin_authority(self.admin_authority.key)?;
-        self.check_fees(min_fee, max_fee)?;
-        self.check_liquidity_target(liquidity_target)?;
+    pub fn check(&self) -> ProgramResult {
+        Self::check_fees(self.min_fee, self.max_fee)?;
+        Self::check_liquidity_target(self.liquidity_target)
+    }
 
-        self.state.liq_pool.lp_min_fee = min_fee;
-        self.state.liq_pool.lp_max_fee = max_fee;
-        self.state.liq_pool.lp_liquidity_target = liquidity_target;
-        Ok(())
+    /// fee increases need a notice period before applying.
+    /// A higher liquidity target raises the fee charged for the same pool liquidity
+    pub fn is_fee_increase(&self, liq_pool: &LiqPool) -> bool {
+        self.min_fee > liq_pool.lp_min_fee
+            || self.max_fee > liq_pool.lp_max_fee
+            || self.liquidity_target > liq_pool.lp_liquidity_target
+    }
+
+    pub fn apply(&self, liq_pool: &mut LiqPool) {
+        liq_pool.lp_min_fee = self.min_fee;
+        liq_pool.lp_max_fee = self.max_fee;
+        liq_pool.lp_liquidity_target = self.liquidity_target;
+    }
+}
+
+impl<'info> SetLpParams<'info> {
+    // fn set_lp_params()
+    // applies the params, or queues them in pending_config for apply_pending_config
+    // when they increase a fee
+    pub fn process(&mut self, min_fee: Fee, max_fee: Fee, liquidity_target: u64) -> ProgramResult {
+        self.state
+            .check_admin_or_role(Role::FeeManager, self.authority.key)?;
+        self.pending_config
+            .check_state_address(&self.state.instance_address)?;
+        let lp_params = LpParams {
+            min_fee,
+            max_fee,
+            liquidity_target,
+        };
+        self.pending_config
+            .queue_lp_params(&mut self.state, lp_params, self.clock.epoch)
     }
 }
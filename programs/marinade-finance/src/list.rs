@@ -49,7 +49,6 @@ impl List {
         data: &mut [u8],
         list_name: &str,
     ) -> ProgramResult {
-        assert_eq!(self.count, 0);
         if data.len() < 8 {
             msg!(
                 "{} account must have at least 8 bytes of storage",
@@ -120,7 +119,7 @@ impl List {
         )
         .map_err(|_| ProgramError::from(CommonError::CalculationFailure))?
         .checked_div(self.item_size())
-        .unwrap_or(std::u32::MAX)) // for zst element (why you are using it in list?)
+        .unwrap_or(u32::MAX)) // for zst element (why you are using it in list?)
     }
 
     pub fn get<I: AnchorDeserialize>(
@@ -225,18 +224,21 @@ impl List {
         Ok(())
     }
 
-    /*
-    pub fn change_account<'info>(
+    /// Copies up to max_copy_count items into new_account, the first call initializes it.
+    /// Items can grow: new_item_size >= item_size, the new bytes are zeroed.
+    /// Returns true when all items are copied and the list uses new_account
+    #[allow(clippy::too_many_arguments)]
+    pub fn change_account(
         &mut self,
-        old_account: &AccountInfo<'info>,
-        new_account: &AccountInfo<'info>,
+        discriminator: &[u8; 8],
+        old_data: &[u8],
+        new_account: &Pubkey,
+        new_data: &mut [u8],
+        new_item_size: u32,
         max_copy_count: u32,
         list_name: &str,
     ) -> Result<bool, ProgramError> {
-        self.check_account(old_account, list_name)?;
-        let data_size = 8 + (self.len() * self.item_size()) as usize;
-        let mut new_data = new_account.data.borrow_mut();
-        if self.new_account != *new_account.key {
+        if self.new_account != *new_account {
             if self.new_account != Pubkey::default() {
                 msg!(
                     "list {} already changing account into {}",
@@ -245,36 +247,54 @@ impl List {
                 );
                 return Err(ProgramError::InvalidArgument);
             }
+            if new_item_size < self.item_size() {
+                msg!(
+                    "list {} item size can not decrease from {} to {}",
+                    list_name,
+                    self.item_size(),
+                    new_item_size
+                );
+                return Err(ProgramError::InvalidArgument);
+            }
+            let data_size = Self::bytes_for(new_item_size, self.len()) as usize;
             if new_data.len() < data_size {
                 msg!(
                     "Account {} is too small for copying list {}. At least {} bytes needed",
-                    new_account.key,
+                    new_account,
                     list_name,
                     data_size
                 );
                 return Err(ProgramError::AccountDataTooSmall);
             }
-            self.init_account(new_account, list_name)?;
+            self.init_account(discriminator, new_data, list_name)?;
 
-            self.new_account = *new_account.key;
+            self.new_account = *new_account;
             self.copied_count = 0;
         }
 
         let copy_count = max_copy_count.min(self.len() - self.copied_count);
-
-        let start = 8 + (self.copied_count * self.item_size()) as usize;
-        let stop = start + (self.item_size() * copy_count) as usize;
-        new_data[start..stop].copy_from_slice(&old_account.data.borrow()[start..stop]);
+        let old_item_size = self.item_size() as usize;
+        for index in self.copied_count..self.copied_count + copy_count {
+            let old_start = 8 + index as usize * old_item_size;
+            let new_start = 8 + (index * new_item_size) as usize;
+            let new_stop = new_start + new_item_size as usize;
+            new_data[new_start..new_start + old_item_size]
+                .copy_from_slice(&old_data[old_start..old_start + old_item_size]);
+            for byte in &mut new_data[new_start + old_item_size..new_stop] {
+                *byte = 0;
+            }
+        }
         self.copied_count += copy_count;
         if self.copied_count == self.len() {
             self.account = self.new_account;
+            self.item_size = new_item_size;
             self.new_account = Pubkey::default();
             self.copied_count = 0;
             Ok(true)
         } else {
             Ok(false)
         }
-    }*/
+    }
 
     /*
     pub fn iter<'a, 'info>(
@@ -388,15 +408,90 @@ mod tests {
             list.remove(&mut list_data, remove_index as u32, "test_list")?;
             assert_eq!(list.len(), COUNT as u32 - 1);
             let expected_set: BTreeSet<u8> = (0..COUNT)
-                .filter(|i| *i as usize != remove_index)
+                .filter(|i| *i != remove_index)
                 .map(|x| (x + 9) as u8)
                 .collect();
             let result_set = (0..list.len())
-                .map(|i| list.get::<u8>(&list_data, i as u32, "test_list"))
+                .map(|i| list.get::<u8>(&list_data, i, "test_list"))
                 .collect::<Result<BTreeSet<u8>, ProgramError>>()?;
 
             assert_eq!(expected_set, result_set);
         }
         Ok(())
     }
+
+    #[test]
+    fn test_change_account() -> ProgramResult {
+        const COUNT: usize = 10;
+        let mut list_data = [0; COUNT * 2 + 8];
+        let discriminator = &[1, 2, 3, 4, 5, 6, 7, 8];
+        let mut list = List::new(
+            discriminator,
+            2u32,
+            Pubkey::new_unique(),
+            &mut list_data,
+            "test_list",
+        )?;
+        for i in 0..COUNT {
+            list.push::<u16>(&mut list_data, 1000 + i as u16, "test_list")?;
+        }
+
+        let new_account = Pubkey::new_unique();
+        let mut new_data = [0xff; COUNT * 3 + 8];
+        // the new account must not be initialized
+        assert!(list
+            .change_account(
+                discriminator,
+                &list_data,
+                &new_account,
+                &mut new_data,
+                3,
+                4,
+                "test_list"
+            )
+            .is_err());
+        new_data[0..8].copy_from_slice(&[0; 8]);
+        // the item size can not decrease
+        assert!(list
+            .change_account(
+                discriminator,
+                &list_data,
+                &new_account,
+                &mut new_data,
+                1,
+                4,
+                "test_list"
+            )
+            .is_err());
+        for expected_done in [false, false, true] {
+            let done = list.change_account(
+                discriminator,
+                &list_data,
+                &new_account,
+                &mut new_data,
+                3,
+                4,
+                "test_list",
+            )?;
+            assert_eq!(done, expected_done);
+            // the list can not be modified while changing the account
+            if !done {
+                assert!(list.push::<u16>(&mut list_data, 0, "test_list").is_err());
+            }
+        }
+
+        assert_eq!(list.account, new_account);
+        assert_eq!(list.item_size(), 3);
+        assert!(!list.is_changing_account());
+        assert_eq!(&new_data[0..8], discriminator);
+        for i in 0..COUNT {
+            assert_eq!(
+                list.get::<u16>(&new_data, i as u32, "test_list")?,
+                1000 + i as u16
+            );
+            // the added record space is zeroed
+            assert_eq!(new_data[8 + i * 3 + 2], 0);
+        }
+        Ok(())
+    }
 }
@@ -8,6 +8,8 @@ pub trait Located<T> {
     fn as_ref(&self) -> &T;
     fn as_mut(&mut self) -> &mut T;
     fn key(&self) -> Pubkey;
+    /// program owning the account, the PDAs of a marinade instance are derived from it
+    fn owner(&self) -> Pubkey;
 }
 
 impl<'info, T, A> Located<T> for A
@@ -25,4 +27,8 @@ where
     fn key(&self) -> Pubkey {
         *self.to_account_info().key
     }
+
+    fn owner(&self) -> Pubkey {
+        *self.to_account_info().owner
+    }
 }
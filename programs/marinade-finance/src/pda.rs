@@ -0,0 +1,82 @@
+//! Creation of program derived accounts
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program::invoke_signed, system_instruction};
+
+/// Creates the PDA signed by seeds with space bytes owned by owner, rent paid by rent_payer.
+/// PDA addresses are predictable and anybody can send lamports to them before
+/// (system create_account fails for accounts with lamports), so in that case
+/// the missing rent is topped up and the account is allocated and assigned like anchor init does
+pub fn create_pda_account<'info>(
+    rent_payer: &AccountInfo<'info>,
+    new_account: &AccountInfo<'info>,
+    space: usize,
+    owner: &Pubkey,
+    system_program: &AccountInfo<'info>,
+    rent: &Rent,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    create_pda_account_signed(
+        rent_payer,
+        new_account,
+        space,
+        owner,
+        system_program,
+        rent,
+        &[seeds],
+    )
+}
+
+/// create_pda_account for a rent_payer that is a PDA too.
+/// signers_seeds: the new account seeds first, then the rent_payer ones
+pub fn create_pda_account_signed<'info>(
+    rent_payer: &AccountInfo<'info>,
+    new_account: &AccountInfo<'info>,
+    space: usize,
+    owner: &Pubkey,
+    system_program: &AccountInfo<'info>,
+    rent: &Rent,
+    signers_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let rent_lamports = rent.minimum_balance(space);
+    if new_account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                rent_payer.key,
+                new_account.key,
+                rent_lamports,
+                space as u64,
+                owner,
+            ),
+            &[
+                system_program.clone(),
+                rent_payer.clone(),
+                new_account.clone(),
+            ],
+            signers_seeds,
+        );
+    }
+
+    let missing_rent = rent_lamports.saturating_sub(new_account.lamports());
+    if missing_rent > 0 {
+        invoke_signed(
+            &system_instruction::transfer(rent_payer.key, new_account.key, missing_rent),
+            &[
+                system_program.clone(),
+                rent_payer.clone(),
+                new_account.clone(),
+            ],
+            signers_seeds,
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(new_account.key, space as u64),
+        &[system_program.clone(), new_account.clone()],
+        signers_seeds,
+    )?;
+    invoke_signed(
+        &system_instruction::assign(new_account.key, owner),
+        &[system_program.clone(), new_account.clone()],
+        signers_seeds,
+    )
+}
@@ -0,0 +1,119 @@
+use crate::{checks::check_address, error::CommonError, Fee};
+use anchor_lang::prelude::*;
+
+pub mod deposit_with_referral;
+pub mod init_referral_account;
+pub mod liquid_unstake_with_referral;
+pub mod update_referral;
+
+/// Referral partner registered by the admin.
+/// PDA [state, "referral", partner_account]
+#[account]
+#[derive(Debug, Default)]
+pub struct ReferralState {
+    pub state_address: Pubkey, // instance of marinade state this partner belongs to
+    pub partner_account: Pubkey, // partner main account
+    pub msol_token_partner_account: Pubkey, // receives the partner share of liquid unstake fees
+    pub partner_share: Fee,    // share of the treasury cut of liquid unstake fees
+
+    // volume attributed to the partner
+    pub deposit_sol_amount: u64,
+    pub deposit_operations: u64,
+    pub liq_unstake_msol_amount: u64,
+    pub liq_unstake_msol_fees: u64,
+    pub liq_unstake_operations: u64,
+    pub partner_msol_fees: u64, // total mSOL paid to the partner
+}
+
+impl ReferralState {
+    pub const SEED: &'static [u8] = b"referral";
+
+    pub fn serialized_len() -> usize {
+        Self::default().try_to_vec().unwrap().len() + 8
+    }
+
+    pub fn find_address(
+        state: &Pubkey,
+        partner_account: &Pubkey,
+        program_id: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                &state.to_bytes()[..32],
+                Self::SEED,
+                &partner_account.to_bytes()[..32],
+            ],
+            program_id,
+        )
+    }
+
+    pub fn check_state_address(&self, state_address: &Pubkey) -> ProgramResult {
+        check_address(state_address, &self.state_address, "referral state")
+    }
+
+    pub fn check_msol_token_partner_account(
+        &self,
+        msol_token_partner_account: &Pubkey,
+    ) -> ProgramResult {
+        check_address(
+            msol_token_partner_account,
+            &self.msol_token_partner_account,
+            "msol_token_partner_account",
+        )
+    }
+
+    pub fn on_deposit(&mut self, lamports: u64) -> Result<(), CommonError> {
+        self.deposit_sol_amount = self
+            .deposit_sol_amount
+            .checked_add(lamports)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.deposit_operations += 1;
+        Ok(())
+    }
+
+    pub fn on_liquid_unstake(
+        &mut self,
+        msol_amount: u64,
+        msol_fee: u64,
+        partner_msol_cut: u64,
+    ) -> Result<(), CommonError> {
+        self.liq_unstake_msol_amount = self
+            .liq_unstake_msol_amount
+            .checked_add(msol_amount)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.liq_unstake_msol_fees = self
+            .liq_unstake_msol_fees
+            .checked_add(msol_fee)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.partner_msol_fees = self
+            .partner_msol_fees
+            .checked_add(partner_msol_cut)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.liq_unstake_operations += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_referral_volume() -> Result<(), CommonError> {
+        let mut referral = ReferralState::default();
+        referral.on_deposit(5)?;
+        referral.on_deposit(7)?;
+        assert_eq!(referral.deposit_sol_amount, 12);
+        assert_eq!(referral.deposit_operations, 2);
+
+        referral.on_liquid_unstake(1_000, 30, 3)?;
+        referral.on_liquid_unstake(2_000, 60, 6)?;
+        assert_eq!(referral.liq_unstake_msol_amount, 3_000);
+        assert_eq!(referral.liq_unstake_msol_fees, 90);
+        assert_eq!(referral.partner_msol_fees, 9);
+        assert_eq!(referral.liq_unstake_operations, 2);
+
+        assert!(referral.on_deposit(u64::MAX).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::{events::ReferralDepositEvent, DepositWithReferral};
+
+impl<'info> DepositWithReferral<'info> {
+    // fn deposit_with_referral()
+    // min_msol_out: fail if the user gets less mSOL (0 = no check)
+    pub fn process(
+        &mut self,
+        lamports: u64,
+        min_msol_out: u64,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.referral_state
+            .check_state_address(&self.deposit.state.instance_address)?;
+        self.deposit.process(lamports, min_msol_out, epoch_stats)?;
+        self.referral_state.on_deposit(lamports)?;
+        emit!(ReferralDepositEvent {
+            state: self.referral_state.state_address,
+            referral_state: *self.referral_state.to_account_info().key,
+            partner_account: self.referral_state.partner_account,
+            lamports,
+        });
+        Ok(())
+    }
+}
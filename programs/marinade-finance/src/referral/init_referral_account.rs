@@ -0,0 +1,82 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::system_program;
+
+use crate::{
+    admin_council::CouncilAction,
+    checks::{check_address, check_owner_program, check_token_mint},
+    events::InitReferralAccountEvent,
+    pda::create_pda_account,
+    referral::ReferralState,
+    Fee, InitReferralAccount,
+};
+
+impl<'info> InitReferralAccount<'info> {
+    // fn init_referral_account()
+    pub fn process(&mut self, partner_share: Fee) -> ProgramResult {
+        self.state.check_admin_or_council(
+            &self.admin_authority,
+            self.state.to_account_info().owner,
+            CouncilAction::InitReferralAccount {
+                partner_account: *self.partner_account.key,
+                msol_token_partner_account: *self.msol_token_partner_account.to_account_info().key,
+                partner_share,
+            },
+        )?;
+        partner_share.check()?;
+        check_token_mint(
+            &self.msol_token_partner_account,
+            self.state.msol_mint,
+            "msol_token_partner_account",
+        )?;
+        check_owner_program(&self.rent_payer, &system_program::ID, "rent_payer")?;
+        check_address(
+            self.system_program.key,
+            &system_program::ID,
+            "system_program",
+        )?;
+
+        let state_address = self.state.instance_address;
+        let program_id = *self.state.to_account_info().owner;
+        let (referral_address, bump_seed) =
+            ReferralState::find_address(&state_address, self.partner_account.key, &program_id);
+        check_address(self.referral_state.key, &referral_address, "referral_state")?;
+        check_owner_program(&self.referral_state, &system_program::ID, "referral_state")?;
+
+        create_pda_account(
+            &self.rent_payer,
+            &self.referral_state,
+            ReferralState::serialized_len(),
+            &program_id,
+            &self.system_program,
+            &self.rent,
+            &[
+                &state_address.to_bytes()[..32],
+                ReferralState::SEED,
+                &self.partner_account.key.to_bytes()[..32],
+                &[bump_seed],
+            ],
+        )?;
+
+        let referral = ReferralState {
+            state_address,
+            partner_account: *self.partner_account.key,
+            msol_token_partner_account: *self.msol_token_partner_account.to_account_info().key,
+            partner_share,
+            ..Default::default()
+        };
+        msg!(
+            "Referral {} for partner {}",
+            self.referral_state.key,
+            referral.partner_account
+        );
+        emit!(InitReferralAccountEvent {
+            state: state_address,
+            referral_state: *self.referral_state.key,
+            partner_account: referral.partner_account,
+            partner_share,
+        });
+        let mut data = self.referral_state.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        referral.try_serialize(&mut std::io::Cursor::new(dst))
+    }
+}
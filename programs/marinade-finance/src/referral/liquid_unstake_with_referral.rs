@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    checks::check_token_mint, events::ReferralLiquidUnstakeEvent, LiquidUnstakeWithReferral,
+};
+
+impl<'info> LiquidUnstakeWithReferral<'info> {
+    // fn liquid_unstake_with_referral()
+    // min_lamports_out: fail if the user gets less SOL (0 = no check)
+    pub fn process(
+        &mut self,
+        msol_amount: u64,
+        min_lamports_out: u64,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.referral_state
+            .check_state_address(&self.liquid_unstake.state.instance_address)?;
+        self.referral_state.check_msol_token_partner_account(
+            self.msol_token_partner_account.to_account_info().key,
+        )?;
+        check_token_mint(
+            &self.msol_token_partner_account,
+            self.liquid_unstake.state.msol_mint,
+            "msol_token_partner_account",
+        )?;
+
+        let partner_msol_account = self.msol_token_partner_account.to_account_info();
+        let (msol_fee, partner_msol_cut) = self.liquid_unstake.unstake(
+            msol_amount,
+            min_lamports_out,
+            false,
+            Some((&partner_msol_account, self.referral_state.partner_share)),
+            epoch_stats,
+        )?;
+        self.referral_state
+            .on_liquid_unstake(msol_amount, msol_fee, partner_msol_cut)?;
+        emit!(ReferralLiquidUnstakeEvent {
+            state: self.referral_state.state_address,
+            referral_state: *self.referral_state.to_account_info().key,
+            partner_account: self.referral_state.partner_account,
+            msol_amount,
+            msol_fee,
+            partner_msol_cut,
+        });
+        Ok(())
+    }
+}
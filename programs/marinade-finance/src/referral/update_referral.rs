@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    admin_council::CouncilAction, checks::check_token_mint, events::UpdateReferralEvent, Fee,
+    UpdateReferral,
+};
+
+impl<'info> UpdateReferral<'info> {
+    // fn update_referral()
+    pub fn process(&mut self, partner_share: Fee) -> ProgramResult {
+        self.state.check_admin_or_council(
+            &self.admin_authority,
+            self.state.to_account_info().owner,
+            CouncilAction::UpdateReferral {
+                referral_state: *self.referral_state.to_account_info().key,
+                msol_token_partner_account: *self.msol_token_partner_account.to_account_info().key,
+                partner_share,
+            },
+        )?;
+        self.referral_state
+            .check_state_address(&self.state.instance_address)?;
+        partner_share.check()?;
+        check_token_mint(
+            &self.msol_token_partner_account,
+            self.state.msol_mint,
+            "msol_token_partner_account",
+        )?;
+
+        self.referral_state.partner_share = partner_share;
+        self.referral_state.msol_token_partner_account =
+            *self.msol_token_partner_account.to_account_info().key;
+        emit!(UpdateReferralEvent {
+            state: self.state.instance_address,
+            referral_state: *self.referral_state.to_account_info().key,
+            partner_share,
+        });
+        Ok(())
+    }
+}
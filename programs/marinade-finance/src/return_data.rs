@@ -0,0 +1,30 @@
+//! Program return data (readable by the caller after CPI or in transaction simulation)
+
+#[cfg(target_arch = "bpf")]
+extern "C" {
+    fn sol_set_return_data(data: *const u8, length: u64);
+}
+
+/// Sets the instruction return data.
+/// solana-program 1.7 has no wrapper for this syscall yet, so it is declared here
+#[cfg(target_arch = "bpf")]
+pub fn set_return_data(data: &[u8]) {
+    unsafe { sol_set_return_data(data.as_ptr(), data.len() as u64) };
+}
+
+#[cfg(not(target_arch = "bpf"))]
+thread_local! {
+    static RETURN_DATA: std::cell::RefCell<Vec<u8>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Off-chain builds (tests, clients) keep the data in the current thread
+#[cfg(not(target_arch = "bpf"))]
+pub fn set_return_data(data: &[u8]) {
+    RETURN_DATA.with(|return_data| *return_data.borrow_mut() = data.to_vec());
+}
+
+/// Last data set by set_return_data in the current thread, off-chain builds only
+#[cfg(not(target_arch = "bpf"))]
+pub fn get_return_data() -> Vec<u8> {
+    RETURN_DATA.with(|return_data| return_data.borrow().clone())
+}
@@ -0,0 +1,135 @@
+use crate::{checks::check_address, State};
+use anchor_lang::prelude::*;
+
+pub mod set_role;
+
+#[derive(Clone, Copy, Debug, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub enum Role {
+    /// config_marinade rewards_fee, set_lp_params
+    FeeManager,
+    /// config_marinade staking_sol_cap and liquidity_sol_cap
+    CapManager,
+    /// set_validator_score only
+    ValidatorScorer,
+    /// emergency_unstake, partial_unstake and pause
+    EmergencyOperator,
+    /// apply_pending_config. Permissionless while not assigned
+    CrankOperator,
+}
+
+/// Keys holding a restricted part of the admin/validator_manager powers.
+/// Pubkey::default() = role not assigned
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct Roles {
+    pub fee_manager: Pubkey,
+    pub cap_manager: Pubkey,
+    pub validator_scorer: Pubkey,
+    pub emergency_operator: Pubkey,
+    pub crank_operator: Pubkey,
+}
+
+impl Roles {
+    pub fn get(&self, role: Role) -> &Pubkey {
+        match role {
+            Role::FeeManager => &self.fee_manager,
+            Role::CapManager => &self.cap_manager,
+            Role::ValidatorScorer => &self.validator_scorer,
+            Role::EmergencyOperator => &self.emergency_operator,
+            Role::CrankOperator => &self.crank_operator,
+        }
+    }
+
+    pub fn get_mut(&mut self, role: Role) -> &mut Pubkey {
+        match role {
+            Role::FeeManager => &mut self.fee_manager,
+            Role::CapManager => &mut self.cap_manager,
+            Role::ValidatorScorer => &mut self.validator_scorer,
+            Role::EmergencyOperator => &mut self.emergency_operator,
+            Role::CrankOperator => &mut self.crank_operator,
+        }
+    }
+
+    pub fn has_role(&self, role: Role, authority: &Pubkey) -> bool {
+        let holder = self.get(role);
+        holder != &Pubkey::default() && holder == authority
+    }
+}
+
+impl State {
+    /// admin can do everything a role can
+    pub fn check_admin_or_role(&self, role: Role, authority: &Pubkey) -> ProgramResult {
+        if self.roles.has_role(role, authority) {
+            Ok(())
+        } else {
+            check_address(authority, &self.admin_authority, "admin_authority")
+        }
+    }
+
+    /// validator manager can do everything a validator role can
+    pub fn check_validator_manager_or_role(&self, role: Role, authority: &Pubkey) -> ProgramResult {
+        if self.roles.has_role(role, authority) {
+            Ok(())
+        } else {
+            self.validator_system
+                .check_validator_manager_authority(authority)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    const ALL_ROLES: [Role; 5] = [
+        Role::FeeManager,
+        Role::CapManager,
+        Role::ValidatorScorer,
+        Role::EmergencyOperator,
+        Role::CrankOperator,
+    ];
+
+    #[test]
+    fn test_has_role() {
+        let mut roles = Roles::default();
+        // not assigned roles match nobody, not even the default key
+        for role in ALL_ROLES {
+            assert!(!roles.has_role(role, &Pubkey::default()));
+        }
+
+        let holders: Vec<Pubkey> = ALL_ROLES.iter().map(|_| Pubkey::new_unique()).collect();
+        for (role, holder) in ALL_ROLES.iter().zip(&holders) {
+            *roles.get_mut(*role) = *holder;
+        }
+        for (i, role) in ALL_ROLES.iter().enumerate() {
+            assert_eq!(roles.get(*role), &holders[i]);
+            for (j, holder) in holders.iter().enumerate() {
+                // a role does not grant the other ones
+                assert_eq!(roles.has_role(*role, holder), i == j);
+            }
+            assert!(!roles.has_role(*role, &Pubkey::default()));
+        }
+    }
+
+    #[test]
+    fn test_check_admin_or_role() {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.admin_authority = Pubkey::new_unique();
+        let fee_manager = Pubkey::new_unique();
+        state.roles.fee_manager = fee_manager;
+
+        assert!(state
+            .check_admin_or_role(Role::FeeManager, &fee_manager)
+            .is_ok());
+        assert!(state
+            .check_admin_or_role(Role::FeeManager, &state.admin_authority)
+            .is_ok());
+        assert!(state
+            .check_admin_or_role(Role::CapManager, &fee_manager)
+            .is_err());
+        // the admin key can not be impersonated by the unassigned role
+        assert!(state
+            .check_admin_or_role(Role::CapManager, &Pubkey::default())
+            .is_err());
+    }
+}
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::{admin_council::CouncilAction, events::RoleChangedEvent, roles::Role, SetRole};
+
+impl<'info> SetRole<'info> {
+    // fn set_role()
+    // Pubkey::default() revokes the role
+    pub fn process(&mut self, role: Role, authority: Pubkey) -> ProgramResult {
+        self.state.check_admin_or_council(
+            &self.admin_authority,
+            self.state.to_account_info().owner,
+            CouncilAction::SetRole { role, authority },
+        )?;
+        let old_authority = *self.state.roles.get(role);
+        *self.state.roles.get_mut(role) = authority;
+        emit!(RoleChangedEvent {
+            state: self.state.instance_address,
+            role,
+            old_authority,
+            new_authority: authority,
+        });
+        msg!("Role {:?} is assigned to {}", role, authority);
+        Ok(())
+    }
+}
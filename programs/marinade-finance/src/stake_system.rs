@@ -1,13 +1,15 @@
-use crate::{checks::check_address, list::List, located::Located, State, ID};
+use crate::{checks::check_address, error::CommonError, list::List, located::Located, State};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::clock::Epoch;
 
+pub mod cooling_in;
 pub mod deactivate_stake;
 pub mod deposit_stake_account;
 pub mod emergency_unstake;
 pub mod merge;
 pub mod partial_unstake;
 pub mod stake_reserve;
+pub mod withdraw_stake_account;
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
 pub struct StakeRecord {
@@ -15,6 +17,10 @@ pub struct StakeRecord {
     pub last_update_delegated_lamports: u64,
     pub last_update_epoch: u64,
     pub is_emergency_unstaking: u8, // 1 for cooling down after emergency unstake, 0 otherwise
+    /// CoolingInClaim of a stake account deposited while activating (cooling in).
+    /// update_active (or unstake_cooling_in) mints the mSOL into the escrow credited to the claim
+    /// and resets it to default. Stored in the additional record space
+    pub cooling_in_claim: Pubkey,
 }
 
 impl StakeRecord {
@@ -31,8 +37,22 @@ impl StakeRecord {
             last_update_delegated_lamports: delegated_lamports,
             last_update_epoch: clock.epoch,
             is_emergency_unstaking,
+            cooling_in_claim: Pubkey::default(),
         }
     }
+
+    pub fn is_cooling_in(&self) -> bool {
+        self.cooling_in_claim != Pubkey::default()
+    }
+
+    /// Fails for stake accounts deposited while activating and not yet accounted as active
+    pub fn check_not_cooling_in(&self) -> ProgramResult {
+        if self.is_cooling_in() {
+            msg!("Stake {} is cooling in", self.stake_account);
+            return Err(CommonError::StakeAccountIsCoolingIn.into());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, AnchorSerialize, AnchorDeserialize, Debug)]
@@ -75,16 +95,24 @@ impl StakeSystem {
         )
     }*/
 
-    pub fn find_stake_withdraw_authority(state: &Pubkey) -> (Pubkey, u8) {
-        Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::STAKE_WITHDRAW_SEED], &ID)
+    pub fn find_stake_withdraw_authority(state: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&state.to_bytes()[..32], Self::STAKE_WITHDRAW_SEED],
+            program_id,
+        )
     }
 
-    pub fn find_stake_deposit_authority(state: &Pubkey) -> (Pubkey, u8) {
-        Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::STAKE_DEPOSIT_SEED], &ID)
+    pub fn find_stake_deposit_authority(state: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&state.to_bytes()[..32], Self::STAKE_DEPOSIT_SEED],
+            program_id,
+        )
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         state: &Pubkey,
+        program_id: &Pubkey,
         stake_list_account: Pubkey,
         stake_list_data: &mut [u8],
         slots_for_stake_delta: u64,
@@ -103,8 +131,8 @@ impl StakeSystem {
         Ok(Self {
             stake_list,
             delayed_unstake_cooling_down: 0,
-            stake_deposit_bump_seed: Self::find_stake_deposit_authority(state).1,
-            stake_withdraw_bump_seed: Self::find_stake_withdraw_authority(state).1,
+            stake_deposit_bump_seed: Self::find_stake_deposit_authority(state, program_id).1,
+            stake_withdraw_bump_seed: Self::find_stake_withdraw_authority(state, program_id).1,
             slots_for_stake_delta,
             last_stake_delta_epoch: Epoch::MAX, // never
             min_stake,
@@ -149,6 +177,20 @@ impl StakeSystem {
         Ok(())
     }
 
+    /// adds a deposited stake account that is still activating
+    pub fn add_cooling_in(
+        &mut self,
+        stake_list_data: &mut [u8],
+        stake_account: &Pubkey,
+        delegated_lamports: u64,
+        clock: &Clock,
+        cooling_in_claim: &Pubkey,
+    ) -> ProgramResult {
+        let mut stake = StakeRecord::new(stake_account, delegated_lamports, clock, 0);
+        stake.cooling_in_claim = *cooling_in_claim;
+        self.stake_list.push(stake_list_data, stake, "stake_list")
+    }
+
     fn get(&self, stake_list_data: &[u8], index: u32) -> Result<StakeRecord, ProgramError> {
         self.stake_list.get(stake_list_data, index, "stake_list")
     }
@@ -208,13 +250,13 @@ where
 {
     fn stake_withdraw_authority(&self) -> Pubkey {
         self.with_stake_withdraw_authority_seeds(|seeds| {
-            Pubkey::create_program_address(seeds, &ID).unwrap()
+            Pubkey::create_program_address(seeds, &self.owner()).unwrap()
         })
     }
 
     fn with_stake_withdraw_authority_seeds<R, F: FnOnce(&[&[u8]]) -> R>(&self, f: F) -> R {
         f(&[
-            &self.key().to_bytes()[..32],
+            &self.as_ref().instance_address.to_bytes()[..32],
             StakeSystem::STAKE_WITHDRAW_SEED,
             &[self.as_ref().stake_system.stake_withdraw_bump_seed],
         ])
@@ -230,13 +272,13 @@ where
 
     fn stake_deposit_authority(&self) -> Pubkey {
         self.with_stake_deposit_authority_seeds(|seeds| {
-            Pubkey::create_program_address(seeds, &ID).unwrap()
+            Pubkey::create_program_address(seeds, &self.owner()).unwrap()
         })
     }
 
     fn with_stake_deposit_authority_seeds<R, F: FnOnce(&[&[u8]]) -> R>(&self, f: F) -> R {
         f(&[
-            &self.key().to_bytes()[..32],
+            &self.as_ref().instance_address.to_bytes()[..32],
             StakeSystem::STAKE_DEPOSIT_SEED,
             &[self.as_ref().stake_system.stake_deposit_bump_seed],
         ])
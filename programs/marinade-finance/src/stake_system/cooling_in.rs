@@ -0,0 +1,379 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    program::invoke_signed,
+    stake::{self},
+};
+use anchor_spl::token::{mint_to, transfer, MintTo, Transfer};
+
+use crate::{
+    checks::{check_address, check_owner_program, check_token_mint},
+    cooling_in_claim::CoolingInClaim,
+    error::CommonError,
+    events::{CoolingInClaimedEvent, CoolingInUnstakedEvent},
+    stake_system::{StakeRecord, StakeSystemHelpers},
+    state::StateHelpers,
+    ticket_account::close_ticket,
+    validator_system::ValidatorRecord,
+    ClaimCoolingIn, State, UnstakeCoolingIn,
+};
+
+/// Accounting of the stake accounts deposited while activating (cooling in).
+/// Their lamports are kept in deposit_cooling_in, outside of the mSOL price, until
+/// update_active sees them fully active or unstake_cooling_in deactivates them
+impl State {
+    /// The staking cap counts cooling-in lamports (see check_staking_cap): the deposit reserves
+    /// the cap space, so the conversion by update_active can not exceed the cap
+    pub fn on_cooling_in_deposit(&mut self, lamports: u64) -> ProgramResult {
+        self.deposit_cooling_in = self
+            .deposit_cooling_in
+            .checked_add(lamports)
+            .ok_or(CommonError::CalculationFailure)?;
+        Ok(())
+    }
+
+    /// the stake deposited with `deposited` lamports is fully active with `delegated` lamports
+    pub fn on_cooling_in_activated(&mut self, deposited: u64, delegated: u64) -> ProgramResult {
+        self.deposit_cooling_in = self
+            .deposit_cooling_in
+            .checked_sub(deposited)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.validator_system.total_active_balance = self
+            .validator_system
+            .total_active_balance
+            .checked_add(delegated)
+            .ok_or(CommonError::CalculationFailure)?;
+        Ok(())
+    }
+
+    /// the stake deposited with `deposited` lamports is deactivating with `delegated` lamports
+    pub fn on_cooling_in_unstaked(&mut self, deposited: u64, delegated: u64) -> ProgramResult {
+        self.deposit_cooling_in = self
+            .deposit_cooling_in
+            .checked_sub(deposited)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.emergency_cooling_down = self
+            .emergency_cooling_down
+            .checked_add(delegated)
+            .ok_or(CommonError::CalculationFailure)?;
+        Ok(())
+    }
+}
+
+/// Mints msol_amount for the cooling-in stake into the escrow and credits it to the stake claim
+#[allow(clippy::too_many_arguments)]
+pub fn mint_cooling_in<'info>(
+    state: &mut ProgramAccount<'info, State>,
+    stake: &StakeRecord,
+    claim_account: &AccountInfo<'info>,
+    escrow: &AccountInfo<'info>,
+    msol_mint: &AccountInfo<'info>,
+    msol_mint_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    msol_amount: u64,
+) -> ProgramResult {
+    let state_address = state.instance_address;
+    let program_id = *state.to_account_info().owner;
+    check_address(
+        claim_account.key,
+        &stake.cooling_in_claim,
+        "cooling_in_claim",
+    )?;
+    check_owner_program(claim_account, &program_id, "cooling_in_claim")?;
+    CoolingInClaim::with_escrow_seeds(&state_address, &program_id, escrow.key, |_| ())?;
+    let mut claim = CoolingInClaim::read(claim_account)?;
+    if claim.stake_account != stake.stake_account {
+        msg!(
+            "Claim {} is for stake {}",
+            claim_account.key,
+            claim.stake_account
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    state.with_msol_mint_authority_seeds(|seeds| {
+        mint_to(
+            CpiContext::new_with_signer(
+                token_program.clone(),
+                MintTo {
+                    mint: msol_mint.clone(),
+                    to: escrow.clone(),
+                    authority: msol_mint_authority.clone(),
+                },
+                &[seeds],
+            ),
+            msol_amount,
+        )
+    })?;
+    state.on_msol_mint(msol_amount);
+
+    claim.msol_amount = claim
+        .msol_amount
+        .checked_add(msol_amount)
+        .ok_or(CommonError::CalculationFailure)?;
+    claim.write(claim_account)
+}
+
+impl<'info> ClaimCoolingIn<'info> {
+    // fn claim_cooling_in()
+    // moves the mSOL of an activated cooling-in deposit from the escrow to the beneficiary
+    // and closes the claim (its rent goes to the beneficiary)
+    pub fn process(&mut self) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_CLAIM)?;
+        let state_address = self.state.instance_address;
+        let program_id = *self.state.to_account_info().owner;
+        self.claim
+            .check_held_by(&state_address, self.beneficiary.key)?;
+        check_token_mint(
+            &self.transfer_msol_to,
+            self.state.msol_mint,
+            "transfer_msol_to",
+        )?;
+        check_address(self.token_program.key, &spl_token::ID, "token_program")?;
+
+        let msol_amount = self.claim.msol_amount;
+        CoolingInClaim::with_escrow_seeds(
+            &state_address,
+            &program_id,
+            self.cooling_in_escrow.key,
+            |seeds| {
+                transfer(
+                    CpiContext::new_with_signer(
+                        self.token_program.clone(),
+                        Transfer {
+                            from: self.cooling_in_escrow.clone(),
+                            to: self.transfer_msol_to.to_account_info(),
+                            authority: self.cooling_in_escrow.clone(),
+                        },
+                        &[seeds],
+                    ),
+                    msol_amount,
+                )
+            },
+        )??;
+
+        self.claim.msol_amount = 0;
+        close_ticket(&self.claim.to_account_info(), &self.beneficiary)?;
+
+        emit!(CoolingInClaimedEvent {
+            state: state_address,
+            claim: *self.claim.to_account_info().key,
+            stake_account: self.claim.stake_account,
+            beneficiary: self.claim.beneficiary,
+            msol_amount,
+        });
+        Ok(())
+    }
+}
+
+impl<'info> UnstakeCoolingIn<'info> {
+    // fn unstake_cooling_in()
+    // Permissionless. A cooling-in stake of a removed validator can not be updated by update_active,
+    // so it is unstaked like emergency_unstake does and its depositor gets the mSOL for the stake
+    pub fn process(&mut self, stake_index: u32) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_UNSTAKE)?;
+        // the price is not trusted
+        self.state.check_not_restricted()?;
+        self.state.stake_system.check_stake_list(&self.stake_list)?;
+        self.state
+            .check_stake_deposit_authority(self.stake_deposit_authority.key)?;
+        self.state
+            .check_msol_mint(self.msol_mint.to_account_info().key)?;
+        self.state
+            .check_msol_mint_authority(self.msol_mint_authority.key)?;
+        check_owner_program(&self.stake_account, &stake::program::ID, "stake_account")?;
+        check_address(self.stake_program.key, &stake::program::ID, "stake_program")?;
+        check_address(self.token_program.key, &spl_token::ID, "token_program")?;
+
+        let mut stake = self.state.stake_system.get_checked(
+            &self.stake_list.data.as_ref().borrow(),
+            stake_index,
+            self.stake_account.to_account_info().key,
+        )?;
+        if !stake.is_cooling_in() {
+            msg!("Stake {} is not cooling in", stake.stake_account);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let delegation = self.stake_account.delegation().ok_or_else(|| {
+            msg!("Undelegated stake under marinade control!");
+            ProgramError::InvalidAccountData
+        })?;
+        if delegation.deactivation_epoch != u64::MAX {
+            msg!("Stake {} is already deactivating", stake.stake_account);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // the duplication flag of a validator is closed when it is removed from the list
+        let (duplication_flag, _) = ValidatorRecord::find_duplication_flag(
+            &self.state.instance_address,
+            &delegation.voter_pubkey,
+            self.state.to_account_info().owner,
+        );
+        check_address(
+            self.duplication_flag.key,
+            &duplication_flag,
+            "duplication_flag",
+        )?;
+        if self.duplication_flag.lamports() > 0 {
+            msg!(
+                "Validator {} is in the list. Use update_active",
+                delegation.voter_pubkey
+            );
+            return Err(CommonError::InvalidValidator.into());
+        }
+
+        msg!("Deactivate cooling-in stake {}", stake.stake_account);
+        self.state.with_stake_deposit_authority_seeds(|seeds| {
+            invoke_signed(
+                &stake::instruction::deactivate_stake(
+                    self.stake_account.to_account_info().key,
+                    self.stake_deposit_authority.key,
+                ),
+                &[
+                    self.stake_program.clone(),
+                    self.stake_account.to_account_info(),
+                    self.clock.to_account_info(),
+                    self.stake_deposit_authority.clone(),
+                ],
+                &[seeds],
+            )
+        })?;
+
+        // mint at the price before the stake joins the cooling down lamports
+        let msol_amount = self.state.calc_msol_from_lamports(delegation.stake)?;
+        mint_cooling_in(
+            &mut self.state,
+            &stake,
+            &self.cooling_in_claim,
+            &self.cooling_in_escrow,
+            &self.msol_mint.to_account_info(),
+            &self.msol_mint_authority,
+            &self.token_program,
+            msol_amount,
+        )?;
+
+        // update_deactivated returns it to the reserve
+        self.state
+            .on_cooling_in_unstaked(stake.last_update_delegated_lamports, delegation.stake)?;
+        stake.last_update_delegated_lamports = delegation.stake;
+        stake.last_update_epoch = self.clock.epoch;
+        stake.is_emergency_unstaking = 1;
+        stake.cooling_in_claim = Pubkey::default();
+        self.state.stake_system.set(
+            &mut self.stake_list.data.as_ref().borrow_mut(),
+            stake_index,
+            stake,
+        )?;
+
+        emit!(CoolingInUnstakedEvent {
+            state: self.state.instance_address,
+            stake_account: stake.stake_account,
+            stake_index,
+            validator: delegation.voter_pubkey,
+            lamports: delegation.stake,
+            msol_amount,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stake_system::StakeSystem;
+    use std::mem::MaybeUninit;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn test_state() -> State {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.available_reserve_balance = 100 * SOL;
+        state.msol_supply = 90 * SOL; // msol_price > 1
+        state.staking_sol_cap = 200 * SOL;
+        state
+    }
+
+    #[test]
+    fn test_cooling_in_deposit() -> ProgramResult {
+        let mut state = test_state();
+        let msol_for_sol = state.calc_msol_from_lamports(SOL)?;
+        state.check_staking_cap(60 * SOL)?;
+        state.on_cooling_in_deposit(60 * SOL)?;
+        // not part of the mSOL price until activated
+        assert_eq!(state.calc_msol_from_lamports(SOL)?, msol_for_sol);
+        // but it reserves its staking cap space
+        assert!(state.check_staking_cap(40 * SOL).is_ok());
+        assert!(state.check_staking_cap(40 * SOL + 1).is_err());
+
+        let mut stake_list_data = vec![0u8; 1_000];
+        let mut stake_system = StakeSystem::new(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            &mut stake_list_data,
+            3_000,
+            SOL,
+            0,
+            0,
+        )?;
+        let stake_account = Pubkey::new_unique();
+        let claim = Pubkey::new_unique();
+        let clock = Clock {
+            epoch: 7,
+            ..Default::default()
+        };
+        stake_system.add_cooling_in(
+            &mut stake_list_data,
+            &stake_account,
+            60 * SOL,
+            &clock,
+            &claim,
+        )?;
+        let stake = stake_system.get_checked(&stake_list_data, 0, &stake_account)?;
+        assert!(stake.is_cooling_in());
+        assert!(stake.check_not_cooling_in().is_err());
+        assert_eq!(stake.cooling_in_claim, claim);
+        assert_eq!(stake.last_update_delegated_lamports, 60 * SOL);
+        assert_eq!(stake.last_update_epoch, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cooling_in_activated() -> ProgramResult {
+        let mut state = test_state();
+        state.on_cooling_in_deposit(60 * SOL)?;
+        let msol_for_sol = state.calc_msol_from_lamports(SOL)?;
+
+        // update_active: mint for the delegation (with its rewards) at the current price
+        let msol_to_mint = state.calc_msol_from_lamports(61 * SOL)?;
+        state.on_msol_mint(msol_to_mint);
+        state.on_cooling_in_activated(60 * SOL, 61 * SOL)?;
+        assert_eq!(state.deposit_cooling_in, 0);
+        assert_eq!(state.validator_system.total_active_balance, 61 * SOL);
+        // the conversion does not move the price (up to rounding)
+        let msol_for_sol_after = state.calc_msol_from_lamports(SOL)?;
+        assert!(msol_for_sol_after.max(msol_for_sol) - msol_for_sol_after.min(msol_for_sol) <= 1);
+        // more than a cooling-in record holds
+        assert!(state.on_cooling_in_activated(1, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cooling_in_unstaked() -> ProgramResult {
+        let mut state = test_state();
+        state.on_cooling_in_deposit(60 * SOL)?;
+        let cap_room = 200 * SOL - 160 * SOL;
+        assert!(state.check_staking_cap(cap_room).is_ok());
+
+        // unstake_cooling_in: mint before the stake joins the cooling down lamports
+        let msol_amount = state.calc_msol_from_lamports(60 * SOL)?;
+        state.on_msol_mint(msol_amount);
+        state.on_cooling_in_unstaked(60 * SOL, 60 * SOL)?;
+        assert_eq!(state.deposit_cooling_in, 0);
+        assert_eq!(state.emergency_cooling_down, 60 * SOL);
+        assert_eq!(state.total_cooling_down(), 60 * SOL);
+        // still counted by the cap until update_deactivated returns it to the reserve
+        assert!(state.check_staking_cap(cap_room).is_ok());
+        assert!(state.check_staking_cap(cap_room + 1).is_err());
+        Ok(())
+    }
+}
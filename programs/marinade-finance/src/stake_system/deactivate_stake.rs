@@ -1,5 +1,5 @@
 use crate::error::CommonError;
-use crate::{checks::check_owner_program, stake_system::StakeSystemHelpers};
+use crate::{checks::check_owner_program, stake_system::StakeSystemHelpers, State};
 use std::convert::TryFrom;
 
 use anchor_lang::prelude::*;
@@ -12,6 +12,8 @@ use anchor_lang::solana_program::{
 
 use crate::{
     checks::{check_address, check_stake_amount_and_validator},
+    epoch_stats::EpochStats,
+    events::DeactivateStakeEvent,
     state::StateHelpers,
     DeactivateStake,
 };
@@ -20,7 +22,13 @@ impl<'info> DeactivateStake<'info> {
     //
     // fn deactivate_stake()
     //
-    pub fn process(&mut self, stake_index: u32, validator_index: u32) -> ProgramResult {
+    pub fn process(
+        &mut self,
+        stake_index: u32,
+        validator_index: u32,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_UNSTAKE)?;
         self.state.check_reserve_address(self.reserve_pda.key)?;
         self.state
             .validator_system
@@ -43,6 +51,8 @@ impl<'info> DeactivateStake<'info> {
             stake_index,
             self.stake_account.to_account_info().key,
         )?;
+        // mSOL is not minted yet for a cooling-in stake
+        stake.check_not_cooling_in()?;
         // check the account is not already in emergency_unstake
         if stake.is_emergency_unstaking != 0 {
             return Err(crate::CommonError::StakeAccountIsEmergencyUnstaking.into());
@@ -94,7 +104,6 @@ impl<'info> DeactivateStake<'info> {
         // compute target for this particular validator (total_stake_target * score/total_score)
         let validator_stake_target = self
             .state
-            .validator_system
             .validator_stake_target(&validator, total_stake_target)?;
 
         // compute how much we should unstake from this validator
@@ -123,6 +132,7 @@ impl<'info> DeactivateStake<'info> {
             },
         );
 
+        let mut split_stake_account = Pubkey::default();
         let unstaked_amount = if stake_account_target < 2 * self.state.stake_system.min_stake {
             // unstake all if what will remain in the account is < twice min_stake
             msg!("Deactivate whole stake {}", stake.stake_account);
@@ -313,6 +323,7 @@ impl<'info> DeactivateStake<'info> {
             })?;
 
             stake.last_update_delegated_lamports -= split_amount;
+            split_stake_account = *self.split_stake_account.key;
             split_amount
         };
         // we now consider amount no longer "active" for this specific validator
@@ -350,6 +361,19 @@ impl<'info> DeactivateStake<'info> {
             validator,
         )?;
 
+        EpochStats::record(
+            epoch_stats,
+            &self.state.instance_address,
+            self.state.to_account_info().owner,
+            |stats, epoch| stats.on_deactivate_stake(epoch, self.state.msol_price, unstaked_amount),
+        )?;
+        emit!(DeactivateStakeEvent {
+            state: self.state.instance_address,
+            stake_account: stake.stake_account,
+            stake_index,
+            split_stake_account,
+            lamports: unstaked_amount,
+        });
         Ok(())
     }
 }
@@ -3,23 +3,64 @@ use anchor_lang::solana_program::stake::instruction::LockupArgs;
 use anchor_lang::solana_program::{
     program::{invoke, invoke_signed},
     stake,
-    stake::state::StakeAuthorize,
+    stake::state::{StakeAuthorize, StakeState},
     system_instruction, system_program,
+    sysvar::stake_history,
 };
 use anchor_spl::token::{mint_to, MintTo};
 
 use crate::error::CommonError;
 use crate::{
     checks::{check_address, check_owner_program, check_token_mint},
-    stake_system::StakeSystemHelpers,
+    cooling_in_claim::CoolingInClaim,
+    epoch_stats::EpochStats,
+    events::DepositStakeAccountEvent,
+    stake_system::{StakeRecord, StakeSystemHelpers},
     state::StateHelpers,
-    DepositStakeAccount, ID,
+    DepositStakeAccount, DepositStakeAccountMerge, State,
 };
 
+/// stake account deposited under marinade control
+struct TakenStake {
+    stake: u64,
+    validator: Pubkey,
+    is_cooling_in: bool,
+    // moved to the reserve
+    extra_lamports: u64,
+}
+
+/// lamports above the delegation (minus rent) go to the reserve like a SOL deposit
+fn calc_extra_lamports(stake_lamports: u64, delegated: u64, rent_exempt_reserve: u64) -> u64 {
+    stake_lamports.saturating_sub(delegated.saturating_add(rent_exempt_reserve))
+}
+
+/// Returns (added_stake, returned_rent) of merging a stake of source_lamports
+/// (without its extra lamports) into a stake delegating destination_delegated.
+/// added_stake: deposited stake + part of its rent delegated by the merge,
+/// returned_rent: the rest of the deposited rent, it goes to the reserve
+fn calc_merged_deposit(
+    source_lamports: u64,
+    destination_delegated: u64,
+    merged_delegation: u64,
+) -> Result<(u64, u64), CommonError> {
+    let added_stake = merged_delegation
+        .checked_sub(destination_delegated)
+        .ok_or(CommonError::CalculationFailure)?;
+    let returned_rent = source_lamports
+        .checked_sub(added_stake)
+        .ok_or(CommonError::CalculationFailure)?;
+    Ok((added_stake, returned_rent))
+}
+
 impl<'info> DepositStakeAccount<'info> {
     pub const WAIT_EPOCHS: u64 = 2;
-    // fn deposit_stake_account()
-    pub fn process(&mut self, validator_index: u32) -> ProgramResult {
+
+    /// Checks the deposited stake, moves its extra lamports to the reserve,
+    /// accounts its stake for the validator (unless cooling in) and takes its authorities
+    fn take_stake_account(&mut self, validator_index: u32) -> Result<TakenStake, ProgramError> {
+        self.state
+            .check_not_paused(State::PAUSE_DEPOSIT_STAKE_ACCOUNT)?;
+        self.state.check_not_restricted()?;
         self.state
             .validator_system
             .check_validator_list(&self.validator_list)?;
@@ -61,7 +102,7 @@ impl<'info> DepositStakeAccount<'info> {
             ProgramError::InvalidAccountData
         })?;
 
-        if delegation.deactivation_epoch != std::u64::MAX {
+        if delegation.deactivation_epoch != u64::MAX {
             msg!(
                 "Deposited stake {} must not be cooling down",
                 self.stake_account.to_account_info().key
@@ -69,21 +110,22 @@ impl<'info> DepositStakeAccount<'info> {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        if self.clock.epoch
+        // activating stakes are accepted into the cooling-in bucket,
+        // mSOL is minted for them by update_active once they are fully active
+        let is_cooling_in = self.clock.epoch
             < delegation
                 .activation_epoch
                 .checked_add(Self::WAIT_EPOCHS)
-                .unwrap()
-        {
+                .unwrap();
+        if is_cooling_in {
             msg!(
-                "Deposited stake {} is not activated yet. Wait for #{} epoch",
+                "Deposited stake {} is not activated yet. mSOL will be minted after #{} epoch",
                 self.stake_account.to_account_info().key,
                 delegation
                     .activation_epoch
                     .checked_add(Self::WAIT_EPOCHS)
                     .unwrap()
             );
-            return Err(ProgramError::InvalidAccountData);
         }
 
         if delegation.stake < self.state.stake_system.min_stake {
@@ -96,18 +138,18 @@ impl<'info> DepositStakeAccount<'info> {
             return Err(ProgramError::InsufficientFunds);
         }
 
-        if self.stake_account.to_account_info().lamports()
-            > delegation.stake + self.stake_account.meta().unwrap().rent_exempt_reserve
-        {
-            msg!(
-                "Stake account has {} extra lamports. Please withdraw it and try again",
-                self.stake_account.to_account_info().lamports()
-                    - (delegation.stake + self.stake_account.meta().unwrap().rent_exempt_reserve)
-            );
-            return Err(ProgramError::Custom(6212));
-        }
+        let extra_lamports = calc_extra_lamports(
+            self.stake_account.to_account_info().lamports(),
+            delegation.stake,
+            self.stake_account.meta().unwrap().rent_exempt_reserve,
+        );
 
-        self.state.check_staking_cap(delegation.stake)?;
+        self.state.check_staking_cap(
+            delegation
+                .stake
+                .checked_add(extra_lamports)
+                .ok_or(CommonError::CalculationFailure)?,
+        )?;
 
         let lockup = self.stake_account.lockup().unwrap();
         // Check Lockup
@@ -116,6 +158,41 @@ impl<'info> DepositStakeAccount<'info> {
             return Err(CommonError::AccountWithLockup.into());
         }
 
+        if extra_lamports > 0 {
+            msg!(
+                "Move {} extra lamports of stake {} to the reserve",
+                extra_lamports,
+                self.stake_account.to_account_info().key
+            );
+            self.state.check_reserve_address(self.reserve_pda.key)?;
+            if !stake_history::check_id(self.stake_history.key) {
+                msg!(
+                    "Stake history sysvar must be {}. Got {}",
+                    stake_history::ID,
+                    self.stake_history.key
+                );
+                return Err(ProgramError::InvalidArgument);
+            }
+            // stake_authority is still the withdrawer here
+            invoke(
+                &stake::instruction::withdraw(
+                    self.stake_account.to_account_info().key,
+                    self.stake_authority.key,
+                    self.reserve_pda.key,
+                    extra_lamports,
+                    None,
+                ),
+                &[
+                    self.stake_program.clone(),
+                    self.stake_account.to_account_info(),
+                    self.reserve_pda.clone(),
+                    self.clock.to_account_info(),
+                    self.stake_history.clone(),
+                    self.stake_authority.clone(),
+                ],
+            )?;
+        }
+
         if validator_index == self.state.validator_system.validator_count() {
             if self.state.validator_system.auto_add_validator_enabled == 0 {
                 return Err(CommonError::InvalidValidator.into());
@@ -133,13 +210,15 @@ impl<'info> DepositStakeAccount<'info> {
                 return Err(ProgramError::InsufficientFunds);
             }
             // Add extra validator with 0 score
-            let state_address = *self.state.to_account_info().key;
+            let state_address = self.state.instance_address;
+            let program_id = *self.state.to_account_info().owner;
             self.state.validator_system.add_with_balance(
                 &mut self.validator_list.data.as_ref().borrow_mut(),
                 delegation.voter_pubkey,
                 0,
-                delegation.stake,
+                if is_cooling_in { 0 } else { delegation.stake },
                 &state_address,
+                &program_id,
                 self.duplication_flag.key,
             )?;
 
@@ -149,7 +228,7 @@ impl<'info> DepositStakeAccount<'info> {
                 self.state.validator_system.validator_count() - 1,
             )?;
             validator_record.with_duplication_flag_seeds(
-                self.state.to_account_info().key,
+                &self.state.instance_address,
                 |seeds| {
                     invoke_signed(
                         &system_instruction::create_account(
@@ -157,7 +236,7 @@ impl<'info> DepositStakeAccount<'info> {
                             self.duplication_flag.key,
                             self.rent.minimum_balance(0),
                             0,
-                            &ID,
+                            &program_id,
                         ),
                         &[
                             self.system_program.clone(),
@@ -182,15 +261,17 @@ impl<'info> DepositStakeAccount<'info> {
                 return Err(CommonError::InvalidValidator.into());
             }
 
-            validator.active_balance = validator
-                .active_balance
-                .checked_add(delegation.stake)
-                .ok_or(CommonError::CalculationFailure)?;
-            self.state.validator_system.set(
-                &mut self.validator_list.data.as_ref().borrow_mut(),
-                validator_index,
-                validator,
-            )?;
+            if !is_cooling_in {
+                validator.active_balance = validator
+                    .active_balance
+                    .checked_add(delegation.stake)
+                    .ok_or(CommonError::CalculationFailure)?;
+                self.state.validator_system.set(
+                    &mut self.validator_list.data.as_ref().borrow_mut(),
+                    validator_index,
+                    validator,
+                )?;
+            }
         }
 
         {
@@ -271,39 +352,458 @@ impl<'info> DepositStakeAccount<'info> {
             )?;
         }
 
-        self.state.stake_system.add(
-            &mut self.stake_list.data.as_ref().borrow_mut(),
-            self.stake_account.to_account_info().key,
-            delegation.stake,
-            &self.clock,
-            0, // is_emergency_unstaking? no
+        Ok(TakenStake {
+            stake: delegation.stake,
+            validator: delegation.voter_pubkey,
+            is_cooling_in,
+            extra_lamports,
+        })
+    }
+
+    fn mint_msol(&mut self, msol_to_mint: u64) -> ProgramResult {
+        if msol_to_mint > 0 {
+            self.state.with_msol_mint_authority_seeds(|mint_seeds| {
+                mint_to(
+                    CpiContext::new_with_signer(
+                        self.token_program.clone(),
+                        MintTo {
+                            mint: self.msol_mint.to_account_info(),
+                            to: self.mint_to.to_account_info(),
+                            authority: self.msol_mint_authority.clone(),
+                        },
+                        &[mint_seeds],
+                    ),
+                    msol_to_mint,
+                )
+            })?;
+            self.state.on_msol_mint(msol_to_mint);
+        }
+
+        Ok(())
+    }
+
+    /// Creates the claim of the mSOL for a cooling-in stake (and the escrow it is minted into)
+    /// Returns the claim address
+    fn create_cooling_in_claim(
+        &self,
+        stake: u64,
+        cooling_in_accounts: &[AccountInfo<'info>],
+    ) -> Result<Pubkey, ProgramError> {
+        let (claim_account, escrow) = match cooling_in_accounts {
+            [claim_account, escrow] => (claim_account, escrow),
+            _ => {
+                msg!(
+                    "Stake {} is cooling in. Need the cooling-in claim and escrow in remaining accounts",
+                    self.stake_account.to_account_info().key
+                );
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+        };
+        let state_address = self.state.instance_address;
+        let program_id = *self.state.to_account_info().owner;
+        CoolingInClaim::create_escrow_if_needed(
+            &state_address,
+            &program_id,
+            escrow,
+            &self.msol_mint.to_account_info(),
+            &self.rent_payer,
+            &self.system_program,
+            &self.token_program,
+            &self.rent,
+        )?;
+        CoolingInClaim {
+            state_address,
+            stake_account: *self.stake_account.to_account_info().key,
+            beneficiary: self.mint_to.owner,
+            lamports: stake,
+            msol_amount: 0,
+        }
+        .create_pda(
+            &program_id,
+            claim_account,
+            &self.rent_payer,
+            &self.system_program,
+            &self.rent,
         )?;
+        Ok(*claim_account.key)
+    }
 
-        let msol_to_mint = self.state.calc_msol_from_lamports(delegation.stake)?;
-
-        self.state.with_msol_mint_authority_seeds(|mint_seeds| {
-            mint_to(
-                CpiContext::new_with_signer(
-                    self.token_program.clone(),
-                    MintTo {
-                        mint: self.msol_mint.to_account_info(),
-                        to: self.mint_to.to_account_info(),
-                        authority: self.msol_mint_authority.clone(),
-                    },
-                    &[mint_seeds],
-                ),
-                msol_to_mint,
-            )
+    // fn deposit_stake_account()
+    // cooling_in_accounts: [cooling_in_claim, cooling_in_escrow], only used for an activating stake
+    pub fn process(
+        &mut self,
+        validator_index: u32,
+        cooling_in_accounts: &[AccountInfo<'info>],
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        let taken = self.take_stake_account(validator_index)?;
+
+        // mSOL for the stake (unless it is cooling in) and for the extra lamports, at the current price
+        let msol_to_mint = if taken.is_cooling_in {
+            let claim = self.create_cooling_in_claim(taken.stake, cooling_in_accounts)?;
+            self.state.stake_system.add_cooling_in(
+                &mut self.stake_list.data.as_ref().borrow_mut(),
+                self.stake_account.to_account_info().key,
+                taken.stake,
+                &self.clock,
+                &claim,
+            )?;
+            self.state.on_cooling_in_deposit(taken.stake)?;
+            self.state.calc_msol_from_lamports(taken.extra_lamports)?
+        } else {
+            self.state.stake_system.add(
+                &mut self.stake_list.data.as_ref().borrow_mut(),
+                self.stake_account.to_account_info().key,
+                taken.stake,
+                &self.clock,
+                0, // is_emergency_unstaking? no
+            )?;
+            self.state
+                .calc_msol_from_lamports(taken.stake + taken.extra_lamports)?
+        };
+
+        self.mint_msol(msol_to_mint)?;
+
+        if !taken.is_cooling_in {
+            self.state.validator_system.total_active_balance = self
+                .state
+                .validator_system
+                .total_active_balance
+                .checked_add(taken.stake)
+                .ok_or(CommonError::CalculationFailure)?;
+        }
+        self.state.on_transfer_to_reserve(taken.extra_lamports);
+
+        let deposited = taken
+            .stake
+            .checked_add(taken.extra_lamports)
+            .ok_or(CommonError::CalculationFailure)?;
+        EpochStats::record(
+            epoch_stats,
+            &self.state.instance_address,
+            self.state.to_account_info().owner,
+            |stats, epoch| stats.on_deposit(epoch, self.state.msol_price, deposited),
+        )?;
+        emit!(DepositStakeAccountEvent {
+            state: self.state.instance_address,
+            stake: *self.stake_account.to_account_info().key,
+            merged_into: Pubkey::default(),
+            validator: taken.validator,
+            delegated: taken.stake,
+            extra_lamports: taken.extra_lamports,
+            is_cooling_in: taken.is_cooling_in,
+            msol_minted: msol_to_mint,
+            msol_price: self.state.msol_price,
+        });
+        Ok(())
+    }
+}
+
+impl<'info> DepositStakeAccountMerge<'info> {
+    fn check_destination_stake(&self, destination_stake: &StakeRecord) -> ProgramResult {
+        destination_stake.check_not_cooling_in()?;
+        if destination_stake.is_emergency_unstaking != 0 {
+            return Err(CommonError::StakeAccountIsEmergencyUnstaking.into());
+        }
+        let source = self.deposit.stake_account.stake().ok_or_else(|| {
+            msg!(
+                "Deposited stake {} must be delegated",
+                self.deposit.stake_account.to_account_info().key
+            );
+            ProgramError::InvalidAccountData
         })?;
-        self.state.on_msol_mint(msol_to_mint);
+        let destination = self.destination_stake.stake().ok_or_else(|| {
+            msg!("Undelegated stake under marinade control!");
+            ProgramError::InvalidAccountData
+        })?;
+        if destination.delegation.deactivation_epoch != u64::MAX {
+            msg!(
+                "Destination stake {} must not be deactivating",
+                destination_stake.stake_account
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if destination.delegation.stake != destination_stake.last_update_delegated_lamports {
+            msg!(
+                "Destination stake {} is not updated",
+                destination_stake.stake_account
+            );
+            return Err(CommonError::StakeAccountNotUpdatedYet.into());
+        }
+        if self.deposit.clock.epoch
+            < destination
+                .delegation
+                .activation_epoch
+                .checked_add(DepositStakeAccount::WAIT_EPOCHS)
+                .unwrap()
+        {
+            msg!(
+                "Destination stake {} is not active yet",
+                destination_stake.stake_account
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if destination.delegation.voter_pubkey != source.delegation.voter_pubkey {
+            msg!(
+                "Destination validator {} doesn't match {}",
+                destination.delegation.voter_pubkey,
+                source.delegation.voter_pubkey
+            );
+            return Err(CommonError::InvalidValidator.into());
+        }
+        if destination.credits_observed != source.credits_observed {
+            msg!(
+                "Can not merge into {}: credits observed {} != {}",
+                destination_stake.stake_account,
+                destination.credits_observed,
+                source.credits_observed
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    // fn deposit_stake_account_merge()
+    // deposit_stake_account merging the deposited stake into stake_list[destination_stake_index]
+    pub fn process(
+        &mut self,
+        validator_index: u32,
+        destination_stake_index: u32,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        if validator_index == self.deposit.state.validator_system.validator_count() {
+            msg!("Can not merge into a stake of a new validator");
+            return Err(CommonError::InvalidValidator.into());
+        }
+        self.deposit
+            .state
+            .check_stake_deposit_authority(self.stake_deposit_authority.key)?;
+        self.deposit
+            .state
+            .check_stake_withdraw_authority(self.stake_withdraw_authority.key)?;
+        self.deposit
+            .state
+            .check_reserve_address(self.deposit.reserve_pda.key)?;
+        if !stake_history::check_id(self.deposit.stake_history.key) {
+            msg!(
+                "Stake history sysvar must be {}. Got {}",
+                stake_history::ID,
+                self.deposit.stake_history.key
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.deposit
+            .state
+            .stake_system
+            .check_stake_list(&self.deposit.stake_list)?;
+        let mut destination_stake = self.deposit.state.stake_system.get_checked(
+            &self.deposit.stake_list.data.as_ref().borrow(),
+            destination_stake_index,
+            self.destination_stake.to_account_info().key,
+        )?;
+        self.check_destination_stake(&destination_stake)?;
+
+        let taken = self.deposit.take_stake_account(validator_index)?;
+        if taken.is_cooling_in {
+            msg!("Can not merge a stake that is not active yet");
+            return Err(CommonError::StakeAccountIsCoolingIn.into());
+        }
+        let source_lamports = self.deposit.stake_account.to_account_info().lamports();
 
-        self.state.validator_system.total_active_balance = self
+        msg!(
+            "Merge deposited stake {} into {}",
+            self.deposit.stake_account.to_account_info().key,
+            destination_stake.stake_account
+        );
+        self.deposit
+            .state
+            .with_stake_deposit_authority_seeds(|seeds| {
+                invoke_signed(
+                    &stake::instruction::merge(
+                        self.destination_stake.to_account_info().key,
+                        self.deposit.stake_account.to_account_info().key,
+                        self.stake_deposit_authority.key,
+                    )[0],
+                    &[
+                        self.deposit.stake_program.clone(),
+                        self.destination_stake.to_account_info(),
+                        self.deposit.stake_account.to_account_info(),
+                        self.deposit.clock.to_account_info(),
+                        self.deposit.stake_history.clone(),
+                        self.stake_deposit_authority.clone(),
+                    ],
+                    &[seeds],
+                )
+            })?;
+        // reread stake after merging
+        let result_stake: StakeState = self
+            .destination_stake
+            .to_account_info()
+            .deserialize_data()
+            .map_err(|err| ProgramError::BorshIoError(err.to_string()))?;
+        let merged_delegation = result_stake.delegation().unwrap().stake;
+        let (added_stake, returned_rent) = calc_merged_deposit(
+            source_lamports,
+            destination_stake.last_update_delegated_lamports,
+            merged_delegation,
+        )?;
+        if returned_rent > 0 {
+            self.deposit
+                .state
+                .with_stake_withdraw_authority_seeds(|seeds| {
+                    invoke_signed(
+                        &stake::instruction::withdraw(
+                            self.destination_stake.to_account_info().key,
+                            self.stake_withdraw_authority.key,
+                            self.deposit.reserve_pda.key,
+                            returned_rent,
+                            None,
+                        ),
+                        &[
+                            self.deposit.stake_program.clone(),
+                            self.destination_stake.to_account_info(),
+                            self.deposit.reserve_pda.clone(),
+                            self.deposit.clock.to_account_info(),
+                            self.deposit.stake_history.clone(),
+                            self.stake_withdraw_authority.clone(),
+                        ],
+                        &[seeds],
+                    )
+                })?;
+        }
+        let to_reserve = taken
+            .extra_lamports
+            .checked_add(returned_rent)
+            .ok_or(CommonError::CalculationFailure)?;
+
+        // the user gets mSOL for all the deposited lamports
+        let msol_to_mint = self.deposit.state.calc_msol_from_lamports(
+            added_stake
+                .checked_add(to_reserve)
+                .ok_or(CommonError::CalculationFailure)?,
+        )?;
+        self.deposit.mint_msol(msol_to_mint)?;
+
+        // take_stake_account accounted taken.stake for the validator
+        let extra_delegated = added_stake
+            .checked_sub(taken.stake)
+            .ok_or(CommonError::CalculationFailure)?;
+        if extra_delegated > 0 {
+            let mut validator = self.deposit.state.validator_system.get(
+                &self.deposit.validator_list.data.as_ref().borrow(),
+                validator_index,
+            )?;
+            validator.active_balance = validator
+                .active_balance
+                .checked_add(extra_delegated)
+                .ok_or(CommonError::CalculationFailure)?;
+            self.deposit.state.validator_system.set(
+                &mut self.deposit.validator_list.data.as_ref().borrow_mut(),
+                validator_index,
+                validator,
+            )?;
+        }
+        self.deposit.state.validator_system.total_active_balance = self
+            .deposit
             .state
             .validator_system
             .total_active_balance
-            .checked_add(delegation.stake)
+            .checked_add(added_stake)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.deposit.state.on_transfer_to_reserve(to_reserve);
+        let deposited = added_stake
+            .checked_add(to_reserve)
             .ok_or(CommonError::CalculationFailure)?;
+        EpochStats::record(
+            epoch_stats,
+            &self.deposit.state.instance_address,
+            self.deposit.state.to_account_info().owner,
+            |stats, epoch| stats.on_deposit(epoch, self.deposit.state.msol_price, deposited),
+        )?;
+
+        destination_stake.last_update_delegated_lamports = merged_delegation;
+        self.deposit.state.stake_system.set(
+            &mut self.deposit.stake_list.data.as_ref().borrow_mut(),
+            destination_stake_index,
+            destination_stake,
+        )?;
+
+        emit!(DepositStakeAccountEvent {
+            state: self.deposit.state.instance_address,
+            stake: *self.deposit.stake_account.to_account_info().key,
+            merged_into: destination_stake.stake_account,
+            validator: taken.validator,
+            delegated: added_stake,
+            extra_lamports: to_reserve,
+            is_cooling_in: false,
+            msol_minted: msol_to_mint,
+            msol_price: self.deposit.state.msol_price,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    const SOL: u64 = 1_000_000_000;
+    const RENT: u64 = 2_282_880;
+
+    #[test]
+    fn test_extra_lamports() -> ProgramResult {
+        assert_eq!(calc_extra_lamports(10 * SOL + RENT, 10 * SOL, RENT), 0);
+        assert_eq!(
+            calc_extra_lamports(12 * SOL + RENT, 10 * SOL, RENT),
+            2 * SOL
+        );
+        // rewards not delegated yet are extra lamports too
+        assert_eq!(calc_extra_lamports(10 * SOL + RENT + 5, 10 * SOL, RENT), 5);
+        assert_eq!(calc_extra_lamports(10 * SOL, 10 * SOL, RENT), 0);
+
+        // the extra lamports are minted at the price like a SOL deposit
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.available_reserve_balance = 1_000 * SOL;
+        state.msol_supply = 800 * SOL; // msol_price = 1.25
+        let extra_lamports = calc_extra_lamports(12 * SOL + RENT, 10 * SOL, RENT);
+        assert_eq!(
+            state.calc_msol_from_lamports(extra_lamports)?,
+            1_600_000_000
+        );
+        state.on_transfer_to_reserve(extra_lamports);
+        assert_eq!(state.available_reserve_balance, 1_002 * SOL);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merged_deposit() -> ProgramResult {
+        // 10 SOL stake with 2 SOL extra merged into a 100 SOL stake,
+        // the merge delegates the source rent too
+        let extra_lamports = calc_extra_lamports(12 * SOL + RENT, 10 * SOL, RENT);
+        let source_lamports = 10 * SOL + RENT;
+        let (added_stake, returned_rent) =
+            calc_merged_deposit(source_lamports, 100 * SOL, 110 * SOL + RENT)?;
+        assert_eq!((added_stake, returned_rent), (10 * SOL + RENT, 0));
+        // the merge keeps the source rent undelegated: it is returned to the reserve
+        let (added_stake, returned_rent) =
+            calc_merged_deposit(source_lamports, 100 * SOL, 110 * SOL)?;
+        assert_eq!((added_stake, returned_rent), (10 * SOL, RENT));
+
+        // the user gets mSOL for added_stake + to_reserve: all the deposited lamports
+        let to_reserve = extra_lamports + returned_rent;
+        assert_eq!(added_stake + to_reserve, 12 * SOL + RENT);
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.available_reserve_balance = 1_000 * SOL;
+        state.msol_supply = 1_000 * SOL;
+        assert_eq!(
+            state.calc_msol_from_lamports(added_stake + to_reserve)?,
+            12 * SOL + RENT
+        );
 
+        // the destination stake must not shrink and the source must cover the added stake
+        assert!(calc_merged_deposit(source_lamports, 100 * SOL, 99 * SOL).is_err());
+        assert!(calc_merged_deposit(source_lamports, 100 * SOL, 111 * SOL).is_err());
         Ok(())
     }
 }
@@ -1,6 +1,8 @@
 use crate::{
     checks::{check_owner_program, check_stake_amount_and_validator},
     error::CommonError,
+    events::EmergencyUnstakeEvent,
+    roles::Role,
     stake_system::StakeSystemHelpers,
 };
 
@@ -10,13 +12,15 @@ use anchor_lang::solana_program::{
     stake::{self},
 };
 
-use crate::{checks::check_address, EmergencyUnstake};
+use crate::{checks::check_address, EmergencyUnstake, State};
 
 impl<'info> EmergencyUnstake<'info> {
     pub fn process(&mut self, stake_index: u32, validator_index: u32) -> ProgramResult {
-        self.state
-            .validator_system
-            .check_validator_manager_authority(self.validator_manager_authority.key)?;
+        self.state.check_not_paused(State::PAUSE_UNSTAKE)?;
+        self.state.check_validator_manager_or_role(
+            Role::EmergencyOperator,
+            self.validator_manager_authority.key,
+        )?;
         self.state
             .validator_system
             .check_validator_list(&self.validator_list)?;
@@ -33,6 +37,8 @@ impl<'info> EmergencyUnstake<'info> {
             stake_index,
             self.stake_account.to_account_info().key,
         )?;
+        // mSOL is not minted yet for a cooling-in stake
+        stake.check_not_cooling_in()?;
 
         let mut validator = self
             .state
@@ -108,6 +114,13 @@ impl<'info> EmergencyUnstake<'info> {
             validator,
         )?;
 
+        emit!(EmergencyUnstakeEvent {
+            state: self.state.instance_address,
+            stake_account: stake.stake_account,
+            stake_index,
+            validator: validator.validator_account,
+            lamports: unstake_amount,
+        });
         Ok(())
     }
 }
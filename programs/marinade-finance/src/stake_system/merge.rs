@@ -7,8 +7,9 @@ use anchor_lang::solana_program::{
 use crate::{
     checks::{check_address, check_owner_program},
     error::CommonError,
+    events::MergeStakesEvent,
     stake_system::StakeSystemHelpers,
-    MergeStakes,
+    MergeStakes, State,
 };
 
 impl<'info> MergeStakes<'info> {
@@ -18,6 +19,7 @@ impl<'info> MergeStakes<'info> {
         source_stake_index: u32,
         validator_index: u32,
     ) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_MERGE_STAKES)?;
         self.state.stake_system.check_stake_list(&self.stake_list)?;
         self.state
             .validator_system
@@ -47,6 +49,7 @@ impl<'info> MergeStakes<'info> {
             destination_stake_index,
             self.destination_stake.to_account_info().key,
         )?;
+        destination_stake_info.check_not_cooling_in()?;
         let destination_delegation = if let Some(delegation) = self.destination_stake.delegation() {
             delegation
         } else {
@@ -56,7 +59,7 @@ impl<'info> MergeStakes<'info> {
             );
             return Err(ProgramError::InvalidArgument);
         };
-        if destination_delegation.deactivation_epoch != std::u64::MAX {
+        if destination_delegation.deactivation_epoch != u64::MAX {
             msg!(
                 "Destination stake {} must not be deactivating",
                 self.destination_stake.to_account_info().key
@@ -83,6 +86,7 @@ impl<'info> MergeStakes<'info> {
             source_stake_index,
             self.source_stake.to_account_info().key,
         )?;
+        source_stake_info.check_not_cooling_in()?;
         let source_delegation = if let Some(delegation) = self.source_stake.delegation() {
             delegation
         } else {
@@ -92,7 +96,7 @@ impl<'info> MergeStakes<'info> {
             );
             return Err(ProgramError::InvalidArgument);
         };
-        if source_delegation.deactivation_epoch != std::u64::MAX {
+        if source_delegation.deactivation_epoch != u64::MAX {
             msg!(
                 "Source stake {} must not be deactivating",
                 self.source_stake.to_account_info().key
@@ -215,6 +219,17 @@ impl<'info> MergeStakes<'info> {
                 extra_delegated
             );
         }
+        emit!(MergeStakesEvent {
+            state: self.state.instance_address,
+            destination_stake: destination_stake_info.stake_account,
+            source_stake: source_stake_info.stake_account,
+            validator: validator.validator_account,
+            merged_lamports: source_stake_info
+                .last_update_delegated_lamports
+                .checked_add(extra_delegated)
+                .ok_or(CommonError::CalculationFailure)?,
+            to_operational_sol_account: returned_stake_rent,
+        });
         Ok(())
     }
 }
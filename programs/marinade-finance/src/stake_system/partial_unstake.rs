@@ -1,6 +1,9 @@
 use crate::{
     checks::{check_owner_program, check_stake_amount_and_validator},
+    events::PartialUnstakeEvent,
+    roles::Role,
     stake_system::StakeSystemHelpers,
+    State,
 };
 use std::convert::TryFrom;
 
@@ -21,13 +24,15 @@ impl<'info> PartialUnstake<'info> {
         validator_index: u32,
         desired_unstake_amount: u64,
     ) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_UNSTAKE)?;
         assert!(
             desired_unstake_amount >= self.state.stake_system.min_stake,
             "desired_unstake_amount too low"
         );
-        self.state
-            .validator_system
-            .check_validator_manager_authority(self.validator_manager_authority.key)?;
+        self.state.check_validator_manager_or_role(
+            Role::EmergencyOperator,
+            self.validator_manager_authority.key,
+        )?;
         self.state
             .validator_system
             .check_validator_list(&self.validator_list)?;
@@ -49,6 +54,8 @@ impl<'info> PartialUnstake<'info> {
             stake_index,
             self.stake_account.to_account_info().key,
         )?;
+        // mSOL is not minted yet for a cooling-in stake
+        stake.check_not_cooling_in()?;
 
         // check the account is not already in emergency_unstake
         if stake.is_emergency_unstaking != 0 {
@@ -74,7 +81,6 @@ impl<'info> PartialUnstake<'info> {
         // compute target for this particular validator (total_stake_target * score/total_score)
         let validator_stake_target = self
             .state
-            .validator_system
             .validator_stake_target(&validator, total_stake_target)?;
         // if validator is already on-target (or the split will be lower than min_stake), exit now
         if validator.active_balance <= validator_stake_target + self.state.stake_system.min_stake {
@@ -100,6 +106,7 @@ impl<'info> PartialUnstake<'info> {
             .last_update_delegated_lamports
             .saturating_sub(unstake_amount);
 
+        let mut split_stake_account = Pubkey::default();
         let unstaked_from_account = if stake_account_after < self.state.stake_system.min_stake {
             // unstake all if what will remain in the account is < min_stake
             msg!("Deactivate whole stake {}", stake.stake_account);
@@ -276,6 +283,7 @@ impl<'info> PartialUnstake<'info> {
 
             // update amount accounted for this account
             stake.last_update_delegated_lamports -= unstake_amount;
+            split_stake_account = *self.split_stake_account.key;
 
             // effective unstaked_from_account
             unstake_amount
@@ -300,6 +308,14 @@ impl<'info> PartialUnstake<'info> {
             validator,
         )?;
 
+        emit!(PartialUnstakeEvent {
+            state: self.state.instance_address,
+            stake_account: stake.stake_account,
+            stake_index,
+            split_stake_account,
+            validator: validator.validator_account,
+            lamports: unstaked_from_account,
+        });
         Ok(())
     }
 }
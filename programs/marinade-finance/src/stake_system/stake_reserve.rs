@@ -1,10 +1,12 @@
 use crate::{
     checks::{check_address, check_owner_program},
+    epoch_stats::EpochStats,
     error::CommonError,
+    events::StakeReserveEvent,
     stake_system::StakeSystemHelpers,
     stake_wrapper::StakeWrapper,
     state::StateHelpers,
-    StakeReserve,
+    StakeReserve, State,
 };
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
@@ -38,7 +40,12 @@ impl<'info> StakeReserve<'info> {
     /// Receives self.stake_account where to stake, normally an empty account (new keypair)
     /// stakes from available delta-stake in data.validator_index
     /// pub fn stake_reserve()
-    pub fn process(&mut self, validator_index: u32) -> ProgramResult {
+    pub fn process(
+        &mut self,
+        validator_index: u32,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_STAKE_RESERVE)?;
         sol_log_compute_units();
         msg!("Stake reserve");
         self.state
@@ -105,7 +112,7 @@ impl<'info> StakeReserve<'info> {
             .get(&self.validator_list.data.as_ref().borrow(), validator_index)?;
 
         check_address(
-            &self.validator_vote.key,
+            self.validator_vote.key,
             &validator.validator_account,
             "validator_vote",
         )?;
@@ -141,7 +148,6 @@ impl<'info> StakeReserve<'info> {
 
         let validator_stake_target = self
             .state
-            .validator_system
             .validator_stake_target(&validator, total_stake_target)?;
 
         //verify the validator is under-staked
@@ -233,7 +239,6 @@ impl<'info> StakeReserve<'info> {
             0, // is_emergency_unstaking? no
         )?;
 
-        // self.state.epoch_stake_orders -= amount;
         validator.active_balance = validator
             .active_balance
             .checked_add(stake_target)
@@ -252,6 +257,21 @@ impl<'info> StakeReserve<'info> {
             .total_active_balance
             .checked_add(stake_target)
             .ok_or(CommonError::CalculationFailure)?;
+        EpochStats::record(
+            epoch_stats,
+            &self.state.instance_address,
+            self.state.to_account_info().owner,
+            |stats, epoch| stats.on_stake_reserve(epoch, self.state.msol_price, stake_target),
+        )?;
+        emit!(StakeReserveEvent {
+            state: self.state.instance_address,
+            stake_account: *self.stake_account.to_account_info().key,
+            stake_index: self.state.stake_system.stake_count() - 1,
+            validator: validator.validator_account,
+            validator_index,
+            amount: stake_target,
+            reserve_balance: self.state.available_reserve_balance,
+        });
         Ok(())
     }
 }
@@ -0,0 +1,345 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    program::{invoke, invoke_signed},
+    stake::{self, state::StakeAuthorize, state::StakeState},
+    system_instruction, system_program,
+};
+use anchor_spl::token::{burn, Burn};
+
+use crate::{
+    checks::{check_address, check_owner_program, check_stake_amount_and_validator},
+    error::CommonError,
+    events::WithdrawStakeAccountEvent,
+    stake_system::StakeSystemHelpers,
+    state::order_unstake::check_burn_msol_from,
+    State, WithdrawStakeAccount,
+};
+
+/// lamports split off a stake of delegated_lamports for msol_amount.
+/// Both the split and what is left in our account must be valid stakes (>= min_stake)
+fn calc_split_amount(
+    state: &State,
+    msol_amount: u64,
+    delegated_lamports: u64,
+) -> Result<u64, ProgramError> {
+    let split_amount = state.calc_lamports_from_msol_amount(msol_amount)?;
+    if split_amount < state.stake_system.min_stake {
+        msg!(
+            "Withdraw stake of {} lamports is less than min_stake {}",
+            split_amount,
+            state.stake_system.min_stake
+        );
+        return Err(ProgramError::InsufficientFunds);
+    }
+    if delegated_lamports.saturating_sub(split_amount) < state.stake_system.min_stake {
+        msg!(
+            "Stake has {} lamports. Can not leave less than min_stake after withdraw",
+            delegated_lamports
+        );
+        return Err(ProgramError::InsufficientFunds);
+    }
+    Ok(split_amount)
+}
+
+impl<'info> WithdrawStakeAccount<'info> {
+    fn prepare_split_stake_account(&self) -> ProgramResult {
+        let stake_account_len = std::mem::size_of::<StakeState>();
+        if self.split_stake_account.owner == &system_program::ID {
+            // empty account
+            invoke(
+                &system_instruction::create_account(
+                    self.split_stake_rent_payer.key,
+                    self.split_stake_account.key,
+                    self.rent.minimum_balance(stake_account_len),
+                    stake_account_len as u64,
+                    &stake::program::ID,
+                ),
+                &[
+                    self.system_program.clone(),
+                    self.split_stake_rent_payer.clone(),
+                    self.split_stake_account.clone(),
+                ],
+            )?;
+        } else {
+            // ready uninitialized stake
+            check_owner_program(
+                &self.split_stake_account,
+                &stake::program::ID,
+                "split_stake_account",
+            )?;
+            if self.split_stake_account.data_len() < stake_account_len {
+                msg!(
+                    "Split stake account {} must have at least {} bytes (got {})",
+                    self.split_stake_account.key,
+                    stake_account_len,
+                    self.split_stake_account.data_len()
+                );
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if !self.rent.is_exempt(
+                self.split_stake_account.lamports(),
+                self.split_stake_account.data_len(),
+            ) {
+                msg!(
+                    "Split stake account {} must be rent-exempt",
+                    self.split_stake_account.key
+                );
+                return Err(ProgramError::InsufficientFunds);
+            }
+            match bincode::deserialize(&self.split_stake_account.data.as_ref().borrow())
+                .map_err(|err| ProgramError::BorshIoError(err.to_string()))?
+            {
+                StakeState::Uninitialized => (),
+                _ => {
+                    msg!(
+                        "Split stake {} must be uninitialized",
+                        self.split_stake_account.key
+                    );
+                    return Err(ProgramError::InvalidAccountData);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // fn withdraw_stake_account()
+    // burns msol_amount and gives the user a stake account split from stake_list[stake_index]
+    // with staker and withdrawer authorities set to beneficiary
+    pub fn process(
+        &mut self,
+        stake_index: u32,
+        validator_index: u32,
+        msol_amount: u64,
+        beneficiary: Pubkey,
+    ) -> ProgramResult {
+        self.state
+            .check_not_paused(State::PAUSE_WITHDRAW_STAKE_ACCOUNT)?;
+        self.state.check_not_restricted()?;
+        self.state
+            .validator_system
+            .check_validator_list(&self.validator_list)?;
+        self.state.stake_system.check_stake_list(&self.stake_list)?;
+        self.state
+            .check_msol_mint(self.msol_mint.to_account_info().key)?;
+        self.state
+            .check_stake_deposit_authority(self.stake_deposit_authority.key)?;
+        self.state
+            .check_stake_withdraw_authority(self.stake_withdraw_authority.key)?;
+        check_burn_msol_from(
+            &self.state,
+            &self.burn_msol_from,
+            &self.burn_msol_authority,
+            msol_amount,
+        )?;
+        check_owner_program(&self.stake_account, &stake::program::ID, "stake_account")?;
+        check_owner_program(
+            &self.split_stake_rent_payer,
+            &system_program::ID,
+            "split_stake_rent_payer",
+        )?;
+        check_address(
+            self.system_program.key,
+            &system_program::ID,
+            "system_program",
+        )?;
+        check_address(self.token_program.key, &spl_token::ID, "token_program")?;
+        check_address(self.stake_program.key, &stake::program::ID, "stake_program")?;
+
+        let mut validator = self
+            .state
+            .validator_system
+            .get(&self.validator_list.data.as_ref().borrow(), validator_index)?;
+
+        let mut stake = self.state.stake_system.get_checked(
+            &self.stake_list.data.as_ref().borrow(),
+            stake_index,
+            self.stake_account.to_account_info().key,
+        )?;
+        // mSOL is not minted yet for a cooling-in stake
+        stake.check_not_cooling_in()?;
+
+        if stake.is_emergency_unstaking != 0 {
+            return Err(CommonError::StakeAccountIsEmergencyUnstaking.into());
+        }
+        // stake account must be updated and delegated to the validator_index sent
+        check_stake_amount_and_validator(
+            &self.stake_account.inner,
+            stake.last_update_delegated_lamports,
+            &validator.validator_account,
+        )?;
+        if self.stake_account.delegation().unwrap().deactivation_epoch != u64::MAX {
+            msg!(
+                "Stake {} is cooling down",
+                self.stake_account.to_account_info().key
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let split_amount = calc_split_amount(
+            &self.state,
+            msol_amount,
+            stake.last_update_delegated_lamports,
+        )?;
+
+        self.prepare_split_stake_account()?;
+        self.state.on_directed_unstake(split_amount)?;
+
+        msg!(
+            "Withdraw split {} ({} lamports) from stake {}",
+            self.split_stake_account.key,
+            split_amount,
+            stake.stake_account
+        );
+
+        burn(
+            CpiContext::new(
+                self.token_program.clone(),
+                Burn {
+                    mint: self.msol_mint.to_account_info(),
+                    to: self.burn_msol_from.to_account_info(),
+                    authority: self.burn_msol_authority.clone(),
+                },
+            ),
+            msol_amount,
+        )?;
+        self.state.on_msol_burn(msol_amount)?;
+
+        // split & give staker authority to the beneficiary
+        self.state.with_stake_deposit_authority_seeds(|seeds| {
+            let split_instruction = stake::instruction::split(
+                self.stake_account.to_account_info().key,
+                self.stake_deposit_authority.key,
+                split_amount,
+                self.split_stake_account.key,
+            )
+            .last()
+            .unwrap()
+            .clone();
+            invoke_signed(
+                &split_instruction,
+                &[
+                    self.stake_program.clone(),
+                    self.stake_account.to_account_info(),
+                    self.split_stake_account.to_account_info(),
+                    self.stake_deposit_authority.clone(),
+                ],
+                &[seeds],
+            )?;
+
+            invoke_signed(
+                &stake::instruction::authorize(
+                    self.split_stake_account.key,
+                    self.stake_deposit_authority.key,
+                    &beneficiary,
+                    StakeAuthorize::Staker,
+                    None,
+                ),
+                &[
+                    self.stake_program.clone(),
+                    self.split_stake_account.to_account_info(),
+                    self.clock.to_account_info(),
+                    self.stake_deposit_authority.clone(),
+                ],
+                &[seeds],
+            )
+        })?;
+
+        // give withdrawer authority to the beneficiary
+        self.state.with_stake_withdraw_authority_seeds(|seeds| {
+            invoke_signed(
+                &stake::instruction::authorize(
+                    self.split_stake_account.key,
+                    self.stake_withdraw_authority.key,
+                    &beneficiary,
+                    StakeAuthorize::Withdrawer,
+                    None,
+                ),
+                &[
+                    self.stake_program.clone(),
+                    self.split_stake_account.to_account_info(),
+                    self.clock.to_account_info(),
+                    self.stake_withdraw_authority.clone(),
+                ],
+                &[seeds],
+            )
+        })?;
+
+        // the split stake is no longer ours
+        stake.last_update_delegated_lamports -= split_amount;
+        validator.active_balance = validator
+            .active_balance
+            .checked_sub(split_amount)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.state.validator_system.total_active_balance = self
+            .state
+            .validator_system
+            .total_active_balance
+            .checked_sub(split_amount)
+            .ok_or(CommonError::CalculationFailure)?;
+
+        self.state.stake_system.set(
+            &mut self.stake_list.data.as_ref().borrow_mut(),
+            stake_index,
+            stake,
+        )?;
+        self.state.validator_system.set(
+            &mut self.validator_list.data.as_ref().borrow_mut(),
+            validator_index,
+            validator,
+        )?;
+
+        emit!(WithdrawStakeAccountEvent {
+            state: self.state.instance_address,
+            stake: stake.stake_account,
+            split_stake: *self.split_stake_account.key,
+            beneficiary,
+            msol_burned: msol_amount,
+            lamports: split_amount,
+            msol_price: self.state.msol_price,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    const SOL: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_calc_split_amount() -> ProgramResult {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.available_reserve_balance = 1_000 * SOL;
+        state.msol_supply = 800 * SOL; // msol_price = 1.25
+        state.stake_system.min_stake = SOL;
+
+        // split at the mSOL price
+        assert_eq!(calc_split_amount(&state, 4 * SOL, 10 * SOL)?, 5 * SOL);
+        // exactly min_stake left
+        assert_eq!(calc_split_amount(&state, 4 * SOL, 6 * SOL)?, 5 * SOL);
+        // less than min_stake left
+        assert!(calc_split_amount(&state, 4 * SOL, 6 * SOL - 1).is_err());
+        // the whole stake can not be withdrawn
+        assert!(calc_split_amount(&state, 8 * SOL, 10 * SOL).is_err());
+        // split below min_stake
+        assert!(calc_split_amount(&state, SOL / 2, 10 * SOL).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_withdraw_directed_stake() -> ProgramResult {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.available_reserve_balance = 100 * SOL;
+        state.msol_supply = 100 * SOL;
+        state.total_directed_stake = 40 * SOL;
+        state.stake_system.min_stake = SOL;
+
+        let split_amount = calc_split_amount(&state, 10 * SOL, 50 * SOL)?;
+        // a tenth of the stake leaves, so does a tenth of the directed stake
+        state.on_directed_unstake(split_amount)?;
+        assert_eq!(state.total_directed_stake, 36 * SOL);
+        Ok(())
+    }
+}
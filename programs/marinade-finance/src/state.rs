@@ -1,24 +1,37 @@
 use crate::{
-    calc::{shares_from_value, value_from_shares},
+    calc::{proportional, shares_from_value, value_from_shares},
     checks::check_address,
     error::CommonError,
     liq_pool::LiqPool,
     located::Located,
+    roles::Roles,
     stake_system::StakeSystem,
-    validator_system::ValidatorSystem,
-    Fee, ID,
+    state::{change_authority::PendingAuthority, price_history::PriceHistory},
+    validator_system::{ScoreFormula, ValidatorRecord, ValidatorSystem},
+    Fee,
 };
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::program_pack::Pack;
 use std::mem::MaybeUninit;
 
+pub mod acknowledge_circuit_breaker;
+pub mod cancel_ticket;
 pub mod change_authority;
 pub mod claim;
 pub mod config_marinade;
 pub mod deposit;
+pub mod deposit_directed;
+pub mod deposit_wsol;
 pub mod initialize;
 pub mod liquid_unstake;
+pub mod migrate;
 pub mod order_unstake;
+pub mod pause;
+pub mod pending_config;
+pub mod price_history;
+pub mod quote;
+pub mod split_ticket;
+pub mod transfer_ticket;
 pub mod update;
 
 #[account]
@@ -46,11 +59,7 @@ pub struct State {
     pub stake_system: StakeSystem,
     pub validator_system: ValidatorSystem, //includes total_balance = total stake under management
 
-    // sum of all the orders received in this epoch
-    // must not be used for stake-unstake amount calculation
-    // only for reference
-    // epoch_stake_orders: u64,
-    // epoch_unstake_orders: u64,
+    // sums of the orders received in each epoch are in the EpochStats account
     pub liq_pool: LiqPool,
     pub available_reserve_balance: u64, // reserve_pda.lamports() - self.rent_exempt_for_token_acc. Virtual value (real may be > because of transfers into reserve). Use Update* to align
     pub msol_supply: u64, // Virtual value (may be < because of token burn). Use Update* to align
@@ -67,13 +76,73 @@ pub struct State {
     pub staking_sol_cap: u64,
 
     pub emergency_cooling_down: u64,
+
+    /// State::LAYOUT_VERSION the account was initialized or migrated with.
+    /// All fields below were added after the first deployment, they read as 0 in old accounts
+    pub layout_version: u8,
+    /// key of the account this instance was initialized at. Seed of its PDAs and the state_address
+    /// of its accounts: it stays the same when migrate_state moves the state into a bigger account
+    pub instance_address: Pubkey,
+
+    /// can pause operations (not unpause). Default = only admin
+    pub pause_authority: Pubkey,
+    /// bitmask of State::PAUSE_* operations rejected while set
+    pub paused_operations: u64,
+
+    /// max mSOL price change during one epoch, relative to price_band_reference. 0 = no limit
+    pub max_msol_price_increase: Fee,
+    pub max_msol_price_decrease: Fee,
+    /// msol_price before the first update of price_band_epoch
+    pub price_band_reference: u64,
+    pub price_band_epoch: u64,
+    /// set by the update crank when the price leaves the band or mSOL is minted outside.
+    /// Deposits, unstakes, ticket cancels and cooling-in mSOL mints are rejected
+    /// until admin acknowledges it
+    pub restricted_mode: u8,
+
+    /// authority change waiting for accept_authority
+    pub pending_authority: PendingAuthority,
+    /// slots between propose_authority and accept_authority. 0 = no timelock
+    pub authority_timelock_slots: u64,
+
+    /// keys with restricted admin/validator_manager powers
+    pub roles: Roles,
+
+    /// msol_price at the end of the last epochs
+    pub price_history: PriceHistory,
+
+    /// lamports of stake accounts deposited while activating, mSOL is not minted for them yet
+    /// (not part of the mSOL price until update_active sees them fully active)
+    pub deposit_cooling_in: u64,
+    /// lamports deposited with deposit_directed and still directed, all validators together.
+    /// Unstakes reduce it in proportion, so it is split between validators by directed_stake_shares
+    pub total_directed_stake: u64,
+    /// sum of all validators directed_stake_shares
+    pub total_directed_shares: u64,
+    /// epochs after the last directed deposit to a validator before its directed stake expires
+    pub directed_stake_epochs: u64,
+    pub score_formula: ScoreFormula,
+
+    /// record size of the list being moved by migrate_list
+    pub migrating_list_item_size: u32,
+
+    /// space for new fields without changing the account size.
+    /// A slot is removed for every 16 bytes of added fields (borsh has no [u8; N] for any N)
+    pub reserved: [[u8; 16]; 29],
 }
 
 impl State {
     pub const PRICE_DENOMINATOR: u64 = 0x1_0000_0000;
+    /// bump when fields needing non-zero defaults are added to the tail (see migrate_state)
+    pub const LAYOUT_VERSION: u8 = 1;
+    pub const DEFAULT_DIRECTED_STAKE_EPOCHS: u64 = 30;
+    pub const MAX_DIRECTED_STAKE_EPOCHS: u64 = 365;
     /// Suffix for reserve account seed
     pub const RESERVE_SEED: &'static [u8] = b"reserve";
     pub const MSOL_MINT_AUTHORITY_SEED: &'static [u8] = b"st_mint";
+    /// Seed of the temporary wSOL token account used by deposit_wsol and add_liquidity_wsol,
+    /// followed by the wSOL account funding the instruction
+    pub const TEMP_WSOL_SEED: &'static [u8] = b"temp_wsol";
 
     // Account seeds for simplification of creation (optional)
     pub const STAKE_LIST_SEED: &'static str = "stake_list";
@@ -87,23 +156,84 @@ impl State {
             + 8
     }
 
-    pub fn find_msol_mint_authority(state: &Pubkey) -> (Pubkey, u8) {
+    pub fn find_msol_mint_authority(state: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
         Pubkey::find_program_address(
             &[&state.to_bytes()[..32], State::MSOL_MINT_AUTHORITY_SEED],
-            &ID,
+            program_id,
+        )
+    }
+
+    pub fn find_reserve_address(state: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::RESERVE_SEED], program_id)
+    }
+
+    pub fn find_temp_wsol_account(
+        state: &Pubkey,
+        wsol_account: &Pubkey,
+        program_id: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                &state.to_bytes()[..32],
+                Self::TEMP_WSOL_SEED,
+                &wsol_account.to_bytes()[..32],
+            ],
+            program_id,
         )
     }
 
-    pub fn find_reserve_address(state: &Pubkey) -> (Pubkey, u8) {
-        Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::RESERVE_SEED], &ID)
+    pub fn default_stake_list_address(state: &Pubkey, program_id: &Pubkey) -> Pubkey {
+        Pubkey::create_with_seed(state, Self::STAKE_LIST_SEED, program_id).unwrap()
     }
 
-    pub fn default_stake_list_address(state: &Pubkey) -> Pubkey {
-        Pubkey::create_with_seed(state, Self::STAKE_LIST_SEED, &ID).unwrap()
+    pub fn default_validator_list_address(state: &Pubkey, program_id: &Pubkey) -> Pubkey {
+        Pubkey::create_with_seed(state, Self::VALIDATOR_LIST_SEED, program_id).unwrap()
     }
 
-    pub fn default_validator_list_address(state: &Pubkey) -> Pubkey {
-        Pubkey::create_with_seed(state, Self::VALIDATOR_LIST_SEED, &ID).unwrap()
+    /// check_not_paused operations
+    pub const PAUSE_DEPOSIT: u64 = 1 << 0;
+    pub const PAUSE_DEPOSIT_STAKE_ACCOUNT: u64 = 1 << 1;
+    pub const PAUSE_LIQUID_UNSTAKE: u64 = 1 << 2;
+    pub const PAUSE_ORDER_UNSTAKE: u64 = 1 << 3;
+    pub const PAUSE_CLAIM: u64 = 1 << 4;
+    /// transfer, split and cancel tickets
+    pub const PAUSE_TICKET: u64 = 1 << 5;
+    pub const PAUSE_ADD_LIQUIDITY: u64 = 1 << 6;
+    pub const PAUSE_REMOVE_LIQUIDITY: u64 = 1 << 7;
+    pub const PAUSE_WITHDRAW_STAKE_ACCOUNT: u64 = 1 << 8;
+    pub const PAUSE_STAKE_RESERVE: u64 = 1 << 9;
+    /// update_active and update_deactivated
+    pub const PAUSE_UPDATE: u64 = 1 << 10;
+    /// deactivate_stake, partial_unstake and emergency_unstake
+    pub const PAUSE_UNSTAKE: u64 = 1 << 11;
+    pub const PAUSE_MERGE_STAKES: u64 = 1 << 12;
+    pub const PAUSE_ALL: u64 = (1 << 13) - 1;
+
+    pub fn check_not_paused(&self, operation: u64) -> ProgramResult {
+        if self.paused_operations & operation != 0 {
+            msg!("Operation {:#x} is paused", operation);
+            return Err(CommonError::OperationPaused.into());
+        }
+        Ok(())
+    }
+
+    pub fn check_not_restricted(&self) -> ProgramResult {
+        if self.restricted_mode != 0 {
+            msg!("Restricted mode. Waiting for admin to acknowledge the circuit breaker");
+            return Err(CommonError::RestrictedMode.into());
+        }
+        Ok(())
+    }
+
+    /// is msol_price out of [reference - max decrease, reference + max increase]
+    pub fn is_msol_price_out_of_band(&self) -> bool {
+        let reference = self.price_band_reference;
+        (self.max_msol_price_increase.basis_points > 0
+            && self.msol_price
+                > reference.saturating_add(self.max_msol_price_increase.apply(reference)))
+            || (self.max_msol_price_decrease.basis_points > 0
+                && self.msol_price
+                    < reference.saturating_sub(self.max_msol_price_decrease.apply(reference)))
     }
 
     pub fn check_admin_authority(&self, admin_authority: &Pubkey) -> ProgramResult {
@@ -189,10 +319,13 @@ impl State {
             .expect("Total SOLs under control overflow")
     }
 
+    /// cooling-in deposits count from the deposit on: they are converted to active stake
+    /// later by update_active, which can not refuse them
     pub fn check_staking_cap(&self, transfering_lamports: u64) -> ProgramResult {
         let result_amount = self
             .total_lamports_under_control()
-            .checked_add(transfering_lamports)
+            .checked_add(self.deposit_cooling_in)
+            .and_then(|amount| amount.checked_add(transfering_lamports))
             .ok_or_else(|| {
                 msg!("SOL overflow");
                 ProgramError::InvalidArgument
@@ -232,6 +365,125 @@ impl State {
         )
     }
 
+    /// deposit of lamports when the liq-pool mSOL leg holds msol_leg_balance:
+    /// returns (mSOL sold by the liq-pool, lamports paid to the liq-pool, mSOL to mint)
+    pub fn calc_deposit(
+        &self,
+        lamports: u64,
+        msol_leg_balance: u64,
+    ) -> Result<(u64, u64, u64), CommonError> {
+        //compute how many mSOL to sell/mint for the user, base on how many lamports being deposited
+        let user_msol_buy_order = self.calc_msol_from_lamports(lamports)?;
+
+        //First we try to "sell" mSOL to the user from the LiqPool.
+        //The LiqPool needs to get rid of their mSOL because it works better if fully "unbalanced", i.e. with all SOL no mSOL
+        //so, if we can, the LiqPool "sells" mSOL to the user (no fee)
+        //
+        // At max, we can sell all the mSOL in the LiqPool.mSOL_leg
+        let swap_msol_max: u64 = user_msol_buy_order.min(msol_leg_balance);
+
+        // how much lamports go into the LiqPool?
+        let lamports_for_the_liq_pool = if swap_msol_max == 0 {
+            0
+        } else if user_msol_buy_order == swap_msol_max {
+            //we are fulfilling 100% the user order
+            lamports //100% of the user deposit
+        } else {
+            //partially filled
+            //then it's the lamport value of the tokens we're selling
+            self.calc_lamports_from_msol_amount(swap_msol_max)?
+        };
+
+        //compute how much msol_to_mint for the rest
+        //NOTE: it is IMPORTANT to use calc_msol_from_lamports() BEFORE adding the lamports
+        // because on_transfer_to_reserve(user_lamports) alters price calculation
+        // the same goes for state.on_msol_mint()
+        let msol_to_mint =
+            self.calc_msol_from_lamports(lamports.saturating_sub(lamports_for_the_liq_pool))?;
+        Ok((swap_msol_max, lamports_for_the_liq_pool, msol_to_mint))
+    }
+
+    /// LP tokens for adding lamports to the liq-pool
+    /// with sol_leg_lamports (without rent) and msol_leg_balance
+    pub fn calc_lp_from_lamports(
+        &self,
+        lamports: u64,
+        sol_leg_lamports: u64,
+        msol_leg_balance: u64,
+    ) -> Result<u64, CommonError> {
+        let msol_leg_value = self.calc_lamports_from_msol_amount(msol_leg_balance)?;
+        let total_liq_pool_value = sol_leg_lamports
+            .checked_add(msol_leg_value)
+            .ok_or(CommonError::CalculationFailure)?;
+        msg!(
+            "liq_pool SOL:{}, liq_pool mSOL value:{} liq_pool_value:{}",
+            sol_leg_lamports,
+            msol_leg_value,
+            total_liq_pool_value
+        );
+        shares_from_value(lamports, total_liq_pool_value, self.liq_pool.lp_supply)
+    }
+
+    /// liquid unstake of msol_amount when the liq-pool sol leg has max_lamports available:
+    /// returns (msol_fee, lamports the user receives)
+    pub fn calc_liquid_unstake(
+        &self,
+        msol_amount: u64,
+        max_lamports: u64,
+    ) -> Result<(u64, u64), CommonError> {
+        // fee is computed based on the liquidity *after* the user takes the sol
+        let user_remove_lamports = self.calc_lamports_from_msol_amount(msol_amount)?;
+        let liquid_unstake_fee = if user_remove_lamports >= max_lamports {
+            // user is removing all liquidity
+            self.liq_pool.lp_max_fee
+        } else {
+            let after_lamports = max_lamports - user_remove_lamports; //how much will be left?
+            self.liq_pool.linear_fee(after_lamports)
+        };
+
+        // compute fee in msol
+        let msol_fee = liquid_unstake_fee.apply(msol_amount);
+
+        // fee goes into treasury & LPs, so the user receives lamport value of data.msol_amount - msol_fee
+        // compute how many lamports the msol_amount the user is "selling" (minus fee) is worth
+        let working_lamports_value = self.calc_lamports_from_msol_amount(msol_amount - msol_fee)?;
+        Ok((msol_fee, working_lamports_value))
+    }
+
+    /// inverse of calc_liquid_unstake: the minimal mSOL amount <= max_msol_in
+    /// for which the user receives at least `lamports`.
+    /// The fee curve makes the received lamports grow with msol_amount, so a binary search finds it
+    pub fn calc_liquid_unstake_msol_for_lamports(
+        &self,
+        lamports: u64,
+        max_lamports: u64,
+        max_msol_in: u64,
+    ) -> Result<u64, CommonError> {
+        let (_, max_lamports_out) = self.calc_liquid_unstake(max_msol_in, max_lamports)?;
+        if max_lamports_out < lamports {
+            msg!(
+                "{} mSOL can get only {} lamports. Requested {}",
+                max_msol_in,
+                max_lamports_out,
+                lamports
+            );
+            return Err(CommonError::SlippageExceeded);
+        }
+        // invariant: calc_liquid_unstake(high) >= lamports
+        let mut low: u64 = 0;
+        let mut high = max_msol_in;
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let (_, lamports_out) = self.calc_liquid_unstake(middle, max_lamports)?;
+            if lamports_out >= lamports {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+        Ok(high)
+    }
+
     // **i128**: when do staking/unstaking use real reserve balance instead of virtual field
     pub fn stake_delta(&self, reserve_balance: u64) -> i128 {
         // Never try to stake lamports from emergency_cooling_down
@@ -255,6 +507,153 @@ impl State {
         }
     }
 
+    pub fn directed_stake_lamports(&self, validator: &ValidatorRecord) -> Result<u64, CommonError> {
+        value_from_shares(
+            validator.directed_stake_shares,
+            self.total_directed_stake,
+            self.total_directed_shares,
+        )
+    }
+
+    /// directed stake + score-weighted share of the rest of total_stake_target.
+    /// Directed stake is ignored for validators with score 0 (disabled by the manager)
+    pub fn validator_stake_target(
+        &self,
+        validator: &ValidatorRecord,
+        total_stake_target: u64,
+    ) -> Result<u64, CommonError> {
+        let directed_stake = self.directed_stake_lamports(validator)?;
+        let directed_stake_target = if validator.score == 0 {
+            0
+        } else if total_stake_target < self.total_directed_stake {
+            // not enough stake for all directed deposits
+            proportional(
+                directed_stake,
+                total_stake_target,
+                self.total_directed_stake,
+            )?
+        } else {
+            directed_stake
+        };
+        if self.validator_system.total_validator_score == 0 {
+            return Ok(directed_stake_target);
+        }
+        let score_stake_target = proportional(
+            total_stake_target.saturating_sub(self.total_directed_stake),
+            validator.score as u64,
+            self.validator_system.total_validator_score as u64,
+        )?;
+        directed_stake_target
+            .checked_add(score_stake_target)
+            .ok_or(CommonError::CalculationFailure)
+    }
+
+    /// shares left after all the directed stake was unstaked are worth nothing, but would take
+    /// a part of the next directed deposit: they are cleared from all the validators first
+    pub fn clear_stale_directed_shares(&mut self, validator_list_data: &mut [u8]) -> ProgramResult {
+        if self.total_directed_stake != 0 || self.total_directed_shares == 0 {
+            return Ok(());
+        }
+        for index in 0..self.validator_system.validator_count() {
+            let mut validator = self.validator_system.get(validator_list_data, index)?;
+            if validator.directed_stake_shares != 0 {
+                validator.directed_stake_shares = 0;
+                self.validator_system
+                    .set(validator_list_data, index, validator)?;
+            }
+        }
+        self.total_directed_shares = 0;
+        Ok(())
+    }
+
+    /// lamports deposited into the reserve for the validator. Extends its directed stake expiry.
+    /// Call clear_stale_directed_shares first
+    pub fn on_directed_deposit(
+        &mut self,
+        validator: &mut ValidatorRecord,
+        lamports: u64,
+        epoch: u64,
+    ) -> Result<(), CommonError> {
+        let shares = shares_from_value(
+            lamports,
+            self.total_directed_stake,
+            self.total_directed_shares,
+        )?;
+        validator.directed_stake_shares = validator
+            .directed_stake_shares
+            .checked_add(shares)
+            .ok_or(CommonError::CalculationFailure)?;
+        validator.directed_stake_expiry_epoch = epoch
+            .checked_add(self.directed_stake_epochs)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.total_directed_shares = self
+            .total_directed_shares
+            .checked_add(shares)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.total_directed_stake = self
+            .total_directed_stake
+            .checked_add(lamports)
+            .ok_or(CommonError::CalculationFailure)?;
+        Ok(())
+    }
+
+    /// removes all the directed stake of the validator (expired or removed validator).
+    /// Returns its lamports
+    pub fn remove_directed_stake(
+        &mut self,
+        validator: &mut ValidatorRecord,
+    ) -> Result<u64, CommonError> {
+        let lamports = self.directed_stake_lamports(validator)?;
+        self.total_directed_stake = self
+            .total_directed_stake
+            .checked_sub(lamports)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.total_directed_shares = self
+            .total_directed_shares
+            .checked_sub(validator.directed_stake_shares)
+            .ok_or(CommonError::CalculationFailure)?;
+        validator.directed_stake_shares = 0;
+        Ok(lamports)
+    }
+
+    /// unstaking users can not be linked to directed deposits,
+    /// so every unstake reduces all directed stake in proportion.
+    /// Call before the unstake changes total_virtual_staked_lamports
+    pub fn on_directed_unstake(&mut self, lamports: u64) -> Result<(), CommonError> {
+        let total_virtual_staked_lamports = self.total_virtual_staked_lamports();
+        let reduction = if lamports >= total_virtual_staked_lamports {
+            self.total_directed_stake
+        } else {
+            proportional(
+                self.total_directed_stake,
+                lamports,
+                total_virtual_staked_lamports,
+            )?
+        };
+        self.total_directed_stake -= reduction;
+        Ok(())
+    }
+
+    /// reverts on_directed_unstake of a cancelled unstake order (rounded down).
+    /// Call before the cancel changes total_virtual_staked_lamports
+    pub fn on_directed_unstake_cancel(&mut self, lamports: u64) -> Result<(), CommonError> {
+        let total_virtual_staked_lamports = self.total_virtual_staked_lamports();
+        if total_virtual_staked_lamports == 0 {
+            // the order took all the directed stake, the shares of validators are unknown
+            return Ok(());
+        }
+        let restored = proportional(
+            self.total_directed_stake,
+            lamports,
+            total_virtual_staked_lamports,
+        )?;
+        self.total_directed_stake = self
+            .total_directed_stake
+            .checked_add(restored)
+            .ok_or(CommonError::CalculationFailure)?;
+        Ok(())
+    }
+
     pub fn on_transfer_to_reserve(&mut self, amount: u64) {
         self.available_reserve_balance = self
             .available_reserve_balance
@@ -284,16 +683,6 @@ impl State {
             .ok_or(CommonError::CalculationFailure)?;
         Ok(())
     }
-
-    /*
-    pub fn register_stake_order(&mut self, lamports_amount: u64) {
-        self.epoch_stake_orders += lamports_amount;
-    }
-
-    pub fn register_unstake_order(&mut self, unstaker_index: u32, lamports_amount: u64) {
-        self.epoch_unstake_orders += lamports_amount;
-        self.unstakers[unstaker_index as usize].amount += lamports_amount;
-    }*/
 }
 
 pub trait StateHelpers {
@@ -313,25 +702,27 @@ where
 {
     fn msol_mint_authority(&self) -> Pubkey {
         self.with_msol_mint_authority_seeds(|seeds| {
-            Pubkey::create_program_address(seeds, &ID).unwrap()
+            Pubkey::create_program_address(seeds, &self.owner()).unwrap()
         })
     }
 
     fn with_msol_mint_authority_seeds<R, F: FnOnce(&[&[u8]]) -> R>(&self, f: F) -> R {
         f(&[
-            &self.key().to_bytes()[..32],
+            &self.as_ref().instance_address.to_bytes()[..32],
             State::MSOL_MINT_AUTHORITY_SEED,
             &[self.as_ref().msol_mint_authority_bump_seed],
         ])
     }
 
     fn reserve_address(&self) -> Pubkey {
-        self.with_reserve_seeds(|seeds| Pubkey::create_program_address(seeds, &ID).unwrap())
+        self.with_reserve_seeds(|seeds| {
+            Pubkey::create_program_address(seeds, &self.owner()).unwrap()
+        })
     }
 
     fn with_reserve_seeds<R, F: FnOnce(&[&[u8]]) -> R>(&self, f: F) -> R {
         f(&[
-            &self.key().to_bytes()[..32],
+            &self.as_ref().instance_address.to_bytes()[..32],
             State::RESERVE_SEED,
             &[self.as_ref().reserve_bump_seed],
         ])
@@ -349,3 +740,184 @@ where
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CommonError;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn test_state() -> State {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.available_reserve_balance = 1_000 * SOL;
+        state.msol_supply = 900 * SOL; // msol_price > 1
+        state.liq_pool.lp_liquidity_target = 100 * SOL;
+        state.liq_pool.lp_min_fee = Fee::from_basis_points(30);
+        state.liq_pool.lp_max_fee = Fee::from_basis_points(300);
+        state
+    }
+
+    fn lamports_out(state: &State, msol_amount: u64, max_lamports: u64) -> u64 {
+        state
+            .calc_liquid_unstake(msol_amount, max_lamports)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn test_msol_for_lamports_bounds() -> Result<(), CommonError> {
+        let state = test_state();
+        let max_lamports = 50 * SOL;
+        assert_eq!(
+            state.calc_liquid_unstake_msol_for_lamports(0, max_lamports, 10 * SOL)?,
+            0
+        );
+        // all of max_msol_in is needed for its own output
+        let max_msol_in = 10 * SOL;
+        let max_out = lamports_out(&state, max_msol_in, max_lamports);
+        let msol_amount =
+            state.calc_liquid_unstake_msol_for_lamports(max_out, max_lamports, max_msol_in)?;
+        assert!(msol_amount <= max_msol_in);
+        assert!(lamports_out(&state, msol_amount, max_lamports) >= max_out);
+        Ok(())
+    }
+
+    #[test]
+    fn test_msol_for_lamports_exact_target() -> Result<(), CommonError> {
+        let state = test_state();
+        let max_lamports = 50 * SOL;
+        let max_msol_in = 60 * SOL; // enough to drain the liq pool
+        for target in [1, 12_345, SOL, 7 * SOL + 1, 40 * SOL] {
+            let msol_amount =
+                state.calc_liquid_unstake_msol_for_lamports(target, max_lamports, max_msol_in)?;
+            assert!(lamports_out(&state, msol_amount, max_lamports) >= target);
+            // minimal amount
+            assert!(msol_amount > 0);
+            assert!(lamports_out(&state, msol_amount - 1, max_lamports) < target);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_msol_for_lamports_unreachable() {
+        let state = test_state();
+        let max_lamports = 50 * SOL;
+        let max_msol_in = 10 * SOL;
+        let max_out = lamports_out(&state, max_msol_in, max_lamports);
+        assert!(matches!(
+            state.calc_liquid_unstake_msol_for_lamports(max_out + 1, max_lamports, max_msol_in),
+            Err(CommonError::SlippageExceeded)
+        ));
+    }
+
+    #[test]
+    fn test_directed_stake() -> Result<(), CommonError> {
+        let mut state = test_state();
+        state.directed_stake_epochs = 10;
+        state.validator_system.total_validator_score = 100;
+        let mut first = ValidatorRecord {
+            score: 50,
+            ..Default::default()
+        };
+        let mut second = ValidatorRecord {
+            score: 50,
+            ..Default::default()
+        };
+        state.on_directed_deposit(&mut first, 100 * SOL, 5)?;
+        state.on_directed_deposit(&mut second, 300 * SOL, 6)?;
+        assert_eq!(first.directed_stake_expiry_epoch, 15);
+        assert_eq!(state.directed_stake_lamports(&first)?, 100 * SOL);
+        assert_eq!(state.directed_stake_lamports(&second)?, 300 * SOL);
+
+        // directed stake + half of the rest
+        assert_eq!(
+            state.validator_stake_target(&first, 1_000 * SOL)?,
+            100 * SOL + 300 * SOL
+        );
+        // not enough stake for the directed deposits
+        assert_eq!(state.validator_stake_target(&second, 200 * SOL)?, 150 * SOL);
+
+        // unstaking 10% of the total stake reduces all directed stake by 10%
+        state.on_directed_unstake(100 * SOL)?;
+        assert_eq!(state.total_directed_stake, 360 * SOL);
+        assert_eq!(state.directed_stake_lamports(&first)?, 90 * SOL);
+        assert_eq!(state.directed_stake_lamports(&second)?, 270 * SOL);
+
+        // a new deposit does not change the value of the older ones (rounded down)
+        state.on_directed_deposit(&mut first, 10 * SOL, 7)?;
+        assert_eq!(state.directed_stake_lamports(&first)?, 100 * SOL - 1);
+        assert_eq!(state.directed_stake_lamports(&second)?, 270 * SOL);
+
+        assert_eq!(state.remove_directed_stake(&mut second)?, 270 * SOL);
+        assert_eq!(second.directed_stake_shares, 0);
+        assert_eq!(state.total_directed_stake, 100 * SOL);
+        assert_eq!(state.total_directed_shares, first.directed_stake_shares);
+
+        // unstaking everything
+        state.on_directed_unstake(state.total_virtual_staked_lamports())?;
+        assert_eq!(state.directed_stake_lamports(&first)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_directed_unstake_cancel() -> Result<(), CommonError> {
+        let mut state = test_state();
+        let mut first = ValidatorRecord::default();
+        state.on_directed_deposit(&mut first, 400 * SOL, 0)?;
+        // order_unstake of 10% of the total stake
+        state.on_directed_unstake(100 * SOL)?;
+        state.circulating_ticket_balance += 100 * SOL;
+        assert_eq!(state.total_directed_stake, 360 * SOL);
+        // cancel_ticket
+        state.on_directed_unstake_cancel(100 * SOL)?;
+        state.circulating_ticket_balance -= 100 * SOL;
+        assert_eq!(state.total_directed_stake, 400 * SOL);
+        assert_eq!(state.directed_stake_lamports(&first)?, 400 * SOL);
+        Ok(())
+    }
+
+    #[test]
+    fn test_clear_stale_directed_shares() -> ProgramResult {
+        let mut state = test_state();
+        let mut list_data = vec![0u8; ValidatorSystem::bytes_for_list(2, 0) as usize];
+        state.validator_system =
+            ValidatorSystem::new(Pubkey::new_unique(), &mut list_data, Pubkey::default(), 0)?;
+        for _ in 0..2 {
+            state.validator_system.validator_list.push(
+                &mut list_data,
+                ValidatorRecord {
+                    score: 50,
+                    ..Default::default()
+                },
+                "validator_list",
+            )?;
+        }
+        let directed_deposit =
+            |state: &mut State, list_data: &mut [u8], index: u32, lamports: u64| {
+                state.clear_stale_directed_shares(list_data)?;
+                let mut validator = state.validator_system.get(list_data, index)?;
+                state.on_directed_deposit(&mut validator, lamports, 0)?;
+                state.validator_system.set(list_data, index, validator)
+            };
+        directed_deposit(&mut state, &mut list_data, 0, 100 * SOL)?;
+        directed_deposit(&mut state, &mut list_data, 1, 300 * SOL)?;
+        state.on_directed_unstake(state.total_virtual_staked_lamports())?;
+        assert_eq!(state.total_directed_stake, 0);
+        assert_eq!(state.total_directed_shares, 400 * SOL);
+
+        directed_deposit(&mut state, &mut list_data, 0, 10 * SOL)?;
+        let first = state.validator_system.get(&list_data, 0)?;
+        let second = state.validator_system.get(&list_data, 1)?;
+        assert_eq!(state.directed_stake_lamports(&first)?, 10 * SOL);
+        assert_eq!(second.directed_stake_shares, 0);
+        assert_eq!(state.directed_stake_lamports(&second)?, 0);
+        assert_eq!(state.total_directed_shares, first.directed_stake_shares);
+
+        // not stale: kept
+        directed_deposit(&mut state, &mut list_data, 1, 10 * SOL)?;
+        let first = state.validator_system.get(&list_data, 0)?;
+        assert_eq!(state.directed_stake_lamports(&first)?, 10 * SOL);
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    admin_council::CouncilAction, events::CircuitBreakerAcknowledgedEvent,
+    AcknowledgeCircuitBreaker,
+};
+
+impl<'info> AcknowledgeCircuitBreaker<'info> {
+    // fn acknowledge_circuit_breaker()
+    // admin leaves the restricted mode accepting the current mSOL price
+    pub fn process(&mut self) -> ProgramResult {
+        self.state.check_admin_or_council(
+            &self.admin_authority,
+            self.state.to_account_info().owner,
+            CouncilAction::AcknowledgeCircuitBreaker,
+        )?;
+        if self.state.restricted_mode == 0 {
+            msg!("Not in restricted mode");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        self.state.restricted_mode = 0;
+        // measure the rest of the epoch from the acknowledged price
+        self.state.price_band_reference = self.state.msol_price;
+        emit!(CircuitBreakerAcknowledgedEvent {
+            state: self.state.instance_address,
+            msol_price: self.state.msol_price,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{mint_to, MintTo};
+
+use crate::{
+    checks::{check_address, check_owner_program, check_token_mint},
+    epoch_stats::EpochStats,
+    events::CancelTicketEvent,
+    state::StateHelpers,
+    ticket_account::close_ticket,
+    CancelTicket, CommonError, State,
+};
+
+/// CancelTicket instruction: reverts OrderUnstake re-minting mSOL at the current price.
+/// Only possible in the epoch the ticket was created and before the stake delta started,
+/// so the ticket lamports were not accounted for by deactivate_stake yet
+impl<'info> CancelTicket<'info> {
+    fn check_cancellable(&self) -> ProgramResult {
+        if self.clock.epoch != self.ticket_account.created_epoch
+            || self.state.stake_system.last_stake_delta_epoch == self.clock.epoch
+        {
+            msg!(
+                "Ticket of epoch {} can not be cancelled in epoch {} (last stake delta epoch {})",
+                self.ticket_account.created_epoch,
+                self.clock.epoch,
+                self.state.stake_system.last_stake_delta_epoch
+            );
+            return Err(CommonError::TicketNotCancellable.into());
+        }
+        Ok(())
+    }
+
+    pub fn process(&mut self, epoch_stats: Option<&AccountInfo>) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_TICKET)?;
+        self.state.check_not_restricted()?;
+        // fn cancel_ticket()
+        check_address(self.token_program.key, &spl_token::ID, "token_program")?;
+        self.state
+            .check_msol_mint(self.msol_mint.to_account_info().key)?;
+        self.state
+            .check_msol_mint_authority(self.msol_mint_authority.key)?;
+        check_token_mint(&self.mint_to, self.state.msol_mint, "mint_to")?;
+        check_owner_program(
+            &self.ticket_account,
+            self.state.to_account_info().owner,
+            "ticket_account",
+        )?;
+        self.ticket_account
+            .check_held_by(&self.state.instance_address, self.beneficiary.key)?;
+        self.check_cancellable()?;
+
+        let lamports = self.ticket_account.lamports_amount;
+        //NOTE: compute mSOL BEFORE changing circulating_ticket_balance because it alters price calculation
+        let msol_to_mint = self.state.calc_msol_from_lamports(lamports)?;
+        msg!("--- msol_to_mint {}", msol_to_mint);
+        // the directed stake reduced by the order
+        self.state.on_directed_unstake_cancel(lamports)?;
+
+        // circulating_ticket_balance -
+        self.state.circulating_ticket_balance = self
+            .state
+            .circulating_ticket_balance
+            .checked_sub(lamports)
+            .ok_or(CommonError::CalculationFailure)?;
+        self.state.circulating_ticket_count -= 1;
+        //disable ticket-account
+        self.ticket_account.lamports_amount = 0;
+
+        self.state.with_msol_mint_authority_seeds(|mint_seeds| {
+            mint_to(
+                CpiContext::new_with_signer(
+                    self.token_program.clone(),
+                    MintTo {
+                        mint: self.msol_mint.to_account_info(),
+                        to: self.mint_to.to_account_info(),
+                        authority: self.msol_mint_authority.clone(),
+                    },
+                    &[mint_seeds],
+                ),
+                msol_to_mint,
+            )
+        })?;
+        self.state.on_msol_mint(msol_to_mint);
+
+        EpochStats::record(
+            epoch_stats,
+            &self.state.instance_address,
+            self.state.to_account_info().owner,
+            |stats, epoch| stats.on_cancel_ticket(epoch, self.state.msol_price, lamports),
+        )?;
+        emit!(CancelTicketEvent {
+            state: self.state.instance_address,
+            ticket: *self.ticket_account.to_account_info().key,
+            beneficiary: *self.beneficiary.key,
+            lamports,
+            msol_minted: msol_to_mint,
+            msol_price: self.state.msol_price,
+        });
+        // return ticket rent to the beneficiary
+        close_ticket(&self.ticket_account.to_account_info(), &self.beneficiary)
+    }
+}
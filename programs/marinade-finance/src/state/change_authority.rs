@@ -1,27 +1,237 @@
 use anchor_lang::prelude::*;
 
-use crate::{ChangeAuthority, ChangeAuthorityData};
+use crate::{
+    admin_council::AdminCouncil,
+    events::{AuthorityAcceptedEvent, AuthorityProposedEvent},
+    AcceptAuthority, ChangeAuthorityData, ProposeAuthority, State,
+};
 
-impl<'info> ChangeAuthority<'info> {
+/// Authorities proposed by admin. Applied by accept_authority once the timelock expired.
+/// Fixed size: fields not changed by the proposal keep the current values
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct PendingAuthority {
+    pub is_proposed: u8,
+    pub admin_authority: Pubkey,
+    pub validator_manager: Pubkey,
+    pub operational_sol_account: Pubkey,
+    pub treasury_msol_account: Pubkey,
+    pub pause_authority: Pubkey,
+    pub timelock_slots: u64,
+    /// accept_authority is possible starting from this slot
+    pub accept_from_slot: u64,
+}
+
+impl State {
+    /// admin proposal, also executed by the admin council.
+    /// data with all fields None cancels the pending proposal
+    pub fn propose_authority(&mut self, data: ChangeAuthorityData, slot: u64) -> ProgramResult {
+        if data == ChangeAuthorityData::default() {
+            msg!("Cancel authority proposal");
+            self.pending_authority = PendingAuthority::default();
+            return Ok(());
+        }
+
+        let accept_from_slot = slot
+            .checked_add(self.authority_timelock_slots)
+            .ok_or(ProgramError::InvalidArgument)?;
+        self.pending_authority = PendingAuthority {
+            is_proposed: 1,
+            admin_authority: data.admin.unwrap_or(self.admin_authority),
+            validator_manager: data
+                .validator_manager
+                .unwrap_or(self.validator_system.manager_authority),
+            operational_sol_account: data
+                .operational_sol_account
+                .unwrap_or(self.operational_sol_account),
+            treasury_msol_account: data
+                .treasury_msol_account
+                .unwrap_or(self.treasury_msol_account),
+            pause_authority: data.pause_authority.unwrap_or(self.pause_authority),
+            timelock_slots: data.timelock_slots.unwrap_or(self.authority_timelock_slots),
+            accept_from_slot,
+        };
+        msg!(
+            "Authority change proposed. Can be accepted from slot {}",
+            accept_from_slot
+        );
+
+        Ok(())
+    }
+
+    /// the pending proposal if it can be accepted at slot
+    pub fn pending_authority_at(&self, slot: u64) -> Result<PendingAuthority, ProgramError> {
+        let pending = self.pending_authority;
+        if pending.is_proposed == 0 {
+            msg!("No authority change proposed");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if slot < pending.accept_from_slot {
+            msg!(
+                "Authority change is timelocked until slot {}",
+                pending.accept_from_slot
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(pending)
+    }
+
+    /// applies the pending proposal once its new signing authorities were checked
+    pub fn accept_authority(&mut self, pending: PendingAuthority) {
+        self.admin_authority = pending.admin_authority;
+        self.validator_system.manager_authority = pending.validator_manager;
+        self.operational_sol_account = pending.operational_sol_account;
+        self.treasury_msol_account = pending.treasury_msol_account;
+        self.pause_authority = pending.pause_authority;
+        self.authority_timelock_slots = pending.timelock_slots;
+        self.pending_authority = PendingAuthority::default();
+    }
+}
+
+impl<'info> ProposeAuthority<'info> {
+    // fn propose_authority()
     pub fn process(&mut self, data: ChangeAuthorityData) -> ProgramResult {
         self.state.check_admin_authority(self.admin_authority.key)?;
+        self.state.propose_authority(data, self.clock.slot)?;
+        emit!(AuthorityProposedEvent {
+            state: self.state.instance_address,
+            pending: self.state.pending_authority,
+        });
+        Ok(())
+    }
+}
 
-        if let Some(admin) = data.admin {
-            self.state.admin_authority = admin;
+impl<'info> AcceptAuthority<'info> {
+    fn check_signed(signers: &[AccountInfo], key: &Pubkey, field_name: &str) -> ProgramResult {
+        if signers
+            .iter()
+            .any(|signer| signer.key == key && signer.is_signer)
+        {
+            Ok(())
+        } else {
+            msg!("{} {} must sign", field_name, key);
+            Err(ProgramError::MissingRequiredSignature)
         }
+    }
 
-        if let Some(validator_manager) = data.validator_manager {
-            self.state.validator_system.manager_authority = validator_manager;
+    /// admin council can not sign. An initialized council of this state proves the address
+    fn check_admin_signed_or_council(
+        &self,
+        signers: &[AccountInfo],
+        key: &Pubkey,
+    ) -> ProgramResult {
+        if let Some(council) = signers.iter().find(|account| account.key == key) {
+            if council.owner == self.state.to_account_info().owner {
+                let council = AdminCouncil::try_deserialize(&mut &council.data.borrow()[..])?;
+                return council.check_state_address(&self.state.instance_address);
+            }
         }
+        Self::check_signed(signers, key, "admin_authority")
+    }
 
-        if let Some(operational_sol_account) = data.operational_sol_account {
-            self.state.operational_sol_account = operational_sol_account;
-        }
+    // fn accept_authority()
+    // new signing authorities must be in remaining accounts as signers
+    // (or the new admin council account).
+    // Proposals without new signing authorities were signed by admin already, so anyone can accept them
+    pub fn process(&mut self, signers: &[AccountInfo]) -> ProgramResult {
+        let pending = self.state.pending_authority_at(self.clock.slot)?;
 
-        if let Some(treasury_msol_account) = data.treasury_msol_account {
-            self.state.treasury_msol_account = treasury_msol_account;
+        if pending.admin_authority != self.state.admin_authority {
+            self.check_admin_signed_or_council(signers, &pending.admin_authority)?;
+        }
+        if pending.validator_manager != self.state.validator_system.manager_authority {
+            Self::check_signed(signers, &pending.validator_manager, "validator_manager")?;
+        }
+        if pending.pause_authority != self.state.pause_authority
+            && pending.pause_authority != Pubkey::default()
+        {
+            Self::check_signed(signers, &pending.pause_authority, "pause_authority")?;
         }
 
+        self.state.accept_authority(pending);
+
+        emit!(AuthorityAcceptedEvent {
+            state: self.state.instance_address,
+            accepted: pending,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    fn test_state() -> State {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.admin_authority = Pubkey::new_unique();
+        state.validator_system.manager_authority = Pubkey::new_unique();
+        state.operational_sol_account = Pubkey::new_unique();
+        state.treasury_msol_account = Pubkey::new_unique();
+        state.pause_authority = Pubkey::new_unique();
+        state.authority_timelock_slots = 100;
+        state
+    }
+
+    #[test]
+    fn test_propose_and_accept_authority() -> ProgramResult {
+        let mut state = test_state();
+        let old_admin = state.admin_authority;
+        let old_manager = state.validator_system.manager_authority;
+        let new_admin = Pubkey::new_unique();
+        state.propose_authority(
+            ChangeAuthorityData {
+                admin: Some(new_admin),
+                timelock_slots: Some(50),
+                ..Default::default()
+            },
+            1_000,
+        )?;
+        // the current timelock applies to this proposal
+        assert_eq!(state.pending_authority.accept_from_slot, 1_100);
+        assert_eq!(state.admin_authority, old_admin);
+        assert!(state.pending_authority_at(1_099).is_err());
+
+        let pending = state.pending_authority_at(1_100)?;
+        state.accept_authority(pending);
+        assert_eq!(state.admin_authority, new_admin);
+        // not proposed fields keep the values of the proposal time
+        assert_eq!(state.validator_system.manager_authority, old_manager);
+        assert_eq!(state.authority_timelock_slots, 50);
+        assert_eq!(state.pending_authority, PendingAuthority::default());
+        // used once
+        assert!(state.pending_authority_at(2_000).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_authority_proposal() -> ProgramResult {
+        let mut state = test_state();
+        let admin = state.admin_authority;
+        state.propose_authority(
+            ChangeAuthorityData {
+                admin: Some(Pubkey::new_unique()),
+                ..Default::default()
+            },
+            1_000,
+        )?;
+        assert_eq!(state.pending_authority.is_proposed, 1);
+        state.propose_authority(ChangeAuthorityData::default(), 1_010)?;
+        assert_eq!(state.pending_authority, PendingAuthority::default());
+        assert!(state.pending_authority_at(5_000).is_err());
+        assert_eq!(state.admin_authority, admin);
+
+        // overflowing timelock
+        state.authority_timelock_slots = u64::MAX;
+        assert!(state
+            .propose_authority(
+                ChangeAuthorityData {
+                    pause_authority: Some(Pubkey::default()),
+                    ..Default::default()
+                },
+                1,
+            )
+            .is_err());
         Ok(())
     }
 }
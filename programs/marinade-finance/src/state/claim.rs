@@ -3,8 +3,10 @@ use anchor_lang::solana_program::{program::invoke_signed, system_instruction, sy
 
 use crate::{
     checks::{check_address, check_owner_program},
+    events::ClaimEvent,
     state::StateHelpers,
-    Claim, CommonError,
+    ticket_account::{close_ticket, TicketAccountData},
+    Claim, ClaimMany, CommonError, State,
 };
 
 ///How many epochs to wats for ticket. e.g.: Ticket created on epoch 14, ticket is due on epoch 15
@@ -12,6 +14,104 @@ const WAIT_EPOCHS: u64 = 1;
 ///Wait 30 extra minutes from epochs start so the bot has time to withdraw SOL from inactive stake-accounts
 const EXTRA_WAIT_SECONDS: i64 = 30 * 60;
 
+/// Checks the ticket belongs to this marinade instance and beneficiary,
+/// is not used and is due
+fn check_ticket(
+    ticket: &TicketAccountData,
+    state_address: &Pubkey,
+    beneficiary: &Pubkey,
+    clock: &Clock,
+) -> ProgramResult {
+    if &ticket.state_address != state_address {
+        msg!(
+            "Ticket has wrong marinade instance {}",
+            ticket.state_address
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // not used
+    if ticket.lamports_amount == 0 {
+        msg!("Used ticket");
+        return Err(ProgramError::InvalidAccountData);
+    };
+
+    //check if ticket is due
+    if clock.epoch < ticket.created_epoch + WAIT_EPOCHS {
+        msg!("Ticket not due yet");
+        return Err(CommonError::TicketNotDue.into());
+    }
+    // Wait X MORE HOURS FROM THE beginning of the EPOCH to give the bot time to withdraw inactive-stake-accounts
+    if ticket.created_epoch + WAIT_EPOCHS == clock.epoch
+        && clock.unix_timestamp - clock.epoch_start_timestamp < EXTRA_WAIT_SECONDS
+    {
+        msg!(
+            "Ticket not ready {} {}",
+            clock.epoch_start_timestamp,
+            clock.unix_timestamp
+        );
+        return Err(CommonError::TicketNotReady.into());
+    }
+
+    if ticket.beneficiary != *beneficiary {
+        msg!("wrong beneficiary");
+        return Err(CommonError::WrongBeneficiary.into());
+    };
+
+    Ok(())
+}
+
+// Real balance not virtual field
+fn available_for_claim(state: &State, reserve_pda: &AccountInfo) -> u64 {
+    reserve_pda
+        .lamports()
+        .saturating_sub(state.rent_exempt_for_token_acc)
+}
+
+/// lamports paid for a ticket of ticket_lamports when the reserve has available_for_claim.
+/// allow_partial: pay what is available instead of failing
+fn claimable_lamports(
+    ticket_lamports: u64,
+    available_for_claim: u64,
+    allow_partial: bool,
+) -> Result<u64, ProgramError> {
+    if !allow_partial {
+        return Ok(ticket_lamports);
+    }
+    if available_for_claim == 0 {
+        msg!("Nothing ready to claim. Wait a few hours and retry");
+        return Err(CommonError::TicketNotReady.into());
+    }
+    Ok(ticket_lamports.min(available_for_claim))
+}
+
+/// Checks there is enough SOL in reserve to pay lamports to ticket holders
+fn check_reserve_for_claim(
+    state: &State,
+    available_for_claim: u64,
+    lamports: u64,
+) -> ProgramResult {
+    if lamports > state.circulating_ticket_balance {
+        msg!(
+            "Requested to withdraw {} when only {} is total circulating_ticket_balance",
+            lamports,
+            state.circulating_ticket_balance
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    if lamports > available_for_claim {
+        msg!(
+            "Requested to claim {} when only {} ready. Wait a few hours and retry",
+            lamports,
+            available_for_claim
+        );
+        //Error: "Wait a few hours and retry"
+        return Err(CommonError::TicketNotReady.into());
+    }
+    Ok(())
+}
+
 /// Claim instruction: a user claims a Ticket-account
 /// This is done once tickets are due, meaning enough time has passed for the
 /// bot to complete the unstake process and transfer the requested SOL to reserve_pda.
@@ -22,53 +122,100 @@ impl<'info> Claim<'info> {
         // ticket account program-owner must be marinade  (TODO: I think it was checked by anchor already)
         check_owner_program(
             &self.ticket_account,
-            &crate::ID, //owner-program should be marinade
+            self.state.to_account_info().owner, //owner-program should be marinade
             "ticket_account",
         )?;
-        if &self.ticket_account.state_address != self.state.to_account_info().key {
-            msg!(
-                "Ticket has wrong marinade instance {}",
-                self.ticket_account.state_address
-            );
-            return Err(ProgramError::InvalidAccountData);
-        }
-
         // should be initialized - checked by anchor
         // "initialized" means the first 8 bytes are the Anchor's struct hash magic number
+        check_ticket(
+            &self.ticket_account,
+            &self.state.instance_address,
+            self.transfer_sol_to.key,
+            &self.clock,
+        )
+    }
 
-        // not used
-        if self.ticket_account.lamports_amount == 0 {
-            msg!("Used ticket");
-            return Err(ProgramError::InvalidAccountData);
-        };
+    /// allow_partial: if the reserve can not cover the whole ticket yet,
+    /// pay what is available and keep the ticket open for the rest
+    pub fn process(&mut self, allow_partial: bool) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_CLAIM)?;
+        // fn claim() / fn partial_claim()
+        check_address(
+            self.system_program.to_account_info().key,
+            &system_program::ID,
+            "system_program",
+        )?;
+        check_owner_program(
+            &self.transfer_sol_to,
+            &system_program::ID,
+            "transfer_sol_to",
+        )?;
+        self.state.check_reserve_address(self.reserve_pda.key)?;
+        self.check_ticket_account()?;
 
-        //check if ticket is due
-        if self.clock.epoch < self.ticket_account.created_epoch + WAIT_EPOCHS {
-            msg!("Ticket not due yet");
-            return Err(CommonError::TicketNotDue.into());
-        }
-        // Wait X MORE HOURS FROM THE beginning of the EPOCH to give the bot time to withdraw inactive-stake-accounts
-        if self.ticket_account.created_epoch + WAIT_EPOCHS == self.clock.epoch
-            && self.clock.unix_timestamp - self.clock.epoch_start_timestamp < EXTRA_WAIT_SECONDS
-        {
+        let available_for_claim = available_for_claim(&self.state, &self.reserve_pda);
+        let lamports = claimable_lamports(
+            self.ticket_account.lamports_amount,
+            available_for_claim,
+            allow_partial,
+        )?;
+        check_reserve_for_claim(&self.state, available_for_claim, lamports)?;
+
+        self.state.circulating_ticket_balance -= lamports;
+        //disable ticket-account when fully paid
+        self.ticket_account.lamports_amount -= lamports;
+        let fully_claimed = self.ticket_account.lamports_amount == 0;
+        if fully_claimed {
+            self.state.circulating_ticket_count -= 1;
+        } else {
             msg!(
-                "Ticket not ready {} {}",
-                self.clock.epoch_start_timestamp,
-                self.clock.unix_timestamp
+                "Partially claimed {} lamports, {} remain in ticket",
+                lamports,
+                self.ticket_account.lamports_amount
             );
-            return Err(CommonError::TicketNotReady.into());
         }
 
-        if self.ticket_account.beneficiary != *self.transfer_sol_to.key {
-            msg!("wrong beneficiary");
-            return Err(CommonError::WrongBeneficiary.into());
-        };
+        //transfer sol from reserve_pda to user
+        self.state.with_reserve_seeds(|seeds| {
+            invoke_signed(
+                &system_instruction::transfer(
+                    self.reserve_pda.key,
+                    self.transfer_sol_to.key,
+                    lamports,
+                ),
+                &[
+                    self.system_program.clone(),
+                    self.reserve_pda.clone(),
+                    self.transfer_sol_to.clone(),
+                ],
+                &[seeds],
+            )
+        })?;
+        self.state.on_transfer_from_reserve(lamports)?;
 
+        if fully_claimed {
+            close_ticket(
+                &self.ticket_account.to_account_info(),
+                &self.transfer_sol_to,
+            )?;
+        }
+        emit!(ClaimEvent {
+            state: self.state.instance_address,
+            ticket: *self.ticket_account.to_account_info().key,
+            beneficiary: *self.transfer_sol_to.key,
+            lamports,
+            remaining_lamports: self.ticket_account.lamports_amount,
+        });
         Ok(())
     }
+}
 
-    pub fn process(&mut self) -> ProgramResult {
-        // fn claim()
+/// ClaimMany instruction: same as Claim for several due tickets of the same beneficiary
+/// passed as remaining accounts, paid with a single transfer from reserve_pda
+impl<'info> ClaimMany<'info> {
+    pub fn process(&mut self, ticket_accounts: &[AccountInfo]) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_CLAIM)?;
+        // fn claim_many()
         check_address(
             self.system_program.to_account_info().key,
             &system_program::ID,
@@ -80,35 +227,50 @@ impl<'info> Claim<'info> {
             "transfer_sol_to",
         )?;
         self.state.check_reserve_address(self.reserve_pda.key)?;
-        self.check_ticket_account()?;
-
-        let lamports = self.ticket_account.lamports_amount;
-        if lamports > self.state.circulating_ticket_balance {
-            msg!(
-                "Requested to withdraw {} when only {} is total circulating_ticket_balance",
-                lamports,
-                self.state.circulating_ticket_balance
-            );
-            return Err(ProgramError::InvalidAccountData);
+        if ticket_accounts.is_empty() {
+            msg!("No tickets to claim");
+            return Err(ProgramError::NotEnoughAccountKeys);
         }
 
-        // Real balance not virtual field
-        let available_for_claim =
-            self.reserve_pda.lamports() - self.state.rent_exempt_for_token_acc;
-        if lamports > available_for_claim {
-            msg!(
-                "Requested to claim {} when only {} ready. Wait a few hours and retry",
-                lamports,
-                available_for_claim
-            );
-            //Error: "Wait a few hours and retry"
-            return Err(CommonError::TicketNotReady.into());
+        let mut lamports: u64 = 0;
+        for ticket_account in ticket_accounts {
+            check_owner_program(
+                ticket_account,
+                self.state.to_account_info().owner,
+                "ticket_account",
+            )?;
+            let mut ticket =
+                TicketAccountData::try_deserialize(&mut &ticket_account.data.borrow()[..])?;
+            check_ticket(
+                &ticket,
+                &self.state.instance_address,
+                self.transfer_sol_to.key,
+                &self.clock,
+            )?;
+            lamports = lamports
+                .checked_add(ticket.lamports_amount)
+                .ok_or(CommonError::CalculationFailure)?;
+            emit!(ClaimEvent {
+                state: ticket.state_address,
+                ticket: *ticket_account.key,
+                beneficiary: ticket.beneficiary,
+                lamports: ticket.lamports_amount,
+                remaining_lamports: 0,
+            });
+            //disable ticket-account right away so the same ticket passed twice fails as used
+            ticket.lamports_amount = 0;
+            let mut data = ticket_account.try_borrow_mut_data()?;
+            let dst: &mut [u8] = &mut data;
+            ticket.try_serialize(&mut std::io::Cursor::new(dst))?;
         }
+        check_reserve_for_claim(
+            &self.state,
+            available_for_claim(&self.state, &self.reserve_pda),
+            lamports,
+        )?;
 
         self.state.circulating_ticket_balance -= lamports;
-        self.state.circulating_ticket_count -= 1;
-        //disable ticket-account
-        self.ticket_account.lamports_amount = 0;
+        self.state.circulating_ticket_count -= ticket_accounts.len() as u64;
 
         //transfer sol from reserve_pda to user
         self.state.with_reserve_seeds(|seeds| {
@@ -128,16 +290,88 @@ impl<'info> Claim<'info> {
         })?;
         self.state.on_transfer_from_reserve(lamports)?;
 
-        // move all rent-exempt ticket-account lamports to the user,
-        // the ticket-account will be deleted eventually because is no longer rent-exempt
-        let source_account_info = self.ticket_account.to_account_info();
-        let dest_account_info = self.transfer_sol_to.to_account_info();
-        let dest_starting_lamports = dest_account_info.lamports();
-        **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
-            .checked_add(source_account_info.lamports())
-            .ok_or(ProgramError::InvalidAccountData)?;
-        **source_account_info.lamports.borrow_mut() = 0;
+        for ticket_account in ticket_accounts {
+            close_ticket(ticket_account, &self.transfer_sol_to)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn ticket(state_address: Pubkey, beneficiary: Pubkey) -> TicketAccountData {
+        TicketAccountData {
+            state_address,
+            beneficiary,
+            lamports_amount: 5 * SOL,
+            created_epoch: 10,
+        }
+    }
+
+    #[test]
+    fn test_check_ticket() {
+        let state_address = Pubkey::new_unique();
+        let beneficiary = Pubkey::new_unique();
+        let ticket = ticket(state_address, beneficiary);
+        let ready = Clock {
+            epoch: 11,
+            epoch_start_timestamp: 1_000,
+            unix_timestamp: 1_000 + EXTRA_WAIT_SECONDS,
+            ..Default::default()
+        };
+        assert!(check_ticket(&ticket, &state_address, &beneficiary, &ready).is_ok());
+        assert!(check_ticket(&ticket, &Pubkey::new_unique(), &beneficiary, &ready).is_err());
+        assert!(check_ticket(&ticket, &state_address, &Pubkey::new_unique(), &ready).is_err());
+        // not due
+        let created = Clock {
+            epoch: 10,
+            ..ready.clone()
+        };
+        assert!(check_ticket(&ticket, &state_address, &beneficiary, &created).is_err());
+        // due, but the bot may not have withdrawn the stakes yet
+        let epoch_start = Clock {
+            unix_timestamp: 1_000 + EXTRA_WAIT_SECONDS - 1,
+            ..ready.clone()
+        };
+        assert!(check_ticket(&ticket, &state_address, &beneficiary, &epoch_start).is_err());
+        // a later epoch has no extra wait
+        let later = Clock {
+            epoch: 12,
+            ..epoch_start
+        };
+        assert!(check_ticket(&ticket, &state_address, &beneficiary, &later).is_ok());
+        let used = TicketAccountData {
+            lamports_amount: 0,
+            ..ticket
+        };
+        assert!(check_ticket(&used, &state_address, &beneficiary, &ready).is_err());
+    }
+
+    #[test]
+    fn test_partial_claim() -> ProgramResult {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.circulating_ticket_balance = 8 * SOL;
+
+        // full claim needs the whole ticket in the reserve
+        assert_eq!(claimable_lamports(5 * SOL, 3 * SOL, false)?, 5 * SOL);
+        assert!(check_reserve_for_claim(&state, 3 * SOL, 5 * SOL).is_err());
+        assert!(check_reserve_for_claim(&state, 5 * SOL, 5 * SOL).is_ok());
+
+        // partial claim pays what the reserve has, never more than the ticket
+        let lamports = claimable_lamports(5 * SOL, 3 * SOL, true)?;
+        assert_eq!(lamports, 3 * SOL);
+        assert!(check_reserve_for_claim(&state, 3 * SOL, lamports).is_ok());
+        assert_eq!(claimable_lamports(5 * SOL, 9 * SOL, true)?, 5 * SOL);
+        assert!(claimable_lamports(5 * SOL, 0, true).is_err());
 
+        // never more than the tickets in circulation
+        assert!(check_reserve_for_claim(&state, 10 * SOL, 9 * SOL).is_err());
         Ok(())
     }
 }
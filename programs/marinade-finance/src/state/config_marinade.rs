@@ -1,65 +1,215 @@
-use crate::{CommonError, ConfigMarinade, ConfigMarinadeParams, MAX_REWARD_FEE};
+use crate::{
+    roles::Role, CommonError, ConfigMarinade, ConfigMarinadeParams, State, MAX_MSOL_PRICE_INCREASE,
+    MAX_REWARD_FEE,
+};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
 
-impl<'info> ConfigMarinade<'info> {
+impl ConfigMarinadeParams {
     const MIN_WITHDRAW_CAP: u64 = LAMPORTS_PER_SOL / 10;
-    // fn config_marinade()
-    pub fn process(
-        &mut self,
-        ConfigMarinadeParams {
-            rewards_fee,
-            slots_for_stake_delta,
-            min_stake,
-            min_deposit,
-            min_withdraw,
-            staking_sol_cap,
-            liquidity_sol_cap,
-            auto_add_validator_enabled,
-        }: ConfigMarinadeParams,
-    ) -> ProgramResult {
-        self.state.check_admin_authority(self.admin_authority.key)?;
-        if let Some(rewards_fee) = rewards_fee {
+
+    pub fn check(&self, state: &State) -> ProgramResult {
+        if let Some(rewards_fee) = self.rewards_fee {
             rewards_fee.check_max(MAX_REWARD_FEE)?;
-            self.state.reward_fee = rewards_fee;
         }
-        if let Some(slots_for_stake_delta) = slots_for_stake_delta {
+        if let Some(slots_for_stake_delta) = self.slots_for_stake_delta {
             const MIN_UPDATE_WINDOW: u64 = 3_000; //min value is 3_000 => half an hour
             if slots_for_stake_delta < MIN_UPDATE_WINDOW {
                 return Err(CommonError::NumberTooLow.into());
             };
-            self.state.stake_system.slots_for_stake_delta = slots_for_stake_delta;
         }
-        if let Some(min_stake) = min_stake {
-            let min_accepted = 5 * self.state.rent_exempt_for_token_acc;
+        if let Some(min_stake) = self.min_stake {
+            let min_accepted = 5 * state.rent_exempt_for_token_acc;
             if min_stake < min_accepted {
                 return Err(CommonError::NumberTooLow.into());
             };
-            self.state.stake_system.min_stake = min_stake;
-        }
-        if let Some(min_deposit) = min_deposit {
-            // It is not dangerous to skip value checks because it is deposit only action
-            // We can use u64::MAX to stop accepting deposits
-            // or 0 to accept 1 lamport
-            self.state.min_deposit = min_deposit;
         }
-        if let Some(min_withdraw) = min_withdraw {
+        // It is not dangerous to skip min_deposit value checks because it is deposit only action
+        // We can use u64::MAX to stop accepting deposits
+        // or 0 to accept 1 lamport
+        if let Some(min_withdraw) = self.min_withdraw {
             if min_withdraw > Self::MIN_WITHDRAW_CAP {
                 return Err(CommonError::NumberTooHigh.into());
             }
-            self.state.min_withdraw = min_withdraw;
         }
-        if let Some(staking_sol_cap) = staking_sol_cap {
-            self.state.staking_sol_cap = staking_sol_cap;
+        // 0 = no limit. Rewards per epoch are far below MAX_MSOL_PRICE_INCREASE
+        if let Some(max_msol_price_increase) = self.max_msol_price_increase {
+            max_msol_price_increase.check_max(MAX_MSOL_PRICE_INCREASE)?;
         }
-        if let Some(liquidity_sol_cap) = liquidity_sol_cap {
-            self.state.liq_pool.liquidity_sol_cap = liquidity_sol_cap;
+        if let Some(max_msol_price_decrease) = self.max_msol_price_decrease {
+            max_msol_price_decrease.check()?;
         }
-        if let Some(auto_add_validator_enabled) = auto_add_validator_enabled {
-            self.state.validator_system.auto_add_validator_enabled =
-                if auto_add_validator_enabled { 1 } else { 0 };
+        if let Some(directed_stake_epochs) = self.directed_stake_epochs {
+            if directed_stake_epochs == 0 {
+                return Err(CommonError::NumberTooLow.into());
+            }
+            if directed_stake_epochs > State::MAX_DIRECTED_STAKE_EPOCHS {
+                return Err(CommonError::NumberTooHigh.into());
+            }
         }
+        Ok(())
+    }
 
+    /// fee manager and cap manager can change their own fields only.
+    /// Empty params are rejected: they would need no authority at all
+    pub fn check_authority(&self, state: &State, authority: &Pubkey) -> ProgramResult {
+        if *self == Self::default() {
+            return Err(CommonError::NoChangeRequested.into());
+        }
+        if self.rewards_fee.is_some() {
+            state.check_admin_or_role(Role::FeeManager, authority)?;
+        }
+        if self.staking_sol_cap.is_some() || self.liquidity_sol_cap.is_some() {
+            state.check_admin_or_role(Role::CapManager, authority)?;
+        }
+        let admin_only = Self {
+            rewards_fee: None,
+            staking_sol_cap: None,
+            liquidity_sol_cap: None,
+            ..*self
+        };
+        if admin_only != Self::default() {
+            state.check_admin_authority(authority)?;
+        }
         Ok(())
     }
+
+    /// fee increases need a notice period before applying
+    pub fn is_fee_increase(&self, state: &State) -> bool {
+        matches!(self.rewards_fee, Some(rewards_fee) if rewards_fee > state.reward_fee)
+    }
+
+    /// splits into (fee increase to queue, changes to apply immediately)
+    pub fn split_fee_increase(self, state: &State) -> (Self, Self) {
+        if self.is_fee_increase(state) {
+            (
+                Self {
+                    rewards_fee: self.rewards_fee,
+                    ..Default::default()
+                },
+                Self {
+                    rewards_fee: None,
+                    ..self
+                },
+            )
+        } else {
+            (Self::default(), self)
+        }
+    }
+
+    /// fields set in `newer` override self
+    pub fn merge(self, newer: Self) -> Self {
+        Self {
+            rewards_fee: newer.rewards_fee.or(self.rewards_fee),
+            slots_for_stake_delta: newer.slots_for_stake_delta.or(self.slots_for_stake_delta),
+            min_stake: newer.min_stake.or(self.min_stake),
+            min_deposit: newer.min_deposit.or(self.min_deposit),
+            min_withdraw: newer.min_withdraw.or(self.min_withdraw),
+            staking_sol_cap: newer.staking_sol_cap.or(self.staking_sol_cap),
+            liquidity_sol_cap: newer.liquidity_sol_cap.or(self.liquidity_sol_cap),
+            auto_add_validator_enabled: newer
+                .auto_add_validator_enabled
+                .or(self.auto_add_validator_enabled),
+            max_msol_price_increase: newer
+                .max_msol_price_increase
+                .or(self.max_msol_price_increase),
+            max_msol_price_decrease: newer
+                .max_msol_price_decrease
+                .or(self.max_msol_price_decrease),
+            directed_stake_epochs: newer.directed_stake_epochs.or(self.directed_stake_epochs),
+        }
+    }
+
+    /// set values checked by check()
+    pub fn apply(&self, state: &mut State) {
+        if let Some(rewards_fee) = self.rewards_fee {
+            state.reward_fee = rewards_fee;
+        }
+        if let Some(slots_for_stake_delta) = self.slots_for_stake_delta {
+            state.stake_system.slots_for_stake_delta = slots_for_stake_delta;
+        }
+        if let Some(min_stake) = self.min_stake {
+            state.stake_system.min_stake = min_stake;
+        }
+        if let Some(min_deposit) = self.min_deposit {
+            state.min_deposit = min_deposit;
+        }
+        if let Some(min_withdraw) = self.min_withdraw {
+            state.min_withdraw = min_withdraw;
+        }
+        if let Some(staking_sol_cap) = self.staking_sol_cap {
+            state.staking_sol_cap = staking_sol_cap;
+        }
+        if let Some(liquidity_sol_cap) = self.liquidity_sol_cap {
+            state.liq_pool.liquidity_sol_cap = liquidity_sol_cap;
+        }
+        if let Some(auto_add_validator_enabled) = self.auto_add_validator_enabled {
+            state.validator_system.auto_add_validator_enabled =
+                if auto_add_validator_enabled { 1 } else { 0 };
+        }
+        if let Some(max_msol_price_increase) = self.max_msol_price_increase {
+            state.max_msol_price_increase = max_msol_price_increase;
+        }
+        if let Some(max_msol_price_decrease) = self.max_msol_price_decrease {
+            state.max_msol_price_decrease = max_msol_price_decrease;
+        }
+        if let Some(directed_stake_epochs) = self.directed_stake_epochs {
+            state.directed_stake_epochs = directed_stake_epochs;
+        }
+    }
+}
+
+impl<'info> ConfigMarinade<'info> {
+    // fn config_marinade()
+    // applies the params, except fee increases queued in pending_config for apply_pending_config
+    pub fn process(&mut self, params: ConfigMarinadeParams) -> ProgramResult {
+        params.check_authority(&self.state, self.authority.key)?;
+        self.pending_config
+            .check_state_address(&self.state.instance_address)?;
+        self.pending_config
+            .queue_config(&mut self.state, params, self.clock.epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Fee;
+    use std::mem::MaybeUninit;
+
+    #[test]
+    fn test_check_authority() {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.admin_authority = Pubkey::new_unique();
+        let fee_manager = Pubkey::new_unique();
+        state.roles.fee_manager = fee_manager;
+        let anyone = Pubkey::new_unique();
+
+        // nothing to change, whoever signs
+        let empty = ConfigMarinadeParams::default();
+        assert!(empty
+            .check_authority(&state, &state.admin_authority)
+            .is_err());
+        assert!(empty.check_authority(&state, &fee_manager).is_err());
+        assert!(empty.check_authority(&state, &anyone).is_err());
+
+        let fee = ConfigMarinadeParams {
+            rewards_fee: Some(Fee::from_basis_points(100)),
+            ..Default::default()
+        };
+        assert!(fee.check_authority(&state, &fee_manager).is_ok());
+        assert!(fee.check_authority(&state, &state.admin_authority).is_ok());
+        assert!(fee.check_authority(&state, &anyone).is_err());
+
+        let fee_and_min_stake = ConfigMarinadeParams {
+            min_stake: Some(1),
+            ..fee
+        };
+        assert!(fee_and_min_stake
+            .check_authority(&state, &fee_manager)
+            .is_err());
+        assert!(fee_and_min_stake
+            .check_authority(&state, &state.admin_authority)
+            .is_ok());
+    }
 }
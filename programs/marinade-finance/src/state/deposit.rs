@@ -1,12 +1,19 @@
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::{program::invoke, system_instruction, system_program};
+use anchor_lang::solana_program::{
+    program::{invoke, invoke_signed},
+    system_instruction, system_program,
+};
 use anchor_spl::token::{mint_to, transfer, MintTo, Transfer};
 
 use crate::{
-    checks::{check_address, check_min_amount, check_owner_program, check_token_mint},
+    checks::{
+        check_address, check_min_amount, check_min_out, check_owner_program, check_token_mint,
+    },
+    epoch_stats::EpochStats,
+    events::DepositEvent,
     liq_pool::LiqPoolHelpers,
     state::StateHelpers,
-    Deposit,
+    Deposit, State, UnwrapWsol,
 };
 
 impl<'info> Deposit<'info> {
@@ -24,7 +31,38 @@ impl<'info> Deposit<'info> {
     }
 
     // fn deposit_sol()
-    pub fn process(&mut self, lamports: u64) -> ProgramResult {
+    // min_msol_out: fail if the user gets less mSOL (0 = no check)
+    pub fn process(
+        &mut self,
+        lamports: u64,
+        min_msol_out: u64,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.deposit(lamports, min_msol_out, epoch_stats)?;
+        Ok(())
+    }
+
+    // returns the lamports transferred to the reserve, the rest was swapped with the liq-pool
+    pub fn deposit(
+        &mut self,
+        lamports: u64,
+        min_msol_out: u64,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> Result<u64, ProgramError> {
+        self.deposit_from(lamports, min_msol_out, epoch_stats, None)
+    }
+
+    /// deposit paid by transfer_from, or by the wSOL of unwrap_wsol
+    /// (then transfer_from only signs and is not debited)
+    pub fn deposit_from(
+        &mut self,
+        lamports: u64,
+        min_msol_out: u64,
+        epoch_stats: Option<&AccountInfo>,
+        unwrap_wsol: Option<&UnwrapWsol<'info>>,
+    ) -> Result<u64, ProgramError> {
+        self.state.check_not_paused(State::PAUSE_DEPOSIT)?;
+        self.state.check_not_restricted()?;
         check_min_amount(lamports, self.state.min_deposit, "deposit SOL")?;
         self.state.check_reserve_address(self.reserve_pda.key)?;
         self.state
@@ -34,7 +72,9 @@ impl<'info> Deposit<'info> {
         self.state
             .liq_pool
             .check_liq_pool_msol_leg(self.liq_pool_msol_leg.to_account_info().key)?;
-        self.check_transfer_from(lamports)?;
+        if unwrap_wsol.is_none() {
+            self.check_transfer_from(lamports)?;
+        }
         self.check_mint_to()?;
         self.state
             .check_msol_mint_authority(self.msol_mint_authority.key)?;
@@ -58,32 +98,28 @@ impl<'info> Deposit<'info> {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let user_lamports = lamports;
-
-        //compute how many mSOL to sell/mint for the user, base on how many lamports being deposited
-        let user_msol_buy_order = self.state.calc_msol_from_lamports(user_lamports)?;
-        msg!("--- user_m_sol_buy_order {}", user_msol_buy_order);
-
-        //First we try to "sell" mSOL to the user from the LiqPool.
-        //The LiqPool needs to get rid of their mSOL because it works better if fully "unbalanced", i.e. with all SOL no mSOL
-        //so, if we can, the LiqPool "sells" mSOL to the user (no fee)
-        //
-        // At max, we can sell all the mSOL in the LiqPool.mSOL_leg
-        let swap_msol_max: u64 = user_msol_buy_order.min(self.liq_pool_msol_leg.amount);
+        let (swap_msol_max, lamports_for_the_liq_pool, msol_to_mint) = self
+            .state
+            .calc_deposit(lamports, self.liq_pool_msol_leg.amount)?;
         msg!("--- swap_m_sol_max {}", swap_msol_max);
 
-        //if we can sell from the LiqPool
-        let user_lamports = if swap_msol_max > 0 {
-            // how much lamports go into the LiqPool?
-            let lamports_for_the_liq_pool = if user_msol_buy_order == swap_msol_max {
-                //we are fulfilling 100% the user order
-                user_lamports //100% of the user deposit
-            } else {
-                //partially filled
-                //then it's the lamport value of the tokens we're selling
-                self.state.calc_lamports_from_msol_amount(swap_msol_max)?
-            };
+        // the wSOL is unwrapped into the reserve, the liq-pool part is moved from there
+        if let Some(unwrap_wsol) = unwrap_wsol {
+            self.state.with_reserve_seeds(|reserve_seeds| {
+                unwrap_wsol.process(
+                    &self.state.instance_address,
+                    self.state.to_account_info().owner,
+                    lamports,
+                    &self.reserve_pda,
+                    reserve_seeds,
+                    &self.system_program,
+                    &self.token_program,
+                )
+            })?;
+        }
 
+        //if we can sell from the LiqPool
+        if swap_msol_max > 0 {
             //transfer mSOL to the user
             self.state.with_liq_pool_msol_leg_authority_seeds(|seeds| {
                 transfer(
@@ -101,50 +137,62 @@ impl<'info> Deposit<'info> {
             })?;
 
             //transfer lamports to the LiqPool
-            invoke(
-                &system_instruction::transfer(
-                    self.transfer_from.key,
-                    self.liq_pool_sol_leg_pda.key,
-                    lamports_for_the_liq_pool,
-                ),
-                &[
-                    self.transfer_from.clone(),
-                    self.liq_pool_sol_leg_pda.clone(),
-                    self.system_program.clone(),
-                ],
-            )?;
-
-            //we took "lamports_for_the_liq_pool" from the "user_lamports"
-            user_lamports.saturating_sub(lamports_for_the_liq_pool)
+            if unwrap_wsol.is_some() {
+                self.state.with_reserve_seeds(|reserve_seeds| {
+                    invoke_signed(
+                        &system_instruction::transfer(
+                            self.reserve_pda.key,
+                            self.liq_pool_sol_leg_pda.key,
+                            lamports_for_the_liq_pool,
+                        ),
+                        &[
+                            self.reserve_pda.clone(),
+                            self.liq_pool_sol_leg_pda.clone(),
+                            self.system_program.clone(),
+                        ],
+                        &[reserve_seeds],
+                    )
+                })?;
+            } else {
+                invoke(
+                    &system_instruction::transfer(
+                        self.transfer_from.key,
+                        self.liq_pool_sol_leg_pda.key,
+                        lamports_for_the_liq_pool,
+                    ),
+                    &[
+                        self.transfer_from.clone(),
+                        self.liq_pool_sol_leg_pda.clone(),
+                        self.system_program.clone(),
+                    ],
+                )?;
+            }
             //end of sale from the LiqPool
-        } else {
-            user_lamports
-        };
+        }
+
+        //we took "lamports_for_the_liq_pool" from the "user_lamports"
+        let user_lamports = lamports.saturating_sub(lamports_for_the_liq_pool);
 
         // check if we have more lamports from the user
         if user_lamports > 0 {
             self.state.check_staking_cap(user_lamports)?;
-
-            //compute how much msol_to_mint
-            //NOTE: it is IMPORTANT to use calc_msol_from_lamports() BEFORE adding the lamports
-            // because on_transfer_to_reserve(user_lamports) alters price calculation
-            // the same goes for state.on_msol_mint()
-            let msol_to_mint = self.state.calc_msol_from_lamports(user_lamports)?;
             msg!("--- msol_to_mint {}", msol_to_mint);
 
-            //transfer user_lamports to reserve
-            invoke(
-                &system_instruction::transfer(
-                    self.transfer_from.key,
-                    self.reserve_pda.key,
-                    user_lamports,
-                ),
-                &[
-                    self.transfer_from.clone(),
-                    self.reserve_pda.clone(),
-                    self.system_program.clone(),
-                ],
-            )?;
+            //transfer user_lamports to reserve (unwrapped wSOL is there already)
+            if unwrap_wsol.is_none() {
+                invoke(
+                    &system_instruction::transfer(
+                        self.transfer_from.key,
+                        self.reserve_pda.key,
+                        user_lamports,
+                    ),
+                    &[
+                        self.transfer_from.clone(),
+                        self.reserve_pda.clone(),
+                        self.system_program.clone(),
+                    ],
+                )?;
+            }
             self.state.on_transfer_to_reserve(user_lamports);
             if msol_to_mint > 0 {
                 self.state.with_msol_mint_authority_seeds(|mint_seeds| {
@@ -164,9 +212,26 @@ impl<'info> Deposit<'info> {
                 self.state.on_msol_mint(msol_to_mint);
             }
             // self.state.stake_total += user_lamports; // auto calculated
-            // self.state.epoch_stake_orders += user_lamports;
         }
 
-        Ok(())
+        check_min_out(swap_msol_max + msol_to_mint, min_msol_out, "deposit mSOL")?;
+        EpochStats::record(
+            epoch_stats,
+            &self.state.instance_address,
+            self.state.to_account_info().owner,
+            |stats, epoch| stats.on_deposit(epoch, self.state.msol_price, lamports),
+        )?;
+
+        emit!(DepositEvent {
+            state: self.state.instance_address,
+            sol_owner: *self.transfer_from.key,
+            user_msol_account: *self.mint_to.to_account_info().key,
+            lamports,
+            msol_swapped: swap_msol_max,
+            lamports_to_liq_pool: lamports_for_the_liq_pool,
+            msol_minted: msol_to_mint,
+            msol_price: self.state.msol_price,
+        });
+        Ok(user_lamports)
     }
 }
@@ -0,0 +1,73 @@
+use anchor_lang::prelude::*;
+
+use crate::{events::DirectedDepositEvent, DepositDirected};
+
+/// DepositDirected instruction: a deposit whose lamports are added as directed stake
+/// for a listed validator, on top of its score-weighted stake target
+impl<'info> DepositDirected<'info> {
+    // fn deposit_directed()
+    // min_msol_out: fail if the user gets less mSOL (0 = no check)
+    pub fn process(
+        &mut self,
+        lamports: u64,
+        validator_index: u32,
+        min_msol_out: u64,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.deposit
+            .state
+            .validator_system
+            .check_validator_list(&self.validator_list)?;
+        self.deposit
+            .state
+            .clear_stale_directed_shares(&mut self.validator_list.data.as_ref().borrow_mut())?;
+        let mut validator = self
+            .deposit
+            .state
+            .validator_system
+            .get(&self.validator_list.data.as_ref().borrow(), validator_index)?;
+        if validator.validator_account != *self.validator_vote.key {
+            msg!(
+                "Validator index {} is {}. Expected {}",
+                validator_index,
+                validator.validator_account,
+                self.validator_vote.key
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        if validator.score == 0 {
+            msg!(
+                "Validator {} is disabled (score 0)",
+                validator.validator_account
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // lamports swapped with the liq-pool are not new stake
+        let directed_lamports = self.deposit.deposit(lamports, min_msol_out, epoch_stats)?;
+
+        msg!(
+            "Directed stake {} to validator {}",
+            directed_lamports,
+            validator.validator_account
+        );
+        let epoch = Clock::get()?.epoch;
+        self.deposit
+            .state
+            .on_directed_deposit(&mut validator, directed_lamports, epoch)?;
+        self.deposit.state.validator_system.set(
+            &mut self.validator_list.data.as_ref().borrow_mut(),
+            validator_index,
+            validator,
+        )?;
+        emit!(DirectedDepositEvent {
+            state: self.deposit.state.instance_address,
+            validator: *self.validator_vote.key,
+            validator_index,
+            lamports,
+            directed_lamports,
+            expiry_epoch: validator.directed_stake_expiry_epoch,
+        });
+        Ok(())
+    }
+}
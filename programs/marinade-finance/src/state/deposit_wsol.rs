@@ -0,0 +1,134 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program::invoke_signed, program_pack::Pack, system_program};
+use anchor_spl::token::{transfer, Transfer};
+
+use crate::{
+    checks::{check_address, check_token_mint},
+    events::UnwrapWsolEvent,
+    pda::create_pda_account_signed,
+    DepositWsol, State, UnwrapWsol,
+};
+
+/// Wrapped SOL (SPL token account of the native mint) as the source of a deposit or add liquidity.
+/// Lets programs keeping SOL in PDAs with data deposit by CPI: no system account is debited.
+/// The wSOL is moved into temp_wsol_account (PDA token account owned by itself) and unwrapped
+/// straight into the marinade account receiving the SOL (reserve or liq-pool SOL leg).
+/// temp_wsol_account is derived from transfer_wsol_from and closed at the end,
+/// so every wSOL account can fund one instruction per transaction
+impl<'info> UnwrapWsol<'info> {
+    /// moves lamports of wSOL from transfer_wsol_from to `to`, a system PDA signing with to_seeds.
+    /// `to` pays the temp account rent and gets it back
+    #[allow(clippy::too_many_arguments)]
+    pub fn process(
+        &self,
+        state_address: &Pubkey,
+        program_id: &Pubkey,
+        lamports: u64,
+        to: &AccountInfo<'info>,
+        to_seeds: &[&[u8]],
+        system_program: &AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+    ) -> ProgramResult {
+        check_address(self.wsol_mint.key, &spl_token::native_mint::ID, "wsol_mint")?;
+        check_token_mint(
+            &self.transfer_wsol_from,
+            spl_token::native_mint::ID,
+            "transfer_wsol_from",
+        )?;
+        check_address(system_program.key, &system_program::ID, "system_program")?;
+        check_address(token_program.key, &spl_token::ID, "token_program")?;
+        let wsol_account = self.transfer_wsol_from.to_account_info().key;
+        let (temp_wsol_address, temp_wsol_bump) =
+            State::find_temp_wsol_account(state_address, wsol_account, program_id);
+        check_address(
+            self.temp_wsol_account.key,
+            &temp_wsol_address,
+            "temp_wsol_account",
+        )?;
+        let temp_wsol_seeds: &[&[u8]] = &[
+            &state_address.to_bytes()[..32],
+            State::TEMP_WSOL_SEED,
+            &wsol_account.to_bytes()[..32],
+            &[temp_wsol_bump],
+        ];
+
+        create_pda_account_signed(
+            to,
+            &self.temp_wsol_account,
+            spl_token::state::Account::LEN,
+            &spl_token::ID,
+            system_program,
+            &self.rent,
+            &[temp_wsol_seeds, to_seeds],
+        )?;
+        invoke_signed(
+            &spl_token::instruction::initialize_account(
+                &spl_token::ID,
+                self.temp_wsol_account.key,
+                self.wsol_mint.key,
+                self.temp_wsol_account.key,
+            )?,
+            &[
+                token_program.clone(),
+                self.temp_wsol_account.clone(),
+                self.wsol_mint.clone(),
+                self.temp_wsol_account.clone(),
+                self.rent.to_account_info(),
+            ],
+            &[],
+        )?;
+
+        transfer(
+            CpiContext::new(
+                token_program.clone(),
+                Transfer {
+                    from: self.transfer_wsol_from.to_account_info(),
+                    to: self.temp_wsol_account.clone(),
+                    authority: self.transfer_wsol_authority.clone(),
+                },
+            ),
+            lamports,
+        )?;
+
+        // unwrap: all lamports of the temp account (including the rent) go to `to`
+        invoke_signed(
+            &spl_token::instruction::close_account(
+                &spl_token::ID,
+                self.temp_wsol_account.key,
+                to.key,
+                self.temp_wsol_account.key,
+                &[],
+            )?,
+            &[
+                token_program.clone(),
+                self.temp_wsol_account.clone(),
+                to.clone(),
+                self.temp_wsol_account.clone(),
+            ],
+            &[temp_wsol_seeds],
+        )?;
+
+        emit!(UnwrapWsolEvent {
+            state: *state_address,
+            wsol_account: *wsol_account,
+            unwrapped_to: *to.key,
+            lamports,
+        });
+        Ok(())
+    }
+}
+
+impl<'info> DepositWsol<'info> {
+    // fn deposit_wsol()
+    // min_msol_out: fail if the user gets less mSOL (0 = no check)
+    pub fn process(
+        &mut self,
+        lamports: u64,
+        min_msol_out: u64,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.deposit
+            .deposit_from(lamports, min_msol_out, epoch_stats, Some(&self.unwrap_wsol))?;
+        Ok(())
+    }
+}
@@ -5,12 +5,13 @@ use crate::{
     },
     stake_system::StakeSystem,
     validator_system::ValidatorSystem,
-    Initialize, InitializeData, LiqPoolInitialize, ID, MAX_REWARD_FEE,
+    Initialize, InitializeData, LiqPoolInitialize, MAX_REWARD_FEE,
 };
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{program_pack::Pack, system_program};
 
 use super::State;
+use crate::events::InitializeEvent;
 
 impl<'info> Initialize<'info> {
     pub const CREATOR_AUTHORITY: Pubkey = Pubkey::new_from_array([
@@ -26,6 +27,10 @@ impl<'info> Initialize<'info> {
         self.state.to_account_info().key
     }
 
+    pub fn program_id(&self) -> &Pubkey {
+        self.state.to_account_info().owner
+    }
+
     fn check_state(&self) -> ProgramResult {
         // all checked by anchor
         Ok(())
@@ -33,7 +38,7 @@ impl<'info> Initialize<'info> {
 
     fn check_reserve_pda(&mut self) -> ProgramResult {
         check_owner_program(&self.reserve_pda, &system_program::ID, "reserve_pda")?;
-        let (address, bump) = State::find_reserve_address(self.state_address());
+        let (address, bump) = State::find_reserve_address(self.state_address(), self.program_id());
         check_address(self.reserve_pda.key, &address, "reserve_pda")?;
         self.state.reserve_bump_seed = bump;
         {
@@ -53,7 +58,7 @@ impl<'info> Initialize<'info> {
     fn check_msol_mint(&mut self) -> ProgramResult {
         check_owner_program(&self.msol_mint, &spl_token::ID, "msol_mint")?;
         let (authority_address, authority_bump_seed) =
-            State::find_msol_mint_authority(self.state_address());
+            State::find_msol_mint_authority(self.state_address(), self.program_id());
 
         check_mint_authority(&self.msol_mint, authority_address, "msol_mint")?;
         self.state.msol_mint_authority_bump_seed = authority_bump_seed;
@@ -89,6 +94,8 @@ impl<'info> Initialize<'info> {
         )?;
         data.reward_fee.check_max(MAX_REWARD_FEE)?;
 
+        // PDA helpers derive from it
+        self.state.instance_address = *self.state_address();
         self.state.rent_exempt_for_token_acc =
             self.rent.minimum_balance(spl_token::state::Account::LEN);
 
@@ -101,8 +108,8 @@ impl<'info> Initialize<'info> {
             &system_program::ID,
             "operational_sol",
         )?;
-        check_owner_program(&self.stake_list, &ID, "stake_list")?;
-        check_owner_program(&self.validator_list, &ID, "validator_list")?;
+        check_owner_program(&self.stake_list, self.program_id(), "stake_list")?;
+        check_owner_program(&self.validator_list, self.program_id(), "validator_list")?;
 
         self.state.msol_mint = *self.msol_mint.to_account_info().key;
         self.state.admin_authority = data.admin_authority;
@@ -112,6 +119,7 @@ impl<'info> Initialize<'info> {
 
         self.state.stake_system = StakeSystem::new(
             self.state_address(),
+            self.program_id(),
             *self.stake_list.key,
             &mut self.stake_list.data.as_ref().borrow_mut(),
             data.slots_for_stake_delta,
@@ -127,15 +135,23 @@ impl<'info> Initialize<'info> {
         )?;
 
         self.state.msol_price = State::PRICE_DENOMINATOR;
+        self.state.layout_version = State::LAYOUT_VERSION;
+        self.state.directed_stake_epochs = State::DEFAULT_DIRECTED_STAKE_EPOCHS;
 
         // self.state.treasury_sol_account = *self.treasury_sol_account.to_account_info().key;
         self.state.treasury_msol_account = *self.treasury_msol_account.to_account_info().key;
         self.state.min_deposit = 1; // 1 lamport
         self.state.min_withdraw = 1; // 1 lamport
-        self.state.staking_sol_cap = std::u64::MAX; // Unlimited
+        self.state.staking_sol_cap = u64::MAX; // Unlimited
 
         LiqPoolInitialize::process(self, data.liq_pool)?;
 
+        emit!(InitializeEvent {
+            state: *self.state_address(),
+            msol_mint: self.state.msol_mint,
+            admin_authority: self.state.admin_authority,
+            reward_fee: self.state.reward_fee,
+        });
         Ok(())
     }
 }
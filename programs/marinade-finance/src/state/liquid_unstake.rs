@@ -2,13 +2,26 @@ use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{program::invoke_signed, system_instruction, system_program};
 use anchor_spl::token::{transfer, Transfer};
 
-use crate::checks::check_min_amount;
+use crate::checks::{check_min_amount, check_min_out};
 use crate::{
     checks::{check_address, check_owner_program, check_token_mint},
+    epoch_stats::EpochStats,
+    events::LiquidUnstakeEvent,
     liq_pool::LiqPoolHelpers,
-    CommonError, LiquidUnstake,
+    CommonError, Fee, LiquidUnstake, State,
 };
 
+/// Returns (treasury_msol_cut, partner_msol_cut) of msol_fee.
+/// The treasury cut (25% of the fee) includes the referral partner share of it
+fn split_msol_fee(msol_fee: u64, treasury_cut: Fee, partner_share: Option<Fee>) -> (u64, u64) {
+    let treasury_msol_cut = treasury_cut.apply(msol_fee);
+    let partner_msol_cut = match partner_share {
+        Some(partner_share) => partner_share.apply(treasury_msol_cut),
+        None => 0,
+    };
+    (treasury_msol_cut, partner_msol_cut)
+}
+
 impl<'info> LiquidUnstake<'info> {
     fn check_get_msol_from(&self, msol_amount: u64) -> ProgramResult {
         check_token_mint(&self.get_msol_from, self.state.msol_mint, "get_msol_from")?;
@@ -52,8 +65,58 @@ impl<'info> LiquidUnstake<'info> {
         Ok(())
     }
 
+    // fn liquid_unstake_exact_out()
+    // the user gets exactly `lamports`, paying at most max_msol_in mSOL
+    pub fn process_exact_out(
+        &mut self,
+        lamports: u64,
+        max_msol_in: u64,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_LIQUID_UNSTAKE)?;
+        self.state.check_not_restricted()?;
+        self.state
+            .check_liq_pool_sol_leg_pda(self.liq_pool_sol_leg_pda.key)?;
+        let max_lamports = self
+            .liq_pool_sol_leg_pda
+            .lamports()
+            .saturating_sub(self.state.rent_exempt_for_token_acc);
+        let msol_amount = self.state.calc_liquid_unstake_msol_for_lamports(
+            lamports,
+            max_lamports,
+            max_msol_in,
+        )?;
+        msg!("msol_amount {}", msol_amount);
+        // rounding dust above `lamports` stays in the liq-pool
+        self.unstake(msol_amount, lamports, true, None, epoch_stats)?;
+        Ok(())
+    }
+
     // fn liquid_unstake()
-    pub fn process(&mut self, msol_amount: u64) -> ProgramResult {
+    // min_lamports_out: fail if the user gets less SOL (0 = no check)
+    pub fn process(
+        &mut self,
+        msol_amount: u64,
+        min_lamports_out: u64,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.unstake(msol_amount, min_lamports_out, false, None, epoch_stats)?;
+        Ok(())
+    }
+
+    // exact_out: send the user min_lamports_out instead of the whole mSOL value
+    // partner: referral partner mSOL account and its share of the treasury cut
+    // returns (msol_fee, partner_msol_cut)
+    pub fn unstake(
+        &mut self,
+        msol_amount: u64,
+        min_lamports_out: u64,
+        exact_out: bool,
+        partner: Option<(&AccountInfo<'info>, Fee)>,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> Result<(u64, u64), ProgramError> {
+        self.state.check_not_paused(State::PAUSE_LIQUID_UNSTAKE)?;
+        self.state.check_not_restricted()?;
         msg!("enter LiquidUnstake");
 
         self.state
@@ -80,26 +143,10 @@ impl<'info> LiquidUnstake<'info> {
             .lamports()
             .saturating_sub(self.state.rent_exempt_for_token_acc);
 
-        // fee is computed based on the liquidity *after* the user takes the sol
-        let user_remove_lamports = self.state.calc_lamports_from_msol_amount(msol_amount)?;
-        let liquid_unstake_fee = if user_remove_lamports >= max_lamports {
-            // user is removing all liquidity
-            self.state.liq_pool.lp_max_fee
-        } else {
-            let after_lamports = max_lamports - user_remove_lamports; //how much will be left?
-            self.state.liq_pool.linear_fee(after_lamports)
-        };
-
-        // compute fee in msol
-        let msol_fee = liquid_unstake_fee.apply(msol_amount);
+        let (msol_fee, working_lamports_value) =
+            self.state.calc_liquid_unstake(msol_amount, max_lamports)?;
         msg!("msol_fee {}", msol_fee);
 
-        // fee goes into treasury & LPs, so the user receives lamport value of data.msol_amount - msol_fee
-        // compute how many lamports the msol_amount the user is "selling" (minus fee) is worth
-        let working_lamports_value = self
-            .state
-            .calc_lamports_from_msol_amount(msol_amount - msol_fee)?;
-
         // it can't be more than what's in the LiqPool
         if working_lamports_value + self.state.rent_exempt_for_token_acc
             > self.liq_pool_sol_leg_pda.lamports()
@@ -112,6 +159,16 @@ impl<'info> LiquidUnstake<'info> {
             self.state.min_withdraw,
             "withdraw SOL",
         )?;
+        check_min_out(
+            working_lamports_value,
+            min_lamports_out,
+            "liquid unstake SOL",
+        )?;
+        let working_lamports_value = if exact_out {
+            min_lamports_out
+        } else {
+            working_lamports_value
+        };
 
         //transfer SOL from the liq-pool to the user
         if working_lamports_value > 0 {
@@ -132,12 +189,16 @@ impl<'info> LiquidUnstake<'info> {
             })?;
         }
 
-        // cut 25% from the fee for the treasury
-        let treasury_msol_cut = if is_treasury_msol_ready_for_transfer {
-            self.state.liq_pool.treasury_cut.apply(msol_fee)
+        let treasury_cut = if is_treasury_msol_ready_for_transfer {
+            self.state.liq_pool.treasury_cut
         } else {
-            0
+            Fee::from_basis_points(0)
         };
+        let (treasury_msol_cut, partner_msol_cut) = split_msol_fee(
+            msol_fee,
+            treasury_cut,
+            partner.map(|(_, partner_share)| partner_share),
+        );
         msg!("treasury_msol_cut {}", treasury_msol_cut);
 
         //transfer mSOL to the liq-pool
@@ -154,7 +215,7 @@ impl<'info> LiquidUnstake<'info> {
         )?;
 
         //transfer treasury cut to treasury_msol_account
-        if treasury_msol_cut > 0 {
+        if treasury_msol_cut > partner_msol_cut {
             transfer(
                 CpiContext::new(
                     self.token_program.clone(),
@@ -164,10 +225,86 @@ impl<'info> LiquidUnstake<'info> {
                         authority: self.get_msol_from_authority.clone(),
                     },
                 ),
-                treasury_msol_cut,
+                treasury_msol_cut - partner_msol_cut,
             )?;
         }
 
-        Ok(())
+        //transfer partner cut to the referral partner
+        if let Some((partner_msol_account, _)) = partner {
+            if partner_msol_cut > 0 {
+                msg!("partner_msol_cut {}", partner_msol_cut);
+                transfer(
+                    CpiContext::new(
+                        self.token_program.clone(),
+                        Transfer {
+                            from: self.get_msol_from.to_account_info(),
+                            to: partner_msol_account.clone(),
+                            authority: self.get_msol_from_authority.clone(),
+                        },
+                    ),
+                    partner_msol_cut,
+                )?;
+            }
+        }
+
+        // the rest of the fee stays in the liq pool
+        EpochStats::record(
+            epoch_stats,
+            &self.state.instance_address,
+            self.state.to_account_info().owner,
+            |stats, epoch| {
+                stats.on_liquid_unstake(
+                    epoch,
+                    self.state.msol_price,
+                    working_lamports_value,
+                    msol_fee - treasury_msol_cut,
+                )
+            },
+        )?;
+        emit!(LiquidUnstakeEvent {
+            state: self.state.instance_address,
+            msol_owner: self.get_msol_from.owner,
+            sol_destination: *self.transfer_sol_to.key,
+            msol_amount,
+            lamports: working_lamports_value,
+            msol_fee,
+            treasury_msol_cut,
+            partner_msol_cut,
+            msol_price: self.state.msol_price,
+        });
+        Ok((msol_fee, partner_msol_cut))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_msol_fee() {
+        let treasury_cut = Fee::from_basis_points(2500);
+        assert_eq!(split_msol_fee(1_000_000, treasury_cut, None), (250_000, 0));
+        // the partner share comes out of the treasury cut, not from the LPs part
+        assert_eq!(
+            split_msol_fee(1_000_000, treasury_cut, Some(Fee::from_basis_points(4000))),
+            (250_000, 100_000)
+        );
+        assert_eq!(
+            split_msol_fee(
+                1_000_000,
+                treasury_cut,
+                Some(Fee::from_basis_points(10_000))
+            ),
+            (250_000, 250_000)
+        );
+        // treasury not ready: nothing for the treasury nor the partner
+        assert_eq!(
+            split_msol_fee(
+                1_000_000,
+                Fee::from_basis_points(0),
+                Some(Fee::from_basis_points(4000))
+            ),
+            (0, 0)
+        );
     }
 }
@@ -0,0 +1,288 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    admin_council::CouncilAction,
+    checks::check_owner_program,
+    events::{MigrateListEvent, MigrateStateEvent},
+    stake_system::StakeRecord,
+    validator_system::ValidatorRecord,
+    MigrateList, MigrateState, State,
+};
+
+/// Reads a state of any layout version. Accounts of older versions are shorter than the current
+/// layout: fields added later are appended, so the missing tail reads as zeroes
+pub fn read_state(data: &[u8]) -> Result<State, ProgramError> {
+    if data.len() >= State::serialized_len() {
+        return State::try_deserialize(&mut &data[..]);
+    }
+    let mut padded = vec![0u8; State::serialized_len()];
+    padded[..data.len()].copy_from_slice(data);
+    State::try_deserialize(&mut &padded[..])
+}
+
+/// Upgrades accounts created by older program versions.
+/// Solana 1.7 accounts can not grow, so a state smaller than the current layout is copied into
+/// new_state and the old account is closed. The PDAs and the state_address of the instance
+/// accounts keep using the old address (State::instance_address).
+/// Records growing beyond their list's item size need the list moved into a new account first
+impl<'info> MigrateState<'info> {
+    // fn migrate_state()
+    pub fn process(&mut self, program_id: &Pubkey) -> ProgramResult {
+        check_owner_program(&self.state, program_id, "state")?;
+        let mut state = read_state(&self.state.data.borrow())?;
+        if state.layout_version >= State::LAYOUT_VERSION {
+            msg!("State layout version {} is current", state.layout_version);
+            return Err(ProgramError::InvalidArgument);
+        }
+        let old_layout_version = state.layout_version;
+        if old_layout_version == 0 {
+            state.instance_address = *self.state.key;
+        }
+        state.check_admin_or_council(
+            &self.admin_authority,
+            program_id,
+            CouncilAction::MigrateState,
+        )?;
+
+        let validator_record_size = ValidatorRecord::default().try_to_vec()?.len() as u32;
+        if state.validator_system.validator_record_size() < validator_record_size {
+            msg!(
+                "validator_list records have {} bytes, {} needed. Run migrate_list first",
+                state.validator_system.validator_record_size(),
+                validator_record_size
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let stake_record_size = StakeRecord::default().try_to_vec()?.len() as u32;
+        if state.stake_system.stake_record_size() < stake_record_size {
+            msg!(
+                "stake_list records have {} bytes, {} needed. Run migrate_list first",
+                state.stake_system.stake_record_size(),
+                stake_record_size
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if old_layout_version == 0 {
+            // the price band starts from the current price
+            state.price_band_reference = state.msol_price;
+            state.price_band_epoch = self.clock.epoch;
+            state.directed_stake_epochs = State::DEFAULT_DIRECTED_STAKE_EPOCHS;
+        }
+        state.layout_version = State::LAYOUT_VERSION;
+
+        if self.new_state.key == self.state.key {
+            if self.state.data_len() < State::serialized_len() {
+                msg!(
+                    "State account has {} bytes, the current layout needs {}. Pass a new_state account",
+                    self.state.data_len(),
+                    State::serialized_len()
+                );
+                return Err(ProgramError::AccountDataTooSmall);
+            }
+            state.try_serialize(&mut &mut self.state.data.borrow_mut()[..])?;
+        } else {
+            self.move_state(&state, program_id)?;
+        }
+
+        emit!(MigrateStateEvent {
+            state: state.instance_address,
+            new_state: *self.new_state.key,
+            old_layout_version,
+            new_layout_version: State::LAYOUT_VERSION,
+        });
+        Ok(())
+    }
+
+    /// Writes the migrated state into new_state and closes the old account
+    fn move_state(&self, state: &State, program_id: &Pubkey) -> ProgramResult {
+        state.check_operational_sol_account(self.operational_sol_account.key)?;
+        check_owner_program(&self.new_state, program_id, "new_state")?;
+        if self.new_state.data_len() < State::serialized_len() {
+            msg!(
+                "new_state has {} bytes, the current layout needs {}",
+                self.new_state.data_len(),
+                State::serialized_len()
+            );
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        if self.new_state.data.borrow()[0..8] != [0; 8] {
+            msg!("new_state {} is initialized", self.new_state.key);
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if !self
+            .rent
+            .is_exempt(self.new_state.lamports(), self.new_state.data_len())
+        {
+            msg!("new_state {} must be rent exempt", self.new_state.key);
+            return Err(ProgramError::InsufficientFunds);
+        }
+        state.try_serialize(&mut &mut self.new_state.data.borrow_mut()[..])?;
+
+        msg!(
+            "State moved from {} to {}",
+            self.state.key,
+            self.new_state.key
+        );
+        self.state.data.borrow_mut()[0..8].copy_from_slice(&[0; 8]);
+        let rent_return = self.state.lamports();
+        **self.state.try_borrow_mut_lamports()? = 0;
+        **self.operational_sol_account.try_borrow_mut_lamports()? += rent_return;
+        Ok(())
+    }
+}
+
+impl<'info> MigrateList<'info> {
+    // fn migrate_list()
+    // moves old_list (the validator or stake list) into new_list with bigger records,
+    // max_copy_count records per call. The list can not be modified until it is done.
+    // A council needs an executed MigrateList proposal for every call
+    pub fn process(&mut self, additional_record_space: u32, max_copy_count: u32) -> ProgramResult {
+        self.state.check_admin_or_council(
+            &self.admin_authority,
+            self.state.to_account_info().owner,
+            CouncilAction::MigrateList {
+                new_list: *self.new_list.key,
+                additional_record_space,
+            },
+        )?;
+        self.state
+            .check_operational_sol_account(self.operational_sol_account.key)?;
+        check_owner_program(
+            &self.new_list,
+            self.state.to_account_info().owner,
+            "new_list",
+        )?;
+        if !self
+            .rent
+            .is_exempt(self.new_list.lamports(), self.new_list.data_len())
+        {
+            msg!("new_list {} must be rent exempt", self.new_list.key);
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        let state_address = *self.state.to_account_info().key;
+        let state: &mut State = &mut self.state;
+        let is_validator_list =
+            self.old_list.key == state.validator_system.validator_list_address();
+        let (list, other_list, discriminator, record_size, list_name) = if is_validator_list {
+            state
+                .validator_system
+                .check_validator_list(&self.old_list)?;
+            (
+                &mut state.validator_system.validator_list,
+                &state.stake_system.stake_list,
+                ValidatorRecord::DISCRIMINATOR,
+                ValidatorRecord::default().try_to_vec()?.len() as u32,
+                "validator_list",
+            )
+        } else {
+            state.stake_system.check_stake_list(&self.old_list)?;
+            (
+                &mut state.stake_system.stake_list,
+                &state.validator_system.validator_list,
+                StakeRecord::DISCRIMINATOR,
+                StakeRecord::default().try_to_vec()?.len() as u32,
+                "stake_list",
+            )
+        };
+        // migrating_list_item_size is shared
+        if other_list.is_changing_account() {
+            msg!(
+                "Another list is being migrated into {}",
+                other_list.new_account
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        let new_item_size = record_size
+            .checked_add(additional_record_space)
+            .ok_or(ProgramError::InvalidArgument)?;
+        if list.is_changing_account() && new_item_size != state.migrating_list_item_size {
+            msg!(
+                "{} is being migrated with item size {}. Got {}",
+                list_name,
+                state.migrating_list_item_size,
+                new_item_size
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let done = list.change_account(
+            discriminator,
+            &self.old_list.data.borrow(),
+            self.new_list.key,
+            &mut self.new_list.data.borrow_mut(),
+            new_item_size,
+            max_copy_count,
+            list_name,
+        )?;
+        let copied_count = if done { list.len() } else { list.copied_count };
+        let count = list.len();
+        if done {
+            state.migrating_list_item_size = 0;
+            // close the old list
+            self.old_list.data.borrow_mut()[0..8].copy_from_slice(&[0; 8]);
+            let rent_return = self.old_list.lamports();
+            **self.old_list.try_borrow_mut_lamports()? = 0;
+            **self.operational_sol_account.try_borrow_mut_lamports()? += rent_return;
+        } else {
+            state.migrating_list_item_size = new_item_size;
+        }
+
+        emit!(MigrateListEvent {
+            state: state_address,
+            old_list: *self.old_list.key,
+            new_list: *self.new_list.key,
+            item_size: new_item_size,
+            copied_count,
+            count,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    /// State::serialized_len() of the first deployed layout (up to emergency_cooling_down)
+    const FIRST_LAYOUT_LEN: usize = 576;
+
+    #[test]
+    fn test_read_first_layout() {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.msol_mint = Pubkey::new_unique();
+        state.admin_authority = Pubkey::new_unique();
+        state.msol_price = State::PRICE_DENOMINATOR;
+        state.emergency_cooling_down = 5;
+        // fields of the later layouts
+        state.layout_version = 9;
+        state.instance_address = Pubkey::new_unique();
+        state.paused_operations = 1;
+        let mut data = vec![0u8; State::serialized_len()];
+        state.try_serialize(&mut &mut data[..]).unwrap();
+        // the first added field follows the first layout
+        assert_eq!(data[FIRST_LAYOUT_LEN], 9);
+
+        let old = read_state(&data[..FIRST_LAYOUT_LEN]).unwrap();
+        assert_eq!(old.msol_mint, state.msol_mint);
+        assert_eq!(old.admin_authority, state.admin_authority);
+        assert_eq!(old.msol_price, State::PRICE_DENOMINATOR);
+        assert_eq!(old.emergency_cooling_down, 5);
+        assert_eq!(old.layout_version, 0);
+        assert_eq!(old.instance_address, Pubkey::default());
+        assert_eq!(old.paused_operations, 0);
+
+        let current = read_state(&data).unwrap();
+        assert_eq!(current.layout_version, 9);
+        assert_eq!(current.instance_address, state.instance_address);
+        assert_eq!(current.paused_operations, 1);
+    }
+
+    #[test]
+    fn test_read_not_state() {
+        let data = vec![0u8; FIRST_LAYOUT_LEN];
+        assert!(read_state(&data).is_err());
+    }
+}
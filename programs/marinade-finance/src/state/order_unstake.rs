@@ -1,59 +1,75 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{burn, Burn};
+use anchor_spl::token::{burn, Burn, TokenAccount};
 
 use crate::{
     checks::{check_address, check_min_amount, check_owner_program, check_token_mint},
-    OrderUnstake,
+    epoch_stats::EpochStats,
+    events::OrderUnstakeEvent,
+    ticket_account::TicketAccountData,
+    OrderUnstake, OrderUnstakePda, State,
 };
 
-impl<'info> OrderUnstake<'info> {
-    fn check_burn_msol_from(&self, msol_amount: u64) -> ProgramResult {
-        check_token_mint(&self.burn_msol_from, self.state.msol_mint, "burn_msol_from")?;
+/// Checks burn_msol_from can burn msol_amount signed by burn_msol_authority
+/// (as token owner or as delegate)
+pub(crate) fn check_burn_msol_from(
+    state: &State,
+    burn_msol_from: &CpiAccount<TokenAccount>,
+    burn_msol_authority: &AccountInfo,
+    msol_amount: u64,
+) -> ProgramResult {
+    check_token_mint(burn_msol_from, state.msol_mint, "burn_msol_from")?;
 
-        if msol_amount == 0 {
-            return Err(ProgramError::InvalidAccountData);
-        }
+    if msol_amount == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
 
+    // if delegated, check delegated amount
+    if *burn_msol_authority.key == burn_msol_from.owner {
+        if burn_msol_from.amount < msol_amount {
+            msg!(
+                "Requested to unstake {} mSOL lamports but have only {}",
+                msol_amount,
+                burn_msol_from.amount
+            );
+            return Err(ProgramError::InsufficientFunds);
+        }
+    } else if burn_msol_from.delegate.contains(burn_msol_authority.key) {
         // if delegated, check delegated amount
-        if *self.burn_msol_authority.key == self.burn_msol_from.owner {
-            if self.burn_msol_from.amount < msol_amount {
-                msg!(
-                    "Requested to unstake {} mSOL lamports but have only {}",
-                    msol_amount,
-                    self.burn_msol_from.amount
-                );
-                return Err(ProgramError::InsufficientFunds);
-            }
-        } else if self
-            .burn_msol_from
-            .delegate
-            .contains(self.burn_msol_authority.key)
-        {
-            // if delegated, check delegated amount
-            // delegated_amount & delegate must be set on the user's msol account before calling OrderUnstake
-            if self.burn_msol_from.delegated_amount < msol_amount {
-                msg!(
-                    "Delegated {} mSOL lamports. Requested {}",
-                    self.burn_msol_from.delegated_amount,
-                    msol_amount
-                );
-                return Err(ProgramError::InsufficientFunds);
-            }
-        } else {
+        // delegated_amount & delegate must be set on the user's msol account before calling OrderUnstake
+        if burn_msol_from.delegated_amount < msol_amount {
             msg!(
-                "Token must be delegated to {}",
-                self.burn_msol_authority.key
+                "Delegated {} mSOL lamports. Requested {}",
+                burn_msol_from.delegated_amount,
+                msol_amount
             );
-            return Err(ProgramError::InvalidArgument);
+            return Err(ProgramError::InsufficientFunds);
         }
-        Ok(())
+    } else {
+        msg!("Token must be delegated to {}", burn_msol_authority.key);
+        return Err(ProgramError::InvalidArgument);
     }
+    Ok(())
+}
 
+/// If user calls OrderUnstake after we start the stake/unstake delta (close to the end of the epoch),
+/// we must set ticket-due as if unstaking was asked **next-epoch**
+/// Because there's a delay until the bot actually starts the unstakes
+/// and it's not guaranteed that the unstake for the user will be started this epoch
+fn ticket_created_epoch(state: &State, clock: &Clock) -> u64 {
+    clock.epoch
+        + if clock.epoch == state.stake_system.last_stake_delta_epoch {
+            1
+        } else {
+            0
+        }
+}
+
+impl<'info> OrderUnstake<'info> {
     fn check_new_ticket_account(&self) -> ProgramResult {
         // ticket account program-owner must be marinade (TODO: I think it was checked by anchor already)
         check_owner_program(
             &self.new_ticket_account,
-            &crate::ID, //owner-program should be marinade
+            self.state.to_account_info().owner, //owner-program should be marinade
             "new_ticket_account",
         )?;
 
@@ -63,19 +79,31 @@ impl<'info> OrderUnstake<'info> {
     }
 
     // fn order_unstake() // create delayed-unstake Ticket-account
-    pub fn process(&mut self, msol_amount: u64) -> ProgramResult {
+    pub fn process(
+        &mut self,
+        msol_amount: u64,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_ORDER_UNSTAKE)?;
+        self.state.check_not_restricted()?;
         // fn order_unstake()
         check_address(self.token_program.key, &spl_token::ID, "token_program")?;
         self.check_new_ticket_account()?;
         self.state
             .check_msol_mint(self.msol_mint.to_account_info().key)?;
-        self.check_burn_msol_from(msol_amount)?;
+        check_burn_msol_from(
+            &self.state,
+            &self.burn_msol_from,
+            &self.burn_msol_authority,
+            msol_amount,
+        )?;
         let ticket_beneficiary = self.burn_msol_from.owner;
 
         let lamports_amount = self.state.calc_lamports_from_msol_amount(msol_amount)?;
 
         check_min_amount(lamports_amount, self.state.min_withdraw, "withdraw SOL")?;
 
+        self.state.on_directed_unstake(lamports_amount)?;
         // circulating_ticket_balance +
         self.state.circulating_ticket_balance = self
             .state
@@ -112,20 +140,107 @@ impl<'info> OrderUnstake<'info> {
         self.state.on_msol_burn(msol_amount)?;
 
         //initialize new_ticket_account
-        self.new_ticket_account.state_address = *self.state.to_account_info().key;
+        self.new_ticket_account.state_address = self.state.instance_address;
         self.new_ticket_account.beneficiary = ticket_beneficiary;
         self.new_ticket_account.lamports_amount = lamports_amount;
-        // If user calls OrderUnstake after we start the stake/unstake delta (close to the end of the epoch),
-        // we must set ticket-due as if unstaking was asked **next-epoch**
-        // Because there's a delay until the bot actually starts the unstakes
-        // and it's not guaranteed that the unstake for the user will be started this epoch
-        self.new_ticket_account.created_epoch = self.clock.epoch
-            + if self.clock.epoch == self.state.stake_system.last_stake_delta_epoch {
-                1
-            } else {
-                0
-            };
+        self.new_ticket_account.created_epoch = ticket_created_epoch(&self.state, &self.clock);
+
+        EpochStats::record(
+            epoch_stats,
+            &self.state.instance_address,
+            self.state.to_account_info().owner,
+            |stats, epoch| stats.on_order_unstake(epoch, self.state.msol_price, lamports_amount),
+        )?;
+        emit!(OrderUnstakeEvent {
+            state: self.state.instance_address,
+            ticket: *self.new_ticket_account.to_account_info().key,
+            beneficiary: ticket_beneficiary,
+            msol_burned: msol_amount,
+            lamports: lamports_amount,
+            created_epoch: self.new_ticket_account.created_epoch,
+            msol_price: self.state.msol_price,
+        });
+        Ok(())
+    }
+}
+
+impl<'info> OrderUnstakePda<'info> {
+    // fn order_unstake_pda() // create delayed-unstake Ticket-account as PDA [state, "ticket", beneficiary, ticket_nonce]
+    pub fn process(
+        &mut self,
+        msol_amount: u64,
+        ticket_nonce: u64,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_ORDER_UNSTAKE)?;
+        self.state.check_not_restricted()?;
+        check_address(self.token_program.key, &spl_token::ID, "token_program")?;
+        self.state
+            .check_msol_mint(self.msol_mint.to_account_info().key)?;
+        check_burn_msol_from(
+            &self.state,
+            &self.burn_msol_from,
+            &self.burn_msol_authority,
+            msol_amount,
+        )?;
+
+        let lamports_amount = self.state.calc_lamports_from_msol_amount(msol_amount)?;
+
+        check_min_amount(lamports_amount, self.state.min_withdraw, "withdraw SOL")?;
+
+        // create ticket first: fails if the nonce was already used by this beneficiary
+        let ticket = TicketAccountData {
+            state_address: self.state.instance_address,
+            beneficiary: self.burn_msol_from.owner,
+            lamports_amount,
+            created_epoch: ticket_created_epoch(&self.state, &self.clock),
+        };
+        ticket.create_pda(
+            self.state.to_account_info().owner,
+            ticket_nonce,
+            &self.new_ticket_account,
+            &self.rent_payer,
+            &self.system_program,
+            &self.rent,
+        )?;
+
+        self.state.on_directed_unstake(lamports_amount)?;
+        // circulating_ticket_balance +
+        self.state.circulating_ticket_balance = self
+            .state
+            .circulating_ticket_balance
+            .checked_add(lamports_amount)
+            .expect("circulating_ticket_balance overflow");
+        self.state.circulating_ticket_count += 1;
 
+        burn(
+            CpiContext::new(
+                self.token_program.clone(),
+                Burn {
+                    mint: self.msol_mint.to_account_info(),
+                    to: self.burn_msol_from.to_account_info(),
+                    authority: self.burn_msol_authority.clone(),
+                },
+            ),
+            msol_amount,
+        )?;
+        self.state.on_msol_burn(msol_amount)?;
+
+        EpochStats::record(
+            epoch_stats,
+            &self.state.instance_address,
+            self.state.to_account_info().owner,
+            |stats, epoch| stats.on_order_unstake(epoch, self.state.msol_price, lamports_amount),
+        )?;
+        emit!(OrderUnstakeEvent {
+            state: ticket.state_address,
+            ticket: *self.new_ticket_account.key,
+            beneficiary: ticket.beneficiary,
+            msol_burned: msol_amount,
+            lamports: lamports_amount,
+            created_epoch: ticket.created_epoch,
+            msol_price: self.state.msol_price,
+        });
         Ok(())
     }
 }
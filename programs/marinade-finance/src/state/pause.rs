@@ -0,0 +1,139 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    admin_council::CouncilAction, events::PausedOperationsChangedEvent, roles::Role, Pause, State,
+    Unpause,
+};
+
+impl State {
+    /// adds operations (State::PAUSE_* bits) to the paused ones
+    pub fn pause_operations(&mut self, operations: u64) -> ProgramResult {
+        if operations & !State::PAUSE_ALL != 0 {
+            msg!("Unknown operations {:#x}", operations);
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.paused_operations |= operations;
+        Ok(())
+    }
+
+    /// resumes operations, the others stay paused
+    pub fn unpause_operations(&mut self, operations: u64) {
+        self.paused_operations &= !operations;
+    }
+}
+
+impl<'info> Pause<'info> {
+    // fn pause()
+    // admin, pause_authority or emergency operator can stop a set of State::PAUSE_* operations
+    pub fn process(&mut self, operations: u64) -> ProgramResult {
+        if self.authority.key != &self.state.admin_authority
+            && self.authority.key != &self.state.pause_authority
+            && !self
+                .state
+                .roles
+                .has_role(Role::EmergencyOperator, self.authority.key)
+        {
+            msg!(
+                "Invalid pause authority {}. Expected admin, emergency operator or {}",
+                self.authority.key,
+                self.state.pause_authority
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        let old_paused_operations = self.state.paused_operations;
+        self.state.pause_operations(operations)?;
+        emit!(PausedOperationsChangedEvent {
+            state: self.state.instance_address,
+            authority: *self.authority.key,
+            old_paused_operations,
+            new_paused_operations: self.state.paused_operations,
+        });
+        msg!("Paused operations {:#x}", self.state.paused_operations);
+        Ok(())
+    }
+}
+
+impl<'info> Unpause<'info> {
+    // fn unpause()
+    // only admin (or its council) can resume operations
+    pub fn process(&mut self, operations: u64) -> ProgramResult {
+        self.state.check_admin_or_council(
+            &self.admin_authority,
+            self.state.to_account_info().owner,
+            CouncilAction::Unpause { operations },
+        )?;
+        let old_paused_operations = self.state.paused_operations;
+        self.state.unpause_operations(operations);
+        emit!(PausedOperationsChangedEvent {
+            state: self.state.instance_address,
+            authority: *self.admin_authority.key,
+            old_paused_operations,
+            new_paused_operations: self.state.paused_operations,
+        });
+        msg!("Paused operations {:#x}", self.state.paused_operations);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    #[test]
+    fn test_pause_operations() -> ProgramResult {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.pause_operations(State::PAUSE_DEPOSIT | State::PAUSE_CLAIM)?;
+        assert!(state.check_not_paused(State::PAUSE_DEPOSIT).is_err());
+        assert!(state.check_not_paused(State::PAUSE_CLAIM).is_err());
+        assert!(state.check_not_paused(State::PAUSE_LIQUID_UNSTAKE).is_ok());
+
+        // pausing adds to the paused operations
+        state.pause_operations(State::PAUSE_UPDATE)?;
+        assert!(state.check_not_paused(State::PAUSE_DEPOSIT).is_err());
+        assert!(state.check_not_paused(State::PAUSE_UPDATE).is_err());
+
+        state.unpause_operations(State::PAUSE_DEPOSIT | State::PAUSE_UPDATE);
+        assert!(state.check_not_paused(State::PAUSE_DEPOSIT).is_ok());
+        assert!(state.check_not_paused(State::PAUSE_UPDATE).is_ok());
+        assert!(state.check_not_paused(State::PAUSE_CLAIM).is_err());
+
+        // unknown bits are rejected, nothing changes
+        assert!(state.pause_operations(State::PAUSE_ALL + 1).is_err());
+        assert_eq!(state.paused_operations, State::PAUSE_CLAIM);
+
+        state.pause_operations(State::PAUSE_ALL)?;
+        assert!(state.check_not_paused(State::PAUSE_MERGE_STAKES).is_err());
+        state.unpause_operations(State::PAUSE_ALL);
+        assert_eq!(state.paused_operations, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pause_bits() {
+        let operations = [
+            State::PAUSE_DEPOSIT,
+            State::PAUSE_DEPOSIT_STAKE_ACCOUNT,
+            State::PAUSE_LIQUID_UNSTAKE,
+            State::PAUSE_ORDER_UNSTAKE,
+            State::PAUSE_CLAIM,
+            State::PAUSE_TICKET,
+            State::PAUSE_ADD_LIQUIDITY,
+            State::PAUSE_REMOVE_LIQUIDITY,
+            State::PAUSE_WITHDRAW_STAKE_ACCOUNT,
+            State::PAUSE_STAKE_RESERVE,
+            State::PAUSE_UPDATE,
+            State::PAUSE_UNSTAKE,
+            State::PAUSE_MERGE_STAKES,
+        ];
+        // one distinct bit per operation, PAUSE_ALL has all of them
+        assert_eq!(
+            operations.iter().fold(0, |all, op| all | op),
+            State::PAUSE_ALL
+        );
+        assert_eq!(
+            operations.iter().map(|op| op.count_ones()).sum::<u32>(),
+            State::PAUSE_ALL.count_ones()
+        );
+    }
+}
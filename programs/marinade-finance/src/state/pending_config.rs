@@ -0,0 +1,414 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::system_program;
+
+use crate::{
+    admin_council::CouncilAction,
+    checks::{check_address, check_owner_program},
+    events::{ConfigChangedEvent, ConfigQueuedEvent, InitPendingConfigEvent},
+    pda::create_pda_account,
+    roles::Role,
+    ApplyPendingConfig, ConfigMarinadeParams, Fee, InitPendingConfig, LpParams, State,
+};
+
+/// config_marinade and set_lp_params fee increases waiting for effective_epoch.
+/// Other changes are applied immediately.
+/// PDA [state, "pending_config"]
+#[account]
+#[derive(Debug, Default)]
+pub struct PendingConfig {
+    pub state_address: Pubkey, // instance of marinade state this config belongs to
+    /// apply_pending_config is possible starting from this epoch
+    pub effective_epoch: u64,
+    pub config: ConfigMarinadeParams,
+    pub lp_params: Option<LpParams>,
+}
+
+impl PendingConfig {
+    pub const SEED: &'static [u8] = b"pending_config";
+    /// epochs between queueing a fee increase and applying it
+    pub const FEE_INCREASE_NOTICE_EPOCHS: u64 = 2;
+
+    /// space for all the fields set
+    pub fn serialized_len() -> usize {
+        Self {
+            config: ConfigMarinadeParams {
+                rewards_fee: Some(Fee::default()),
+                slots_for_stake_delta: Some(0),
+                min_stake: Some(0),
+                min_deposit: Some(0),
+                min_withdraw: Some(0),
+                staking_sol_cap: Some(0),
+                liquidity_sol_cap: Some(0),
+                auto_add_validator_enabled: Some(false),
+                max_msol_price_increase: Some(Fee::default()),
+                max_msol_price_decrease: Some(Fee::default()),
+                directed_stake_epochs: Some(0),
+            },
+            lp_params: Some(LpParams::default()),
+            ..Default::default()
+        }
+        .try_to_vec()
+        .unwrap()
+        .len()
+            + 8
+    }
+
+    pub fn find_address(state: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::SEED], program_id)
+    }
+
+    pub fn check_state_address(&self, state_address: &Pubkey) -> ProgramResult {
+        check_address(state_address, &self.state_address, "pending_config state")
+    }
+
+    /// queued fee increases are applied together, so new ones can only delay the older ones
+    /// (never speeding them up). Only fee increases are queued
+    pub fn postpone_to(&mut self, effective_epoch: u64) {
+        self.effective_epoch = self.effective_epoch.max(effective_epoch);
+    }
+
+    /// checks params, queues a fee increase and applies the rest to state
+    pub fn queue_config(
+        &mut self,
+        state: &mut State,
+        params: ConfigMarinadeParams,
+        epoch: u64,
+    ) -> ProgramResult {
+        params.check(state)?;
+        let (fee_increase, immediate) = params.split_fee_increase(state);
+        if immediate != ConfigMarinadeParams::default() {
+            if immediate.rewards_fee.is_some() {
+                // not an increase, replaces the queued one
+                self.config.rewards_fee = None;
+                self.reset_if_empty();
+            }
+            immediate.apply(state);
+            emit!(ConfigChangedEvent {
+                state: self.state_address,
+                config: immediate,
+                lp_params: None,
+            });
+        }
+        if fee_increase != ConfigMarinadeParams::default() {
+            self.config = self.config.merge(fee_increase);
+            self.postpone_to(epoch + Self::FEE_INCREASE_NOTICE_EPOCHS);
+            msg!(
+                "Fee increase queued. Effective from epoch {}",
+                self.effective_epoch
+            );
+            self.emit_queued();
+        }
+        Ok(())
+    }
+
+    /// checks lp_params and queues them when they increase a fee, applies them otherwise
+    pub fn queue_lp_params(
+        &mut self,
+        state: &mut State,
+        lp_params: LpParams,
+        epoch: u64,
+    ) -> ProgramResult {
+        lp_params.check()?;
+        if lp_params.is_fee_increase(&state.liq_pool) {
+            self.lp_params = Some(lp_params);
+            self.postpone_to(epoch + Self::FEE_INCREASE_NOTICE_EPOCHS);
+            msg!(
+                "LP fee increase queued. Effective from epoch {}",
+                self.effective_epoch
+            );
+            self.emit_queued();
+        } else {
+            // replaces the queued ones
+            self.lp_params = None;
+            self.reset_if_empty();
+            lp_params.apply(&mut state.liq_pool);
+            emit!(ConfigChangedEvent {
+                state: self.state_address,
+                config: ConfigMarinadeParams::default(),
+                lp_params: Some(lp_params),
+            });
+        }
+        Ok(())
+    }
+
+    fn emit_queued(&self) {
+        emit!(ConfigQueuedEvent {
+            state: self.state_address,
+            config: self.config,
+            lp_params: self.lp_params,
+            effective_epoch: self.effective_epoch,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.config == ConfigMarinadeParams::default() && self.lp_params.is_none()
+    }
+
+    fn reset_if_empty(&mut self) {
+        if self.is_empty() {
+            self.effective_epoch = 0;
+        }
+    }
+}
+
+impl<'info> InitPendingConfig<'info> {
+    // fn init_pending_config()
+    pub fn process(&mut self) -> ProgramResult {
+        self.state.check_admin_or_council(
+            &self.admin_authority,
+            self.state.to_account_info().owner,
+            CouncilAction::InitPendingConfig,
+        )?;
+        check_owner_program(&self.rent_payer, &system_program::ID, "rent_payer")?;
+        check_address(
+            self.system_program.key,
+            &system_program::ID,
+            "system_program",
+        )?;
+
+        let state_address = self.state.instance_address;
+        let program_id = *self.state.to_account_info().owner;
+        let (pending_config_address, bump_seed) =
+            PendingConfig::find_address(&state_address, &program_id);
+        check_address(
+            self.pending_config.key,
+            &pending_config_address,
+            "pending_config",
+        )?;
+        check_owner_program(&self.pending_config, &system_program::ID, "pending_config")?;
+
+        create_pda_account(
+            &self.rent_payer,
+            &self.pending_config,
+            PendingConfig::serialized_len(),
+            &program_id,
+            &self.system_program,
+            &self.rent,
+            &[
+                &state_address.to_bytes()[..32],
+                PendingConfig::SEED,
+                &[bump_seed],
+            ],
+        )?;
+
+        let pending_config = PendingConfig {
+            state_address,
+            ..Default::default()
+        };
+        emit!(InitPendingConfigEvent {
+            state: state_address,
+            pending_config: *self.pending_config.key,
+        });
+        let mut data = self.pending_config.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        pending_config.try_serialize(&mut std::io::Cursor::new(dst))
+    }
+}
+
+impl<'info> ApplyPendingConfig<'info> {
+    // fn apply_pending_config()
+    // crank, permissionless while the crank operator role is not assigned
+    pub fn process(&mut self) -> ProgramResult {
+        if self.state.roles.crank_operator != Pubkey::default() {
+            if !self.crank_operator.is_signer {
+                msg!("Crank operator must sign");
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            self.state
+                .check_admin_or_role(Role::CrankOperator, self.crank_operator.key)?;
+        }
+        self.pending_config
+            .check_state_address(&self.state.instance_address)?;
+        if self.pending_config.is_empty() {
+            msg!("Nothing to apply");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if self.clock.epoch < self.pending_config.effective_epoch {
+            msg!(
+                "Pending config is effective from epoch {}",
+                self.pending_config.effective_epoch
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        self.pending_config.config.apply(&mut self.state);
+        if let Some(lp_params) = self.pending_config.lp_params {
+            lp_params.apply(&mut self.state.liq_pool);
+        }
+        emit!(ConfigChangedEvent {
+            state: self.pending_config.state_address,
+            config: self.pending_config.config,
+            lp_params: self.pending_config.lp_params,
+        });
+
+        self.pending_config.config = ConfigMarinadeParams::default();
+        self.pending_config.lp_params = None;
+        self.pending_config.effective_epoch = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn test_state() -> State {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.reward_fee = Fee::from_basis_points(200);
+        state.liq_pool.lp_liquidity_target = 100 * SOL;
+        state.liq_pool.lp_min_fee = Fee::from_basis_points(30);
+        state.liq_pool.lp_max_fee = Fee::from_basis_points(300);
+        state
+    }
+
+    #[test]
+    fn test_merge() {
+        let older = ConfigMarinadeParams {
+            rewards_fee: Some(Fee::from_basis_points(300)),
+            min_deposit: Some(1),
+            ..Default::default()
+        };
+        let newer = ConfigMarinadeParams {
+            rewards_fee: Some(Fee::from_basis_points(400)),
+            staking_sol_cap: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            older.merge(newer),
+            ConfigMarinadeParams {
+                rewards_fee: Some(Fee::from_basis_points(400)),
+                min_deposit: Some(1),
+                staking_sol_cap: Some(2),
+                ..Default::default()
+            }
+        );
+        assert_eq!(older.merge(ConfigMarinadeParams::default()), older);
+    }
+
+    #[test]
+    fn test_postpone() {
+        let mut pending_config = PendingConfig::default();
+        pending_config.postpone_to(12);
+        assert_eq!(pending_config.effective_epoch, 12);
+        // never sooner
+        pending_config.postpone_to(11);
+        assert_eq!(pending_config.effective_epoch, 12);
+        pending_config.postpone_to(13);
+        assert_eq!(pending_config.effective_epoch, 13);
+    }
+
+    #[test]
+    fn test_queue_config() -> ProgramResult {
+        let mut state = test_state();
+        let mut pending_config = PendingConfig::default();
+
+        // the fee increase waits, the cap applies immediately
+        pending_config.queue_config(
+            &mut state,
+            ConfigMarinadeParams {
+                rewards_fee: Some(Fee::from_basis_points(300)),
+                staking_sol_cap: Some(1_000 * SOL),
+                ..Default::default()
+            },
+            10,
+        )?;
+        assert_eq!(state.staking_sol_cap, 1_000 * SOL);
+        assert_eq!(state.reward_fee, Fee::from_basis_points(200));
+        assert_eq!(
+            pending_config.config,
+            ConfigMarinadeParams {
+                rewards_fee: Some(Fee::from_basis_points(300)),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            pending_config.effective_epoch,
+            10 + PendingConfig::FEE_INCREASE_NOTICE_EPOCHS
+        );
+
+        // urgent changes are not delayed by the queued fee increase
+        pending_config.queue_config(
+            &mut state,
+            ConfigMarinadeParams {
+                max_msol_price_decrease: Some(Fee::from_basis_points(100)),
+                ..Default::default()
+            },
+            11,
+        )?;
+        assert_eq!(state.max_msol_price_decrease, Fee::from_basis_points(100));
+        assert_eq!(
+            pending_config.effective_epoch,
+            10 + PendingConfig::FEE_INCREASE_NOTICE_EPOCHS
+        );
+
+        // a fee decrease applies immediately and drops the queued increase
+        pending_config.queue_config(
+            &mut state,
+            ConfigMarinadeParams {
+                rewards_fee: Some(Fee::from_basis_points(100)),
+                ..Default::default()
+            },
+            11,
+        )?;
+        assert_eq!(state.reward_fee, Fee::from_basis_points(100));
+        assert!(pending_config.is_empty());
+        assert_eq!(pending_config.effective_epoch, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_lp_params() -> ProgramResult {
+        let mut state = test_state();
+        let mut pending_config = PendingConfig::default();
+        let increase = LpParams {
+            min_fee: Fee::from_basis_points(30),
+            max_fee: Fee::from_basis_points(500),
+            liquidity_target: 100 * SOL,
+        };
+        pending_config.queue_lp_params(&mut state, increase, 10)?;
+        assert_eq!(state.liq_pool.lp_max_fee, Fee::from_basis_points(300));
+        assert_eq!(pending_config.lp_params, Some(increase));
+
+        let decrease = LpParams {
+            max_fee: Fee::from_basis_points(200),
+            ..increase
+        };
+        pending_config.queue_lp_params(&mut state, decrease, 11)?;
+        assert_eq!(state.liq_pool.lp_max_fee, Fee::from_basis_points(200));
+        assert!(pending_config.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_lp_liquidity_target() -> ProgramResult {
+        let mut state = test_state();
+        let mut pending_config = PendingConfig::default();
+        // same fees with a higher target: less liquidity counts as full, so the fee rises
+        let higher_target = LpParams {
+            min_fee: Fee::from_basis_points(30),
+            max_fee: Fee::from_basis_points(300),
+            liquidity_target: 200 * SOL,
+        };
+        assert!(higher_target.is_fee_increase(&state.liq_pool));
+        pending_config.queue_lp_params(&mut state, higher_target, 10)?;
+        assert_eq!(state.liq_pool.lp_liquidity_target, 100 * SOL);
+        assert_eq!(pending_config.lp_params, Some(higher_target));
+        assert_eq!(
+            pending_config.effective_epoch,
+            10 + PendingConfig::FEE_INCREASE_NOTICE_EPOCHS
+        );
+
+        let lower_target = LpParams {
+            liquidity_target: 60 * SOL,
+            ..higher_target
+        };
+        assert!(!lower_target.is_fee_increase(&state.liq_pool));
+        pending_config.queue_lp_params(&mut state, lower_target, 11)?;
+        assert_eq!(state.liq_pool.lp_liquidity_target, 60 * SOL);
+        assert!(pending_config.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use anchor_lang::prelude::*;
+
+use crate::{return_data::set_return_data, CommonError, GetPriceAtEpoch};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct PriceSample {
+    pub epoch: u64,
+    pub msol_price: u64, // State::PRICE_DENOMINATOR based. 0 = empty sample
+    pub timestamp: i64,  // of the last update in the epoch
+}
+
+/// Mean msol_price of the samples recorded in epochs from_epoch..=to_epoch
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct PriceAverage {
+    pub from_epoch: u64,
+    pub to_epoch: u64,   // epoch of the latest sample
+    pub samples: u32,    // epochs without a sample (crank not run, restricted mode) are skipped
+    pub msol_price: u64, // State::PRICE_DENOMINATOR based
+}
+
+/// msol_price of the last CAPACITY epochs, set only by the update crank while not in restricted mode.
+/// Ring buffer, one sample per epoch holding the last price of the epoch
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct PriceHistory {
+    /// index of the latest sample
+    pub head: u32,
+    pub samples: [PriceSample; PriceHistory::CAPACITY],
+}
+
+impl PriceHistory {
+    pub const CAPACITY: usize = 16;
+
+    pub fn latest(&self) -> &PriceSample {
+        &self.samples[self.head as usize]
+    }
+
+    pub fn record(&mut self, epoch: u64, msol_price: u64, timestamp: i64) {
+        let latest = self.latest();
+        if latest.msol_price != 0 && latest.epoch != epoch {
+            self.head = ((self.head as usize + 1) % Self::CAPACITY) as u32;
+        }
+        self.samples[self.head as usize] = PriceSample {
+            epoch,
+            msol_price,
+            timestamp,
+        };
+    }
+
+    pub fn get(&self, epoch: u64) -> Option<&PriceSample> {
+        self.samples
+            .iter()
+            .find(|sample| sample.msol_price != 0 && sample.epoch == epoch)
+    }
+
+    /// Average price of the last `epochs` epochs up to the latest sample
+    pub fn average(&self, epochs: u64) -> Option<PriceAverage> {
+        let latest = self.latest();
+        if epochs == 0 || epochs > Self::CAPACITY as u64 || latest.msol_price == 0 {
+            return None;
+        }
+        let from_epoch = latest.epoch.saturating_sub(epochs - 1);
+        let (sum, samples) = self
+            .samples
+            .iter()
+            .filter(|sample| {
+                sample.msol_price != 0 && sample.epoch >= from_epoch && sample.epoch <= latest.epoch
+            })
+            .fold((0u128, 0u32), |(sum, samples), sample| {
+                (sum + sample.msol_price as u128, samples + 1)
+            });
+        Some(PriceAverage {
+            from_epoch,
+            to_epoch: latest.epoch,
+            samples,
+            msol_price: (sum / samples as u128) as u64,
+        })
+    }
+}
+
+impl<'info> GetPriceAtEpoch<'info> {
+    // fn get_price_at_epoch() -> PriceSample
+    // read-only, the sample is set as borsh-serialized return data
+    pub fn process(&self, epoch: u64) -> ProgramResult {
+        let sample = self.state.price_history.get(epoch).ok_or_else(|| {
+            msg!(
+                "No mSOL price for epoch {}. Latest is for epoch {}",
+                epoch,
+                self.state.price_history.latest().epoch
+            );
+            CommonError::PriceNotRecorded
+        })?;
+        set_return_data(&sample.try_to_vec()?);
+        Ok(())
+    }
+
+    // fn get_average_price() -> PriceAverage
+    // read-only TWAP-like view: mean of the per-epoch samples of the last `epochs` epochs
+    pub fn get_average_price(&self, epochs: u64) -> ProgramResult {
+        let average = self.state.price_history.average(epochs).ok_or_else(|| {
+            msg!(
+                "No mSOL price average for {} epochs (1 to {}). Latest sample is for epoch {}",
+                epochs,
+                PriceHistory::CAPACITY,
+                self.state.price_history.latest().epoch
+            );
+            CommonError::PriceNotRecorded
+        })?;
+        set_return_data(&average.try_to_vec()?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut history = PriceHistory::default();
+        history.record(5, 100, 1);
+        assert_eq!(history.head, 0);
+        // same epoch overwrites the sample
+        history.record(5, 101, 2);
+        assert_eq!(history.head, 0);
+        assert_eq!(
+            history.latest(),
+            &PriceSample {
+                epoch: 5,
+                msol_price: 101,
+                timestamp: 2
+            }
+        );
+        history.record(6, 102, 3);
+        assert_eq!(history.head, 1);
+        assert_eq!(history.latest().msol_price, 102);
+        assert_eq!(history.samples[0].msol_price, 101);
+    }
+
+    #[test]
+    fn test_get_wrap_around() {
+        let mut history = PriceHistory::default();
+        assert_eq!(history.get(0), None);
+        let capacity = PriceHistory::CAPACITY as u64;
+        for epoch in 0..=capacity {
+            history.record(epoch, 1000 + epoch, epoch as i64);
+        }
+        assert_eq!(history.head, 0);
+        // epoch 0 was replaced by the newest one
+        assert_eq!(history.get(0), None);
+        assert_eq!(history.get(1).unwrap().msol_price, 1001);
+        assert_eq!(history.get(capacity).unwrap().msol_price, 1000 + capacity);
+        assert_eq!(history.get(capacity + 1), None);
+    }
+
+    #[test]
+    fn test_average() {
+        let mut history = PriceHistory::default();
+        assert_eq!(history.average(1), None);
+        history.record(10, 100, 0);
+        history.record(11, 200, 0);
+        // epoch 12 skipped
+        history.record(13, 400, 0);
+        assert_eq!(
+            history.average(1),
+            Some(PriceAverage {
+                from_epoch: 13,
+                to_epoch: 13,
+                samples: 1,
+                msol_price: 400
+            })
+        );
+        assert_eq!(
+            history.average(3),
+            Some(PriceAverage {
+                from_epoch: 11,
+                to_epoch: 13,
+                samples: 2,
+                msol_price: 300
+            })
+        );
+        assert_eq!(history.average(16).unwrap().msol_price, 233);
+        assert_eq!(history.average(0), None);
+        assert_eq!(history.average(PriceHistory::CAPACITY as u64 + 1), None);
+    }
+}
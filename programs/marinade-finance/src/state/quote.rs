@@ -0,0 +1,297 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    checks::check_min_amount, liq_pool::LiqPoolHelpers, return_data::set_return_data, CommonError,
+    Quote, State,
+};
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize, Debug, PartialEq)]
+pub struct DepositQuote {
+    pub msol_out: u64,           // total mSOL the user gets
+    pub msol_from_liq_pool: u64, // part sold by the liq-pool mSOL leg
+    pub msol_minted: u64,        // part minted for lamports going to reserve
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize, Debug, PartialEq)]
+pub struct LiquidUnstakeQuote {
+    pub lamports_out: u64,
+    pub msol_fee: u64, // goes to the treasury & LPs
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize, Debug, PartialEq)]
+pub struct RemoveLiquidityQuote {
+    pub lamports_out: u64,
+    pub msol_out: u64,
+}
+
+/// Quotes use the same calculations and the same paused/restricted gates as the real
+/// instructions at the current state, so a quote fails when the operation would fail
+impl State {
+    /// deposit of lamports when the liq-pool mSOL leg holds msol_leg_balance
+    pub fn quote_deposit(
+        &self,
+        lamports: u64,
+        msol_leg_balance: u64,
+    ) -> Result<DepositQuote, ProgramError> {
+        self.check_not_paused(State::PAUSE_DEPOSIT)?;
+        self.check_not_restricted()?;
+        check_min_amount(lamports, self.min_deposit, "deposit SOL")?;
+        let (msol_from_liq_pool, lamports_for_the_liq_pool, msol_minted) =
+            self.calc_deposit(lamports, msol_leg_balance)?;
+        let user_lamports = lamports.saturating_sub(lamports_for_the_liq_pool);
+        if user_lamports > 0 {
+            self.check_staking_cap(user_lamports)?;
+        }
+        Ok(DepositQuote {
+            msol_out: msol_from_liq_pool + msol_minted,
+            msol_from_liq_pool,
+            msol_minted,
+        })
+    }
+
+    /// liquid unstake of msol_amount with sol_leg_lamports (without rent) in the liq-pool
+    pub fn quote_liquid_unstake(
+        &self,
+        msol_amount: u64,
+        sol_leg_lamports: u64,
+    ) -> Result<LiquidUnstakeQuote, ProgramError> {
+        self.check_not_paused(State::PAUSE_LIQUID_UNSTAKE)?;
+        self.check_not_restricted()?;
+        let (msol_fee, lamports_out) = self.calc_liquid_unstake(msol_amount, sol_leg_lamports)?;
+        if lamports_out > sol_leg_lamports {
+            return Err(CommonError::InsufficientLiquidity.into());
+        }
+        check_min_amount(lamports_out, self.min_withdraw, "withdraw SOL")?;
+        Ok(LiquidUnstakeQuote {
+            lamports_out,
+            msol_fee,
+        })
+    }
+
+    /// ticket lamports for msol_amount
+    pub fn quote_order_unstake(&self, msol_amount: u64) -> Result<u64, ProgramError> {
+        self.check_not_paused(State::PAUSE_ORDER_UNSTAKE)?;
+        self.check_not_restricted()?;
+        let lamports_amount = self.calc_lamports_from_msol_amount(msol_amount)?;
+        check_min_amount(lamports_amount, self.min_withdraw, "withdraw SOL")?;
+        Ok(lamports_amount)
+    }
+
+    /// LP tokens for lamports, sol_leg_balance includes the rent
+    pub fn quote_add_liquidity(
+        &self,
+        lamports: u64,
+        sol_leg_balance: u64,
+        msol_leg_balance: u64,
+    ) -> Result<u64, ProgramError> {
+        self.check_not_paused(State::PAUSE_ADD_LIQUIDITY)?;
+        check_min_amount(lamports, self.min_deposit, "add_liquidity")?;
+        self.liq_pool
+            .check_liquidity_cap(lamports, sol_leg_balance)?;
+        Ok(self.calc_lp_from_lamports(
+            lamports,
+            sol_leg_balance.saturating_sub(self.rent_exempt_for_token_acc),
+            msol_leg_balance,
+        )?)
+    }
+
+    /// lamports and mSOL for burning LP tokens, sol_leg_lamports without rent
+    pub fn quote_remove_liquidity(
+        &self,
+        tokens: u64,
+        sol_leg_lamports: u64,
+        msol_leg_balance: u64,
+    ) -> Result<RemoveLiquidityQuote, ProgramError> {
+        self.check_not_paused(State::PAUSE_REMOVE_LIQUIDITY)?;
+        let (lamports_out, msol_out) =
+            self.liq_pool
+                .calc_remove_liquidity(tokens, sol_leg_lamports, msol_leg_balance)?;
+        check_min_amount(
+            lamports_out
+                .checked_add(self.calc_lamports_from_msol_amount(msol_out)?)
+                .ok_or(CommonError::CalculationFailure)?,
+            self.min_withdraw,
+            "removed liquidity",
+        )?;
+        Ok(RemoveLiquidityQuote {
+            lamports_out,
+            msol_out,
+        })
+    }
+}
+
+/// Quote instructions: read-only previews of deposit/unstake/liquidity operations
+/// returned borsh-serialized through program return data
+impl<'info> Quote<'info> {
+    fn check_liq_pool(&self) -> ProgramResult {
+        self.state
+            .check_liq_pool_sol_leg_pda(self.liq_pool_sol_leg_pda.key)?;
+        self.state
+            .liq_pool
+            .check_liq_pool_msol_leg(self.liq_pool_msol_leg.to_account_info().key)
+    }
+
+    fn sol_leg_lamports(&self) -> u64 {
+        self.liq_pool_sol_leg_pda
+            .lamports()
+            .saturating_sub(self.state.rent_exempt_for_token_acc)
+    }
+
+    fn set_quote<T: AnchorSerialize>(quote: &T) -> ProgramResult {
+        set_return_data(&quote.try_to_vec()?);
+        Ok(())
+    }
+
+    // fn quote_deposit() -> DepositQuote
+    pub fn quote_deposit(&self, lamports: u64) -> ProgramResult {
+        self.check_liq_pool()?;
+        Self::set_quote(
+            &self
+                .state
+                .quote_deposit(lamports, self.liq_pool_msol_leg.amount)?,
+        )
+    }
+
+    // fn quote_liquid_unstake() -> LiquidUnstakeQuote
+    pub fn quote_liquid_unstake(&self, msol_amount: u64) -> ProgramResult {
+        self.check_liq_pool()?;
+        Self::set_quote(
+            &self
+                .state
+                .quote_liquid_unstake(msol_amount, self.sol_leg_lamports())?,
+        )
+    }
+
+    // fn quote_order_unstake() -> u64 (ticket lamports)
+    pub fn quote_order_unstake(&self, msol_amount: u64) -> ProgramResult {
+        Self::set_quote(&self.state.quote_order_unstake(msol_amount)?)
+    }
+
+    // fn quote_add_liquidity() -> u64 (LP tokens)
+    pub fn quote_add_liquidity(&self, lamports: u64) -> ProgramResult {
+        self.check_liq_pool()?;
+        Self::set_quote(&self.state.quote_add_liquidity(
+            lamports,
+            self.liq_pool_sol_leg_pda.lamports(),
+            self.liq_pool_msol_leg.amount,
+        )?)
+    }
+
+    // fn quote_remove_liquidity() -> RemoveLiquidityQuote
+    pub fn quote_remove_liquidity(&self, tokens: u64) -> ProgramResult {
+        self.check_liq_pool()?;
+        Self::set_quote(&self.state.quote_remove_liquidity(
+            tokens,
+            self.sol_leg_lamports(),
+            self.liq_pool_msol_leg.amount,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{return_data::get_return_data, Fee};
+    use std::mem::MaybeUninit;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn test_state() -> State {
+        let mut state: State = unsafe { MaybeUninit::zeroed().assume_init() };
+        state.available_reserve_balance = 1_000 * SOL;
+        state.msol_supply = 1_000 * SOL; // msol_price = 1
+        state.staking_sol_cap = u64::MAX;
+        state.min_deposit = 1;
+        state.min_withdraw = 1;
+        state.liq_pool.lp_liquidity_target = 100 * SOL;
+        state.liq_pool.lp_min_fee = Fee::from_basis_points(30);
+        state.liq_pool.lp_max_fee = Fee::from_basis_points(300);
+        state.liq_pool.liquidity_sol_cap = u64::MAX;
+        state.liq_pool.lp_supply = 100 * SOL;
+        state
+    }
+
+    #[test]
+    fn test_quote_deposit() -> ProgramResult {
+        let state = test_state();
+        // partially filled by the liq-pool mSOL leg, the rest minted
+        let quote = state.quote_deposit(10 * SOL, 4 * SOL)?;
+        assert_eq!(
+            quote,
+            DepositQuote {
+                msol_out: 10 * SOL,
+                msol_from_liq_pool: 4 * SOL,
+                msol_minted: 6 * SOL,
+            }
+        );
+        let (swapped, _, minted) = state.calc_deposit(10 * SOL, 4 * SOL)?;
+        assert_eq!((swapped, minted), (4 * SOL, 6 * SOL));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_liquid_unstake() -> ProgramResult {
+        let state = test_state();
+        let quote = state.quote_liquid_unstake(SOL, 1_000 * SOL)?;
+        // plenty of liquidity: min fee
+        assert_eq!(quote.msol_fee, Fee::from_basis_points(30).apply(SOL));
+        assert_eq!(quote.lamports_out, SOL - quote.msol_fee);
+        assert!(state.quote_liquid_unstake(2 * SOL, SOL).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_liquidity() -> ProgramResult {
+        let state = test_state();
+        // 100 LP for a pool of 100 SOL
+        let rent = state.rent_exempt_for_token_acc;
+        assert_eq!(state.quote_add_liquidity(SOL, 100 * SOL + rent, 0)?, SOL);
+        assert_eq!(
+            state.quote_remove_liquidity(10 * SOL, 50 * SOL, 50 * SOL)?,
+            RemoveLiquidityQuote {
+                lamports_out: 5 * SOL,
+                msol_out: 5 * SOL,
+            }
+        );
+        assert_eq!(state.quote_order_unstake(3 * SOL)?, 3 * SOL);
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_gates() {
+        let mut state = test_state();
+        state.paused_operations = State::PAUSE_DEPOSIT | State::PAUSE_REMOVE_LIQUIDITY;
+        assert!(state.quote_deposit(SOL, 0).is_err());
+        assert!(state.quote_remove_liquidity(SOL, SOL, SOL).is_err());
+        assert!(state.quote_liquid_unstake(SOL, 100 * SOL).is_ok());
+
+        state.paused_operations = 0;
+        state.restricted_mode = 1;
+        assert!(state.quote_deposit(SOL, 0).is_err());
+        assert!(state.quote_liquid_unstake(SOL, 100 * SOL).is_err());
+        assert!(state.quote_order_unstake(SOL).is_err());
+        // the liq-pool is not restricted
+        assert!(state.quote_add_liquidity(SOL, 100 * SOL, 0).is_ok());
+
+        state.restricted_mode = 0;
+        state.staking_sol_cap = 0;
+        assert!(state.quote_deposit(SOL, 0).is_err());
+        // filled by the liq-pool entirely: no new stake
+        assert!(state.quote_deposit(SOL, 10 * SOL).is_ok());
+    }
+
+    #[test]
+    fn test_set_quote() -> ProgramResult {
+        let quote = LiquidUnstakeQuote {
+            lamports_out: 7,
+            msol_fee: 3,
+        };
+        Quote::set_quote(&quote)?;
+        let data = get_return_data();
+        // borsh: little-endian fields in declaration order
+        assert_eq!(data.len(), 16);
+        assert_eq!(&data[..8], &7u64.to_le_bytes());
+        assert_eq!(LiquidUnstakeQuote::try_from_slice(&data)?, quote);
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+use crate::{checks::check_owner_program, events::SplitTicketEvent, SplitTicket, State};
+
+/// SplitTicket instruction: moves lamports_amount from the ticket to a new ticket PDA
+/// [state, "ticket", beneficiary, new_ticket_nonce] with the same beneficiary and created_epoch
+impl<'info> SplitTicket<'info> {
+    pub fn process(&mut self, lamports_amount: u64, new_ticket_nonce: u64) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_TICKET)?;
+        // fn split_ticket()
+        check_owner_program(
+            &self.ticket_account,
+            self.state.to_account_info().owner,
+            "ticket_account",
+        )?;
+        self.ticket_account
+            .check_held_by(&self.state.instance_address, self.beneficiary.key)?;
+
+        let new_ticket = self
+            .ticket_account
+            .split_off(lamports_amount, self.state.min_withdraw)?;
+        new_ticket.create_pda(
+            self.state.to_account_info().owner,
+            new_ticket_nonce,
+            &self.new_ticket_account,
+            &self.rent_payer,
+            &self.system_program,
+            &self.rent,
+        )?;
+
+        // circulating_ticket_balance is not changed
+        self.state.circulating_ticket_count += 1;
+        emit!(SplitTicketEvent {
+            state: new_ticket.state_address,
+            ticket: *self.ticket_account.to_account_info().key,
+            new_ticket: *self.new_ticket_account.key,
+            lamports: lamports_amount,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::system_program;
+
+use crate::{checks::check_owner_program, events::TransferTicketEvent, State, TransferTicket};
+
+/// TransferTicket instruction: the ticket beneficiary hands the ticket over to a new beneficiary
+impl<'info> TransferTicket<'info> {
+    pub fn process(&mut self) -> ProgramResult {
+        self.state.check_not_paused(State::PAUSE_TICKET)?;
+        // fn transfer_ticket()
+        check_owner_program(
+            &self.ticket_account,
+            self.state.to_account_info().owner,
+            "ticket_account",
+        )?;
+        self.ticket_account
+            .check_held_by(&self.state.instance_address, self.beneficiary.key)?;
+        // Claim pays only to system accounts
+        check_owner_program(
+            &self.new_beneficiary,
+            &system_program::ID,
+            "new_beneficiary",
+        )?;
+
+        msg!(
+            "Ticket {} beneficiary {} -> {}",
+            self.ticket_account.to_account_info().key,
+            self.ticket_account.beneficiary,
+            self.new_beneficiary.key
+        );
+        emit!(TransferTicketEvent {
+            state: self.state.instance_address,
+            ticket: *self.ticket_account.to_account_info().key,
+            old_beneficiary: self.ticket_account.beneficiary,
+            new_beneficiary: *self.new_beneficiary.key,
+        });
+        self.ticket_account.beneficiary = *self.new_beneficiary.key;
+        Ok(())
+    }
+}
@@ -9,8 +9,14 @@ use anchor_spl::token::{mint_to, MintTo};
 use crate::error::CommonError;
 use crate::{
     checks::check_address,
-    stake_system::{StakeRecord, StakeSystemHelpers},
+    epoch_stats::EpochStats,
+    events::{
+        CircuitBreakerAlert, DelegationChange, PriceChange, UpdateActiveEvent,
+        UpdateDeactivatedEvent,
+    },
+    stake_system::{cooling_in::mint_cooling_in, StakeRecord, StakeSystemHelpers},
     state::StateHelpers,
+    DepositStakeAccount,
     State,
     UpdateActive,
     UpdateCommon,
@@ -25,6 +31,7 @@ struct BeginOutput {
 
 impl<'info> UpdateCommon<'info> {
     fn begin(&mut self, stake_index: u32) -> Result<BeginOutput, ProgramError> {
+        self.state.check_not_paused(State::PAUSE_UPDATE)?;
         /*
         self.state
             .validator_system
@@ -65,13 +72,19 @@ impl<'info> UpdateCommon<'info> {
         // Update mSOL supply
         // impossible to happen check outside bug (msol mint auth is a PDA)
         if self.msol_mint.supply > self.state.msol_supply {
+            let minted_outside = self.msol_mint.supply - self.state.msol_supply;
             msg!(
                 "Warning: mSOL minted {} lamports outside of marinade",
-                self.msol_mint.supply - self.state.msol_supply
+                minted_outside
             );
-            self.state.staking_sol_cap = 0;
+            self.enter_restricted_mode(minted_outside);
         }
         self.state.msol_supply = self.msol_mint.supply;
+        // the price band is relative to the price before the first update of the epoch
+        if self.state.price_band_epoch != self.clock.epoch {
+            self.state.price_band_epoch = self.clock.epoch;
+            self.state.price_band_reference = self.state.msol_price;
+        }
 
         let stake = self.state.stake_system.get_checked(
             &self.stake_list.data.as_ref().borrow(),
@@ -89,6 +102,44 @@ impl<'info> UpdateCommon<'info> {
         })
     }
 
+    fn enter_restricted_mode(&mut self, msol_minted_outside: u64) {
+        if self.state.restricted_mode == 0 {
+            self.state.restricted_mode = 1;
+            emit!(CircuitBreakerAlert {
+                state: self.state.instance_address,
+                epoch: self.clock.epoch,
+                price_band_reference: self.state.price_band_reference,
+                msol_price: self.state.msol_price,
+                msol_minted_outside,
+            });
+        }
+    }
+
+    /// set new mSOL price and enter the restricted mode if it moved too much this epoch
+    pub fn update_msol_price(&mut self) -> ProgramResult {
+        let msol_price = self
+            .state
+            .calc_lamports_from_msol_amount(State::PRICE_DENOMINATOR)?; // store binary-denominated mSOL price
+        self.state.msol_price = msol_price;
+        if self.state.is_msol_price_out_of_band() {
+            msg!(
+                "mSOL price {} is out of band. Epoch start price {}",
+                self.state.msol_price,
+                self.state.price_band_reference
+            );
+            self.enter_restricted_mode(0);
+        }
+        // an untrusted price is not kept for the consumers of the history
+        if self.state.restricted_mode == 0 {
+            self.state.price_history.record(
+                self.clock.epoch,
+                msol_price,
+                self.clock.unix_timestamp,
+            );
+        }
+        Ok(())
+    }
+
     pub fn withdraw_to_reserve(&mut self, amount: u64) -> ProgramResult {
         if amount > 0 {
             self.state.with_stake_withdraw_authority_seeds(|seeds| {
@@ -146,12 +197,22 @@ impl<'info> UpdateActive<'info> {
     /// Future optional expansion: Partial: If the stake-account is a fully-deactivated stake account ready to withdraw,
     /// (cool-down period is complete) delete-withdraw the stake-account, send SOL to reserve-account
     //
+    /// A stake deposited while activating (cooling in) is skipped until fully active,
+    /// then the mSOL for it is minted into the cooling-in escrow and credited to its claim
+    //
     // fn update_active()
-    pub fn process(&mut self, stake_index: u32, validator_index: u32) -> ProgramResult {
+    pub fn process(
+        &mut self,
+        stake_index: u32,
+        validator_index: u32,
+        cooling_in_accounts: &[AccountInfo<'info>],
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
         let BeginOutput {
             mut stake,
             is_treasury_msol_ready_for_transfer,
         } = self.begin(stake_index)?;
+        let old_msol_price = self.state.msol_price;
 
         let mut validator = self
             .state
@@ -170,7 +231,7 @@ impl<'info> UpdateActive<'info> {
             );
             return Err(ProgramError::InvalidInstructionData);
         }
-        if delegation.deactivation_epoch != std::u64::MAX {
+        if delegation.deactivation_epoch != u64::MAX {
             // is deactivated or deactivating
             msg!(
                 "Cooling down stake {}. Please use UpdateCoolingDown",
@@ -178,6 +239,27 @@ impl<'info> UpdateActive<'info> {
             );
             return Err(ProgramError::InvalidAccountData);
         }
+        if stake.is_cooling_in()
+            && self.clock.epoch
+                < delegation
+                    .activation_epoch
+                    .checked_add(DepositStakeAccount::WAIT_EPOCHS)
+                    .unwrap()
+        {
+            msg!(
+                "Stake {} is cooling in",
+                self.stake_account.to_account_info().key
+            );
+            return Ok(()); // Not an error. Nothing to update yet
+        }
+        if stake.is_cooling_in() && self.state.restricted_mode != 0 {
+            // the price is not trusted, mint when it is back to normal
+            msg!(
+                "Stake {} is activated. Restricted mode: mSOL is not minted for it",
+                self.stake_account.to_account_info().key
+            );
+            return Ok(());
+        }
         // current lamports amount, to compare with previous
         let delegated_lamports = delegation.stake;
 
@@ -195,7 +277,57 @@ impl<'info> UpdateActive<'info> {
             self.mint_to_treasury(msol_amount)?;
         }
 
+        let mut cooling_in_msol_minted = 0;
+        if stake.is_cooling_in() {
+            // fully active now: mint mSOL for the whole stake at the current price
+            // and account it as active from now on
+            let (claim_account, escrow) = match cooling_in_accounts {
+                [claim_account, escrow] => (claim_account, escrow),
+                _ => {
+                    msg!(
+                        "Stake {} is cooling in. Need its claim {} and the escrow in remaining accounts",
+                        stake.stake_account,
+                        stake.cooling_in_claim
+                    );
+                    return Err(ProgramError::NotEnoughAccountKeys);
+                }
+            };
+            let msol_to_mint = self.state.calc_msol_from_lamports(delegated_lamports)?;
+            msg!(
+                "Stake {} activated. Mint {} mSOL",
+                stake.stake_account,
+                msol_to_mint
+            );
+            mint_cooling_in(
+                &mut self.common.state,
+                &stake,
+                claim_account,
+                escrow,
+                &self.common.msol_mint.to_account_info(),
+                &self.common.msol_mint_authority,
+                &self.common.token_program,
+                msol_to_mint,
+            )?;
+            cooling_in_msol_minted = msol_to_mint;
+
+            self.state.on_cooling_in_activated(
+                stake.last_update_delegated_lamports,
+                delegated_lamports,
+            )?;
+            validator.active_balance = validator
+                .active_balance
+                .checked_add(delegated_lamports)
+                .ok_or(CommonError::CalculationFailure)?;
+            stake.last_update_delegated_lamports = delegated_lamports;
+            stake.cooling_in_claim = Pubkey::default();
+        }
+
         msg!("current staked lamports {}", delegated_lamports);
+        let mut delegation_change = DelegationChange {
+            rewards: 0,
+            slashed: 0,
+        };
+        let mut msol_fees = 0;
         if delegated_lamports >= stake.last_update_delegated_lamports {
             // re-delegated by solana rewards
             let rewards = delegated_lamports - stake.last_update_delegated_lamports;
@@ -209,7 +341,9 @@ impl<'info> UpdateActive<'info> {
                 let fee_as_msol_amount =
                     self.state.calc_msol_from_lamports(protocol_rewards_fee)?;
                 self.mint_to_treasury(fee_as_msol_amount)?;
+                msol_fees = fee_as_msol_amount;
             }
+            delegation_change.rewards = rewards;
 
             // validator active balance is updated with rewards
             validator.active_balance += rewards;
@@ -219,6 +353,7 @@ impl<'info> UpdateActive<'info> {
             //slashed
             let slashed = stake.last_update_delegated_lamports - delegated_lamports;
             msg!("slashed {}", slashed);
+            delegation_change.slashed = slashed;
             //validator balance is updated with slashed
             validator.active_balance = validator.active_balance.saturating_sub(slashed);
             self.state.validator_system.total_active_balance = self
@@ -241,9 +376,7 @@ impl<'info> UpdateActive<'info> {
 
         // self.state.stake_system.updated_during_last_epoch += 1;*/
         // set new mSOL price
-        self.state.msol_price = self
-            .state
-            .calc_lamports_from_msol_amount(State::PRICE_DENOMINATOR)?; // store binary-denominated mSOL price
+        self.update_msol_price()?;
         self.state.stake_system.set(
             &mut self.stake_list.data.as_ref().borrow_mut(),
             stake_index,
@@ -254,6 +387,35 @@ impl<'info> UpdateActive<'info> {
             self.state.available_reserve_balance + self.state.rent_exempt_for_token_acc,
             self.reserve_pda.lamports()
         );
+        EpochStats::record(
+            epoch_stats,
+            &self.common.state.instance_address,
+            self.common.state.to_account_info().owner,
+            |stats, epoch| {
+                stats.on_update(
+                    epoch,
+                    self.common.state.msol_price,
+                    delegation_change.rewards,
+                    delegation_change.slashed,
+                    msol_fees,
+                )
+            },
+        )?;
+        emit!(UpdateActiveEvent {
+            state: self.state.instance_address,
+            stake_account: stake.stake_account,
+            stake_index,
+            validator: validator.validator_account,
+            validator_index,
+            delegation_change,
+            msol_fees,
+            extra_lamports,
+            cooling_in_msol_minted,
+            msol_price_change: PriceChange {
+                old: old_msol_price,
+                new: self.state.msol_price,
+            },
+        });
         Ok(())
     }
 }
@@ -264,11 +426,16 @@ impl<'info> UpdateDeactivated<'info> {
     /// update mSOL price accordingly
     /// Optional Future Expansion: Partial: If the stake-account is a fully-deactivated stake account ready to withdraw,
     /// (cool-down period is complete) delete-withdraw the stake-account, send SOL to reserve-account
-    pub fn process(&mut self, stake_index: u32) -> ProgramResult {
+    pub fn process(
+        &mut self,
+        stake_index: u32,
+        epoch_stats: Option<&AccountInfo>,
+    ) -> ProgramResult {
         let BeginOutput {
             stake,
             is_treasury_msol_ready_for_transfer,
         } = self.begin(stake_index)?;
+        let old_msol_price = self.state.msol_price;
 
         check_address(
             self.system_program.to_account_info().key,
@@ -282,7 +449,7 @@ impl<'info> UpdateDeactivated<'info> {
             .stake_account
             .delegation()
             .expect("Undelegated stake under control");
-        if delegation.deactivation_epoch == std::u64::MAX {
+        if delegation.deactivation_epoch == u64::MAX {
             msg!(
                 "Stake {} is active",
                 self.stake_account.to_account_info().key
@@ -303,6 +470,11 @@ impl<'info> UpdateDeactivated<'info> {
             self.mint_to_treasury(msol_amount)?;
         }
 
+        let mut delegation_change = DelegationChange {
+            rewards: 0,
+            slashed: 0,
+        };
+        let mut msol_fees = 0;
         if delegated_lamports >= stake.last_update_delegated_lamports {
             // if there were rewards, mint treasury fee
             let rewards = delegated_lamports - stake.last_update_delegated_lamports;
@@ -316,15 +488,18 @@ impl<'info> UpdateDeactivated<'info> {
                 let fee_as_msol_amount =
                     self.state.calc_msol_from_lamports(protocol_rewards_fee)?;
                 self.mint_to_treasury(fee_as_msol_amount)?;
+                msol_fees = fee_as_msol_amount;
             }
+            delegation_change.rewards = rewards;
         } else {
             let slashed = stake.last_update_delegated_lamports - delegated_lamports;
             msg!("Slashed {}", slashed);
+            delegation_change.slashed = slashed;
         }
 
         // withdraw all to reserve (the stake account will be marked for deletion by the system)
-        self.common
-            .withdraw_to_reserve(self.stake_account.to_account_info().lamports())?;
+        let withdrawn = self.stake_account.to_account_info().lamports();
+        self.common.withdraw_to_reserve(withdrawn)?;
         // but send the rent-exempt lamports part to operational_sol_account for the future recreation of this slot's account
         self.state.with_reserve_seeds(|seeds| {
             invoke_signed(
@@ -363,9 +538,7 @@ impl<'info> UpdateDeactivated<'info> {
         // We update mSOL price in case we receive "extra deactivating rewards" after the start of Delayed-unstake.
         // Those rewards went into reserve_pda, are part of mSOL price (benefit all stakers) and even might be re-staked
         // set new mSOL price
-        self.state.msol_price = self
-            .state
-            .calc_lamports_from_msol_amount(State::PRICE_DENOMINATOR)?; // store binary-denominated mSOL price
+        self.update_msol_price()?;
 
         //remove deleted stake-account from our list
         self.common.state.stake_system.remove(
@@ -373,6 +546,33 @@ impl<'info> UpdateDeactivated<'info> {
             stake_index,
         )?;
 
+        EpochStats::record(
+            epoch_stats,
+            &self.common.state.instance_address,
+            self.common.state.to_account_info().owner,
+            |stats, epoch| {
+                stats.on_update(
+                    epoch,
+                    self.common.state.msol_price,
+                    delegation_change.rewards,
+                    delegation_change.slashed,
+                    msol_fees,
+                )
+            },
+        )?;
+        emit!(UpdateDeactivatedEvent {
+            state: self.state.instance_address,
+            stake_account: stake.stake_account,
+            stake_index,
+            delegation_change,
+            msol_fees,
+            extra_lamports,
+            withdrawn,
+            msol_price_change: PriceChange {
+                old: old_msol_price,
+                new: self.state.msol_price,
+            },
+        });
         Ok(())
     }
 }
@@ -1,10 +1,251 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::system_program;
+
+use crate::{
+    checks::{check_address, check_min_amount, check_owner_program},
+    pda::create_pda_account,
+    CommonError,
+};
 
 #[account]
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct TicketAccountData {
     pub state_address: Pubkey, // instance of marinade state this ticket belongs to
     pub beneficiary: Pubkey,   // main account where to send SOL when claimed
     pub lamports_amount: u64,  // amount this ticked is worth
     pub created_epoch: u64, // epoch when this acc was created (epoch when delayed-unstake was requested)
 }
+
+impl TicketAccountData {
+    /// Seed for tickets created as PDA [state, "ticket", beneficiary, nonce]
+    pub const SEED: &'static [u8] = b"ticket";
+
+    pub fn serialized_len() -> usize {
+        Self::default().try_to_vec().unwrap().len() + 8
+    }
+
+    pub fn find_address(
+        state: &Pubkey,
+        beneficiary: &Pubkey,
+        nonce: u64,
+        program_id: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                &state.to_bytes()[..32],
+                Self::SEED,
+                &beneficiary.to_bytes()[..32],
+                &nonce.to_le_bytes(),
+            ],
+            program_id,
+        )
+    }
+
+    pub fn with_seeds<R, F: FnOnce(&[&[u8]]) -> R>(
+        state: &Pubkey,
+        beneficiary: &Pubkey,
+        nonce: u64,
+        bump_seed: u8,
+        f: F,
+    ) -> R {
+        f(&[
+            &state.to_bytes()[..32],
+            Self::SEED,
+            &beneficiary.to_bytes()[..32],
+            &nonce.to_le_bytes(),
+            &[bump_seed],
+        ])
+    }
+
+    /// Checks the ticket is a not used ticket of this marinade instance held by beneficiary
+    pub fn check_held_by(&self, state_address: &Pubkey, beneficiary: &Pubkey) -> ProgramResult {
+        if &self.state_address != state_address {
+            msg!("Ticket has wrong marinade instance {}", self.state_address);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if self.lamports_amount == 0 {
+            msg!("Used ticket");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if &self.beneficiary != beneficiary {
+            msg!("wrong beneficiary");
+            return Err(CommonError::WrongBeneficiary.into());
+        }
+        Ok(())
+    }
+
+    /// Moves lamports_amount to a new ticket with the same instance, beneficiary and
+    /// created_epoch. Both parts must still be claimable (>= min_withdraw)
+    pub fn split_off(
+        &mut self,
+        lamports_amount: u64,
+        min_withdraw: u64,
+    ) -> Result<Self, ProgramError> {
+        check_min_amount(lamports_amount, min_withdraw, "split ticket")?;
+        let remaining_amount = self
+            .lamports_amount
+            .checked_sub(lamports_amount)
+            .ok_or_else(|| {
+                msg!(
+                    "Can not split {} from ticket of {}",
+                    lamports_amount,
+                    self.lamports_amount
+                );
+                ProgramError::InsufficientFunds
+            })?;
+        check_min_amount(remaining_amount, min_withdraw, "remaining ticket")?;
+
+        self.lamports_amount = remaining_amount;
+        Ok(Self {
+            state_address: self.state_address,
+            beneficiary: self.beneficiary,
+            lamports_amount,
+            created_epoch: self.created_epoch,
+        })
+    }
+
+    /// Creates the ticket PDA for (self.state_address, self.beneficiary, nonce)
+    /// paid by rent_payer and writes self into it
+    pub fn create_pda<'info>(
+        &self,
+        program_id: &Pubkey,
+        nonce: u64,
+        new_ticket_account: &AccountInfo<'info>,
+        rent_payer: &AccountInfo<'info>,
+        system_program: &AccountInfo<'info>,
+        rent: &Rent,
+    ) -> ProgramResult {
+        let (ticket_address, bump_seed) =
+            Self::find_address(&self.state_address, &self.beneficiary, nonce, program_id);
+        check_address(
+            new_ticket_account.key,
+            &ticket_address,
+            "new_ticket_account",
+        )?;
+        check_owner_program(
+            new_ticket_account,
+            &system_program::ID,
+            "new_ticket_account",
+        )?;
+        check_owner_program(rent_payer, &system_program::ID, "rent_payer")?;
+        check_address(system_program.key, &system_program::ID, "system_program")?;
+
+        Self::with_seeds(
+            &self.state_address,
+            &self.beneficiary,
+            nonce,
+            bump_seed,
+            |seeds| {
+                create_pda_account(
+                    rent_payer,
+                    new_ticket_account,
+                    Self::serialized_len(),
+                    program_id,
+                    system_program,
+                    rent,
+                    seeds,
+                )
+            },
+        )?;
+
+        let mut data = new_ticket_account.try_borrow_mut_data()?;
+        let dst: &mut [u8] = &mut data;
+        self.try_serialize(&mut std::io::Cursor::new(dst))
+    }
+}
+
+// move all rent-exempt ticket-account lamports to the user,
+// the ticket-account will be deleted eventually because is no longer rent-exempt
+pub fn close_ticket(ticket_account: &AccountInfo, transfer_sol_to: &AccountInfo) -> ProgramResult {
+    let dest_starting_lamports = transfer_sol_to.lamports();
+    **transfer_sol_to.lamports.borrow_mut() = dest_starting_lamports
+        .checked_add(ticket_account.lamports())
+        .ok_or(ProgramError::InvalidAccountData)?;
+    **ticket_account.lamports.borrow_mut() = 0;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn ticket() -> TicketAccountData {
+        TicketAccountData {
+            state_address: Pubkey::new_unique(),
+            beneficiary: Pubkey::new_unique(),
+            lamports_amount: 5 * SOL,
+            created_epoch: 10,
+        }
+    }
+
+    #[test]
+    fn test_check_held_by() {
+        let ticket = ticket();
+        assert!(ticket
+            .check_held_by(&ticket.state_address, &ticket.beneficiary)
+            .is_ok());
+        assert!(ticket
+            .check_held_by(&Pubkey::new_unique(), &ticket.beneficiary)
+            .is_err());
+        assert!(ticket
+            .check_held_by(&ticket.state_address, &Pubkey::new_unique())
+            .is_err());
+        let used = TicketAccountData {
+            lamports_amount: 0,
+            ..ticket
+        };
+        assert!(used
+            .check_held_by(&used.state_address, &used.beneficiary)
+            .is_err());
+    }
+
+    #[test]
+    fn test_split_off() -> ProgramResult {
+        let mut ticket = ticket();
+        let new_ticket = ticket.split_off(2 * SOL, SOL)?;
+        assert_eq!(ticket.lamports_amount, 3 * SOL);
+        assert_eq!(new_ticket.lamports_amount, 2 * SOL);
+        assert_eq!(new_ticket.state_address, ticket.state_address);
+        assert_eq!(new_ticket.beneficiary, ticket.beneficiary);
+        assert_eq!(new_ticket.created_epoch, ticket.created_epoch);
+
+        // both parts must stay >= min_withdraw, the ticket is unchanged on failure
+        assert!(ticket.split_off(SOL - 1, SOL).is_err());
+        assert!(ticket.split_off(2 * SOL + 1, SOL).is_err());
+        assert!(ticket.split_off(4 * SOL, SOL).is_err());
+        assert_eq!(ticket.lamports_amount, 3 * SOL);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ticket_seeds() {
+        let program_id = Pubkey::new_unique();
+        let ticket = ticket();
+        let (address, bump_seed) = TicketAccountData::find_address(
+            &ticket.state_address,
+            &ticket.beneficiary,
+            7,
+            &program_id,
+        );
+        let created = TicketAccountData::with_seeds(
+            &ticket.state_address,
+            &ticket.beneficiary,
+            7,
+            bump_seed,
+            |seeds| Pubkey::create_program_address(seeds, &program_id),
+        );
+        assert_eq!(created, Ok(address));
+        assert_ne!(
+            TicketAccountData::find_address(
+                &ticket.state_address,
+                &ticket.beneficiary,
+                8,
+                &program_id
+            )
+            .0,
+            address
+        );
+    }
+}
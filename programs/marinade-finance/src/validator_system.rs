@@ -1,10 +1,13 @@
 //use std::convert::TryInto;
 
-use crate::{calc::proportional, checks::check_address, error::CommonError, list::List, ID};
+use crate::{checks::check_address, error::CommonError, list::List, vote_account::VoteAccount};
 use anchor_lang::prelude::*;
+use std::convert::TryFrom;
 
 pub mod add;
 pub mod config_validator_system;
+pub mod expire_directed_stake;
+pub mod refresh_score;
 pub mod remove;
 pub mod set_score;
 
@@ -18,20 +21,30 @@ pub struct ValidatorRecord {
     pub score: u32,
     pub last_stake_delta_epoch: u64,
     pub duplication_flag_bump_seed: u8,
+    /// share of State::total_directed_stake deposited with deposit_directed for this validator
+    pub directed_stake_shares: u64,
+    /// 1 = score set by the manager, kept by refresh_validator_score
+    pub is_score_overridden: u8,
+    /// expire_directed_stake removes the directed stake starting from this epoch
+    pub directed_stake_expiry_epoch: u64,
 }
 
 impl ValidatorRecord {
     pub const DISCRIMINATOR: &'static [u8; 8] = b"validatr";
     pub const DUPLICATE_FLAG_SEED: &'static [u8] = b"unique_validator";
 
-    pub fn find_duplication_flag(state: &Pubkey, validator_account: &Pubkey) -> (Pubkey, u8) {
+    pub fn find_duplication_flag(
+        state: &Pubkey,
+        validator_account: &Pubkey,
+        program_id: &Pubkey,
+    ) -> (Pubkey, u8) {
         Pubkey::find_program_address(
             &[
                 &state.to_bytes()[..32],
                 Self::DUPLICATE_FLAG_SEED,
                 &validator_account.to_bytes()[..32],
             ],
-            &ID,
+            program_id,
         )
     }
 
@@ -48,19 +61,22 @@ impl ValidatorRecord {
         ])
     }
 
-    pub fn duplication_flag_address(&self, state: &Pubkey) -> Pubkey {
-        self.with_duplication_flag_seeds(state, |seeds| Pubkey::create_program_address(seeds, &ID))
-            .unwrap()
+    pub fn duplication_flag_address(&self, state: &Pubkey, program_id: &Pubkey) -> Pubkey {
+        self.with_duplication_flag_seeds(state, |seeds| {
+            Pubkey::create_program_address(seeds, program_id)
+        })
+        .unwrap()
     }
 
     pub fn new(
         validator_account: Pubkey,
         score: u32,
         state: &Pubkey,
+        program_id: &Pubkey,
         duplication_flag_address: &Pubkey,
     ) -> Result<Self, ProgramError> {
         let (actual_duplication_flag, duplication_flag_bump_seed) =
-            Self::find_duplication_flag(state, &validator_account);
+            Self::find_duplication_flag(state, &validator_account, program_id);
         if duplication_flag_address != &actual_duplication_flag {
             msg!(
                 "Duplication flag {} does not match {}",
@@ -73,12 +89,87 @@ impl ValidatorRecord {
             validator_account,
             active_balance: 0,
             score,
-            last_stake_delta_epoch: std::u64::MAX, // never
+            last_stake_delta_epoch: u64::MAX, // never
             duplication_flag_bump_seed,
+            directed_stake_shares: 0,
+            is_score_overridden: 0,
+            directed_stake_expiry_epoch: 0,
         })
     }
 }
 
+/// How refresh_validator_score computes a score from the vote account:
+/// average vote credits of the last credits_epochs completed epochs, minus commission,
+/// divided by credits_divisor and capped at MAX_SCORE.
+/// 0 for delinquent validators and commission above max_commission
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct ScoreFormula {
+    /// 0 = scores are set by the manager only
+    pub enabled: u8,
+    pub credits_epochs: u8,
+    pub max_commission: u8, // percent
+    /// scales raw credits (up to millions per epoch) down to the range of the manager set scores
+    pub credits_divisor: u32,
+    /// delinquent if the last completed epoch earned fewer credits
+    pub min_epoch_credits: u32,
+}
+
+impl ScoreFormula {
+    /// vote accounts keep the credits of the last 64 epochs
+    pub const MAX_CREDITS_EPOCHS: u8 = 64;
+    /// total_validator_score is u32: 4000 validators at MAX_SCORE still fit
+    pub const MAX_SCORE: u32 = 1_000_000;
+    /// a validator that did not vote in the last epoch is always delinquent
+    pub const MIN_EPOCH_CREDITS: u32 = 1;
+
+    pub fn check(&self) -> ProgramResult {
+        if self.enabled != 0 {
+            if self.credits_epochs == 0 || self.credits_epochs > Self::MAX_CREDITS_EPOCHS {
+                msg!(
+                    "credits_epochs must be from 1 to {}",
+                    Self::MAX_CREDITS_EPOCHS
+                );
+                return Err(ProgramError::InvalidArgument);
+            }
+            if self.max_commission > 100 {
+                return Err(CommonError::NumberTooHigh.into());
+            }
+            if self.credits_divisor == 0 {
+                msg!("credits_divisor must not be 0");
+                return Err(CommonError::NumberTooLow.into());
+            }
+            if self.min_epoch_credits < Self::MIN_EPOCH_CREDITS {
+                msg!(
+                    "min_epoch_credits must be at least {}",
+                    Self::MIN_EPOCH_CREDITS
+                );
+                return Err(CommonError::NumberTooLow.into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn score(&self, vote_account: &VoteAccount, clock: &Clock) -> u32 {
+        // the current epoch is not complete, its credits depend on when the score is refreshed
+        let last_epoch_credits = vote_account.credits_in_epoch(clock.epoch.saturating_sub(1));
+        let is_delinquent = last_epoch_credits < self.min_epoch_credits as u64;
+        if is_delinquent || vote_account.commission > self.max_commission {
+            return 0;
+        }
+        // completed epochs only, not voted epochs count as 0. u128 can not overflow
+        let credits: u128 = (1..=self.credits_epochs as u64)
+            .map(|age| vote_account.credits_in_epoch(clock.epoch.saturating_sub(age)) as u128)
+            .sum();
+        let average_credits = credits / self.credits_epochs as u128;
+        let score = average_credits * (100 - vote_account.commission as u128)
+            / 100
+            / self.credits_divisor.max(1) as u128;
+        u32::try_from(score)
+            .unwrap_or(u32::MAX)
+            .min(Self::MAX_SCORE)
+    }
+}
+
 #[derive(Clone, AnchorSerialize, AnchorDeserialize, Debug)]
 pub struct ValidatorSystem {
     pub validator_list: List,
@@ -158,17 +249,25 @@ impl ValidatorSystem {
         validator_account: Pubkey,
         score: u32,
         state: &Pubkey,
+        program_id: &Pubkey,
         duplication_flag_address: &Pubkey,
     ) -> ProgramResult {
         self.validator_list.push(
             validator_list_data,
-            ValidatorRecord::new(validator_account, score, state, duplication_flag_address)?,
+            ValidatorRecord::new(
+                validator_account,
+                score,
+                state,
+                program_id,
+                duplication_flag_address,
+            )?,
             "validator_list",
         )?;
-        self.total_validator_score += score as u32;
+        self.total_validator_score += score;
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_with_balance(
         &mut self,
         validator_list_data: &mut [u8],
@@ -176,14 +275,20 @@ impl ValidatorSystem {
         score: u32,
         balance: u64,
         state: &Pubkey,
+        program_id: &Pubkey,
         duplication_flag_address: &Pubkey,
     ) -> ProgramResult {
-        let mut validator =
-            ValidatorRecord::new(validator_account, score, state, duplication_flag_address)?;
+        let mut validator = ValidatorRecord::new(
+            validator_account,
+            score,
+            state,
+            program_id,
+            duplication_flag_address,
+        )?;
         validator.active_balance = balance;
         self.validator_list
             .push(validator_list_data, validator, "validator_list")?;
-        self.total_validator_score += score as u32;
+        self.total_validator_score += score;
         Ok(())
     }
 
@@ -236,19 +341,20 @@ impl ValidatorSystem {
         )
     }
 
-    pub fn validator_stake_target(
-        &self,
-        validator: &ValidatorRecord,
-        total_stake_target: u64,
-    ) -> Result<u64, CommonError> {
-        if self.total_validator_score == 0 {
-            return Ok(0);
-        }
-        proportional(
-            total_stake_target,
-            validator.score as u64,
-            self.total_validator_score as u64,
-        )
+    /// updates total_validator_score, the record must be set back into the list
+    pub fn set_score(
+        &mut self,
+        validator: &mut ValidatorRecord,
+        score: u32,
+    ) -> Result<(), CommonError> {
+        self.total_validator_score = self
+            .total_validator_score
+            .checked_sub(validator.score)
+            .ok_or(CommonError::CalculationFailure)?
+            .checked_add(score)
+            .ok_or(CommonError::CalculationFailure)?;
+        validator.score = score;
+        Ok(())
     }
 
     pub fn check_validator_list<'info>(
@@ -275,3 +381,111 @@ impl ValidatorSystem {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formula() -> ScoreFormula {
+        ScoreFormula {
+            enabled: 1,
+            credits_epochs: 2,
+            max_commission: 10,
+            credits_divisor: 10,
+            min_epoch_credits: 100,
+        }
+    }
+
+    fn clock(epoch: u64) -> Clock {
+        Clock {
+            epoch,
+            ..Default::default()
+        }
+    }
+
+    fn vote_account(commission: u8, epoch_credits: Vec<(u64, u64, u64)>) -> VoteAccount {
+        VoteAccount {
+            commission,
+            last_vote_slot: Some(1),
+            epoch_credits,
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(formula().check().is_ok());
+        assert!(ScoreFormula::default().check().is_ok());
+        for invalid in [
+            ScoreFormula {
+                credits_epochs: 0,
+                ..formula()
+            },
+            ScoreFormula {
+                credits_epochs: ScoreFormula::MAX_CREDITS_EPOCHS + 1,
+                ..formula()
+            },
+            ScoreFormula {
+                max_commission: 101,
+                ..formula()
+            },
+            ScoreFormula {
+                credits_divisor: 0,
+                ..formula()
+            },
+            ScoreFormula {
+                min_epoch_credits: 0,
+                ..formula()
+            },
+        ] {
+            assert!(invalid.check().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_score() {
+        // epochs 8 and 9 are completed, 10 is the current one
+        let credits = vec![(8, 4000, 0), (9, 10000, 4000), (10, 10500, 10000)];
+        // (4000 + 6000) / 2 * 95% / 10
+        assert_eq!(
+            formula().score(&vote_account(5, credits.clone()), &clock(10)),
+            475
+        );
+        // not voted epoch 8 counts as 0
+        assert_eq!(
+            formula().score(&vote_account(0, vec![(9, 6000, 0)]), &clock(10)),
+            300
+        );
+        // commission above max
+        assert_eq!(
+            formula().score(&vote_account(11, credits.clone()), &clock(10)),
+            0
+        );
+        // the current epoch credits do not count
+        assert_eq!(formula().score(&vote_account(0, credits), &clock(9)), 200);
+    }
+
+    #[test]
+    fn test_score_delinquent() {
+        // voting now, but less than min_epoch_credits in the last completed epoch
+        let credits = vec![(8, 4000, 0), (9, 4099, 4000), (10, 10000, 4099)];
+        assert_eq!(formula().score(&vote_account(0, credits), &clock(10)), 0);
+        // no credits at all
+        assert_eq!(formula().score(&vote_account(0, vec![]), &clock(10)), 0);
+        let credits = vec![(8, 4000, 0), (9, 4100, 4000)];
+        assert_eq!(formula().score(&vote_account(0, credits), &clock(10)), 205);
+    }
+
+    #[test]
+    fn test_score_max() {
+        let formula = ScoreFormula {
+            credits_divisor: 1,
+            min_epoch_credits: 1,
+            ..formula()
+        };
+        let credits = vec![(8, u64::MAX / 4, 0), (9, u64::MAX / 2, u64::MAX / 4)];
+        assert_eq!(
+            formula.score(&vote_account(0, credits), &clock(10)),
+            ScoreFormula::MAX_SCORE
+        );
+    }
+}
@@ -3,7 +3,8 @@ use anchor_lang::solana_program::{program::invoke_signed, system_instruction, sy
 
 use crate::{
     checks::{check_address, check_owner_program},
-    AddValidator, ID,
+    events::AddValidatorEvent,
+    AddValidator,
 };
 //use super::{ValidatorRecord, ValidatorSystem};
 
@@ -36,12 +37,14 @@ impl<'info> AddValidator<'info> {
 
         msg!("Add validator {}", self.validator_vote.key);
 
-        let state_address = *self.state.to_account_info().key;
+        let state_address = self.state.instance_address;
+        let program_id = *self.state.to_account_info().owner;
         self.state.validator_system.add(
             &mut self.validator_list.data.borrow_mut(),
             *self.validator_vote.key,
             score,
             &state_address,
+            &program_id,
             self.duplication_flag.key,
         )?;
 
@@ -50,27 +53,30 @@ impl<'info> AddValidator<'info> {
             &self.validator_list.data.borrow(),
             self.state.validator_system.validator_count() - 1,
         )?;
-        validator_record.with_duplication_flag_seeds(
-            self.state.to_account_info().key,
-            |seeds| {
-                invoke_signed(
-                    &system_instruction::create_account(
-                        self.rent_payer.key,
-                        self.duplication_flag.key,
-                        self.rent.minimum_balance(0),
-                        0,
-                        &ID,
-                    ),
-                    &[
-                        self.system_program.clone(),
-                        self.rent_payer.clone(),
-                        self.duplication_flag.clone(),
-                    ],
-                    &[seeds],
-                )
-            },
-        )?;
+        validator_record.with_duplication_flag_seeds(&self.state.instance_address, |seeds| {
+            invoke_signed(
+                &system_instruction::create_account(
+                    self.rent_payer.key,
+                    self.duplication_flag.key,
+                    self.rent.minimum_balance(0),
+                    0,
+                    &program_id,
+                ),
+                &[
+                    self.system_program.clone(),
+                    self.rent_payer.clone(),
+                    self.duplication_flag.clone(),
+                ],
+                &[seeds],
+            )
+        })?;
 
+        emit!(AddValidatorEvent {
+            state: state_address,
+            validator: *self.validator_vote.key,
+            index: self.state.validator_system.validator_count() - 1,
+            score,
+        });
         Ok(())
     }
 }
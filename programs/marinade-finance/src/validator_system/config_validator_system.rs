@@ -1,11 +1,32 @@
 use anchor_lang::prelude::*;
 
+use crate::validator_system::ScoreFormula;
+
 impl<'info> crate::ConfigValidatorSystem<'info> {
     pub fn process(&mut self, extra_runs: u32) -> ProgramResult {
         self.state
             .validator_system
             .check_validator_manager_authority(self.manager_authority.key)?;
         self.state.stake_system.extra_stake_delta_runs = extra_runs; // TODO: think about is it stake or validator thing?
+        emit!(crate::events::ConfigValidatorSystemEvent {
+            state: self.state.instance_address,
+            extra_runs,
+        });
+        Ok(())
+    }
+}
+
+impl<'info> crate::ConfigScoreFormula<'info> {
+    pub fn process(&mut self, score_formula: ScoreFormula) -> ProgramResult {
+        self.state
+            .validator_system
+            .check_validator_manager_authority(self.manager_authority.key)?;
+        score_formula.check()?;
+        self.state.score_formula = score_formula;
+        emit!(crate::events::ConfigScoreFormulaEvent {
+            state: self.state.instance_address,
+            score_formula,
+        });
         Ok(())
     }
 }
@@ -0,0 +1,48 @@
+use anchor_lang::prelude::*;
+
+use crate::{events::DirectedStakeExpiredEvent, ExpireDirectedStake};
+
+impl<'info> ExpireDirectedStake<'info> {
+    // fn expire_directed_stake()
+    // permissionless, removes the directed stake of a validator without directed deposits
+    // during state.directed_stake_epochs
+    pub fn process(&mut self, index: u32) -> ProgramResult {
+        self.state
+            .validator_system
+            .check_validator_list(&self.validator_list)?;
+        let mut validator = self
+            .state
+            .validator_system
+            .get(&self.validator_list.data.borrow(), index)?;
+        if validator.directed_stake_shares == 0 {
+            msg!(
+                "Validator {} has no directed stake",
+                validator.validator_account
+            );
+            return Ok(()); // Not an error. Don't fail other instructions in tx
+        }
+        if self.clock.epoch < validator.directed_stake_expiry_epoch {
+            msg!(
+                "Validator {} directed stake expires in epoch {}",
+                validator.validator_account,
+                validator.directed_stake_expiry_epoch
+            );
+            return Ok(()); // Not an error. Don't fail other instructions in tx
+        }
+
+        let lamports = self.state.remove_directed_stake(&mut validator)?;
+        self.state.validator_system.set(
+            &mut self.validator_list.data.borrow_mut(),
+            index,
+            validator,
+        )?;
+
+        emit!(DirectedStakeExpiredEvent {
+            state: self.state.instance_address,
+            validator: validator.validator_account,
+            validator_index: index,
+            lamports,
+        });
+        Ok(())
+    }
+}
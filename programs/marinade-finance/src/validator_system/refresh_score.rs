@@ -0,0 +1,65 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::vote;
+
+use crate::{
+    checks::{check_address, check_owner_program},
+    events::{ScoreChange, SetValidatorScoreEvent},
+    vote_account::VoteAccount,
+    RefreshValidatorScore,
+};
+
+impl<'info> RefreshValidatorScore<'info> {
+    // fn refresh_validator_score()
+    // permissionless, computes the score with state.score_formula
+    pub fn process(&mut self, index: u32) -> ProgramResult {
+        let formula = self.state.score_formula;
+        if formula.enabled == 0 {
+            msg!("Score formula is not enabled");
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.state
+            .validator_system
+            .check_validator_list(&self.validator_list)?;
+        let mut validator = self
+            .state
+            .validator_system
+            .get(&self.validator_list.data.borrow(), index)?;
+        check_address(
+            self.validator_vote.key,
+            &validator.validator_account,
+            "validator_vote",
+        )?;
+        check_owner_program(&self.validator_vote, &vote::program::ID, "validator_vote")?;
+        if validator.is_score_overridden != 0 {
+            msg!(
+                "Validator {} score {} is set by the manager",
+                validator.validator_account,
+                validator.score
+            );
+            return Ok(()); // Not an error. Don't fail other instructions in tx
+        }
+
+        let vote_account = VoteAccount::deserialize(&self.validator_vote.data.borrow())?;
+        let score = formula.score(&vote_account, &self.clock);
+        let score_change = ScoreChange {
+            old: validator.score,
+            new: score,
+        };
+        self.state
+            .validator_system
+            .set_score(&mut validator, score)?;
+        self.state.validator_system.set(
+            &mut self.validator_list.data.borrow_mut(),
+            index,
+            validator,
+        )?;
+
+        emit!(SetValidatorScoreEvent {
+            state: self.state.instance_address,
+            validator: validator.validator_account,
+            index,
+            score_change,
+        });
+        Ok(())
+    }
+}
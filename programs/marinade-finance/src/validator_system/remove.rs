@@ -1,6 +1,6 @@
 use anchor_lang::prelude::*;
 
-use crate::RemoveValidator;
+use crate::{events::RemoveValidatorEvent, RemoveValidator};
 
 impl<'info> RemoveValidator<'info> {
     pub fn process(&mut self, index: u32, validator_vote: Pubkey) -> ProgramResult {
@@ -13,7 +13,7 @@ impl<'info> RemoveValidator<'info> {
 
         self.state
             .check_operational_sol_account(self.operational_sol_account.key)?;
-        let validator = self
+        let mut validator = self
             .state
             .validator_system
             .get(&self.validator_list.data.borrow(), index)?;
@@ -22,16 +22,23 @@ impl<'info> RemoveValidator<'info> {
             return Err(ProgramError::InvalidArgument);
         }
         if self.duplication_flag.key
-            != &validator.duplication_flag_address(self.state.to_account_info().key)
+            != &validator.duplication_flag_address(
+                &self.state.instance_address,
+                self.state.to_account_info().owner,
+            )
         {
             msg!(
                 "Invalid duplication flag {}. Expected {}",
                 self.duplication_flag.key,
-                validator.duplication_flag_address(self.state.to_account_info().key)
+                validator.duplication_flag_address(
+                    &self.state.instance_address,
+                    self.state.to_account_info().owner
+                )
             );
             return Err(ProgramError::InvalidArgument);
         }
 
+        self.state.remove_directed_stake(&mut validator)?;
         self.state.validator_system.remove(
             &mut self.validator_list.data.as_ref().borrow_mut(),
             index,
@@ -41,6 +48,12 @@ impl<'info> RemoveValidator<'info> {
         let rent_return = self.duplication_flag.lamports();
         **self.duplication_flag.try_borrow_mut_lamports()? = 0;
         **self.operational_sol_account.try_borrow_mut_lamports()? += rent_return;
+        emit!(RemoveValidatorEvent {
+            state: self.state.instance_address,
+            validator: validator_vote,
+            index,
+            rent_returned: rent_return,
+        });
         Ok(())
     }
 }
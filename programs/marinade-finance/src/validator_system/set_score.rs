@@ -1,17 +1,25 @@
 use anchor_lang::prelude::*;
 
-use crate::{error::CommonError, SetValidatorScore};
+use crate::{
+    events::{ScoreChange, ScoreOverrideClearedEvent, SetValidatorScoreEvent},
+    roles::Role,
+    validator_system::ValidatorRecord,
+    SetValidatorScore,
+};
 
 impl<'info> SetValidatorScore<'info> {
-    pub fn process(&mut self, index: u32, validator_vote: Pubkey, score: u32) -> ProgramResult {
+    fn get_validator(
+        &self,
+        index: u32,
+        validator_vote: Pubkey,
+    ) -> Result<ValidatorRecord, ProgramError> {
         self.state
-            .validator_system
-            .check_validator_manager_authority(self.manager_authority.key)?;
+            .check_validator_manager_or_role(Role::ValidatorScorer, self.manager_authority.key)?;
         self.state
             .validator_system
             .check_validator_list(&self.validator_list)?;
 
-        let mut validator = self
+        let validator = self
             .state
             .validator_system
             .get(&self.validator_list.data.borrow(), index)?;
@@ -24,26 +32,53 @@ impl<'info> SetValidatorScore<'info> {
             );
             return Err(ProgramError::InvalidArgument);
         }
+        Ok(validator)
+    }
 
-        self.state.validator_system.total_validator_score = self
-            .state
-            .validator_system
-            .total_validator_score
-            .checked_sub(validator.score)
-            .ok_or(CommonError::CalculationFailure)?;
-        validator.score = score;
-        self.state.validator_system.total_validator_score = self
-            .state
+    // fn set_validator_score()
+    // the score overrides the one computed by refresh_validator_score
+    pub fn process(&mut self, index: u32, validator_vote: Pubkey, score: u32) -> ProgramResult {
+        let mut validator = self.get_validator(index, validator_vote)?;
+
+        let score_change = ScoreChange {
+            old: validator.score,
+            new: score,
+        };
+        self.state
             .validator_system
-            .total_validator_score
-            .checked_add(score)
-            .ok_or(CommonError::CalculationFailure)?;
+            .set_score(&mut validator, score)?;
+        validator.is_score_overridden = 1;
+        self.state.validator_system.set(
+            &mut self.validator_list.data.borrow_mut(),
+            index,
+            validator,
+        )?;
+
+        emit!(SetValidatorScoreEvent {
+            state: self.state.instance_address,
+            validator: validator_vote,
+            index,
+            score_change,
+        });
+        Ok(())
+    }
+
+    // fn clear_validator_score_override()
+    // the score is kept until the next refresh_validator_score
+    pub fn clear_override(&mut self, index: u32, validator_vote: Pubkey) -> ProgramResult {
+        let mut validator = self.get_validator(index, validator_vote)?;
+        validator.is_score_overridden = 0;
         self.state.validator_system.set(
             &mut self.validator_list.data.borrow_mut(),
             index,
             validator,
         )?;
 
+        emit!(ScoreOverrideClearedEvent {
+            state: self.state.instance_address,
+            validator: validator_vote,
+            index,
+        });
         Ok(())
     }
 }
@@ -0,0 +1,221 @@
+//! Manual parsing of vote program accounts.
+//! solana-program 1.7 has no vote state, only the fields needed for scoring are read
+
+use anchor_lang::prelude::*;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VoteAccount {
+    pub commission: u8,
+    /// slot of the latest vote. None if never voted
+    pub last_vote_slot: Option<u64>,
+    /// (epoch, credits, prev_credits) of the latest epochs, oldest first
+    pub epoch_credits: Vec<(u64, u64, u64)>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ProgramError> {
+        if self.data.len() < len {
+            msg!("Vote account data is too short");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn skip(&mut self, len: usize) -> ProgramResult {
+        self.take(len)?;
+        Ok(())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, ProgramError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ProgramError> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, ProgramError> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    // bincode Vec/VecDeque/BTreeMap length
+    fn read_len(&mut self) -> Result<usize, ProgramError> {
+        Ok(self.read_u64()? as usize)
+    }
+}
+
+impl VoteAccount {
+    // VoteStateVersions tags
+    const VERSION_1_14_11: u32 = 1;
+    const VERSION_CURRENT: u32 = 2;
+
+    /// bincode serialized VoteStateVersions. The 0.23.5 version is not supported
+    pub fn deserialize(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut reader = Reader { data };
+        // votes have a latency byte in the current version
+        let vote_len = match reader.read_u32()? {
+            Self::VERSION_1_14_11 => 12,
+            Self::VERSION_CURRENT => 13,
+            version => {
+                msg!("Unsupported vote account version {}", version);
+                return Err(ProgramError::InvalidAccountData);
+            }
+        };
+        reader.skip(32)?; // node_pubkey
+        reader.skip(32)?; // authorized_withdrawer
+        let commission = reader.read_u8()?;
+
+        let votes_count = reader.read_len()?;
+        let last_vote_slot = if votes_count > 0 {
+            reader.skip((votes_count - 1) * vote_len)?;
+            // vote: [latency u8,] slot u64, confirmation_count u32
+            reader.skip(vote_len - 12)?;
+            let slot = reader.read_u64()?;
+            reader.skip(4)?;
+            Some(slot)
+        } else {
+            None
+        };
+
+        if reader.read_u8()? != 0 {
+            reader.skip(8)?; // root_slot
+        }
+        let authorized_voters_count = reader.read_len()?;
+        reader.skip(authorized_voters_count * (8 + 32))?;
+        // prior_voters circular buffer: 32 * (pubkey, epoch, epoch), idx, is_empty
+        reader.skip(32 * (32 + 8 + 8) + 8 + 1)?;
+
+        let epoch_credits_count = reader.read_len()?;
+        let mut epoch_credits = Vec::with_capacity(epoch_credits_count);
+        for _ in 0..epoch_credits_count {
+            epoch_credits.push((reader.read_u64()?, reader.read_u64()?, reader.read_u64()?));
+        }
+
+        Ok(Self {
+            commission,
+            last_vote_slot,
+            epoch_credits,
+        })
+    }
+
+    /// credits earned in the epoch, 0 if not voted
+    pub fn credits_in_epoch(&self, epoch: u64) -> u64 {
+        self.epoch_credits
+            .iter()
+            .find(|(credits_epoch, _, _)| *credits_epoch == epoch)
+            .map(|(_, credits, prev_credits)| credits.saturating_sub(*prev_credits))
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// bincode VoteStateVersions with the given votes (slots) and epoch credits
+    fn vote_account_data(
+        version: u32,
+        commission: u8,
+        vote_slots: &[u64],
+        root_slot: Option<u64>,
+        epoch_credits: &[(u64, u64, u64)],
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&version.to_le_bytes());
+        data.extend_from_slice(&[1u8; 32]); // node_pubkey
+        data.extend_from_slice(&[2u8; 32]); // authorized_withdrawer
+        data.push(commission);
+        data.extend_from_slice(&(vote_slots.len() as u64).to_le_bytes());
+        for (i, slot) in vote_slots.iter().enumerate() {
+            if version == VoteAccount::VERSION_CURRENT {
+                data.push(1); // latency
+            }
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&(vote_slots.len() as u32 - i as u32).to_le_bytes());
+        }
+        match root_slot {
+            Some(slot) => {
+                data.push(1);
+                data.extend_from_slice(&slot.to_le_bytes());
+            }
+            None => data.push(0),
+        }
+        // authorized_voters: one (epoch, pubkey)
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.extend_from_slice(&[3u8; 32]);
+        // prior_voters
+        data.extend_from_slice(&[0u8; 32 * (32 + 8 + 8)]);
+        data.extend_from_slice(&31u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&(epoch_credits.len() as u64).to_le_bytes());
+        for (epoch, credits, prev_credits) in epoch_credits {
+            data.extend_from_slice(&epoch.to_le_bytes());
+            data.extend_from_slice(&credits.to_le_bytes());
+            data.extend_from_slice(&prev_credits.to_le_bytes());
+        }
+        // last_timestamp (slot, unix timestamp), not read
+        data.extend_from_slice(&[0u8; 16]);
+        data
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let epoch_credits = [(5, 1000, 0), (6, 2500, 1000), (8, 2600, 2500)];
+        for version in [VoteAccount::VERSION_1_14_11, VoteAccount::VERSION_CURRENT] {
+            let data = vote_account_data(version, 7, &[100, 101, 105], Some(50), &epoch_credits);
+            assert_eq!(
+                VoteAccount::deserialize(&data).unwrap(),
+                VoteAccount {
+                    commission: 7,
+                    last_vote_slot: Some(105),
+                    epoch_credits: epoch_credits.to_vec(),
+                }
+            );
+        }
+
+        let data = vote_account_data(VoteAccount::VERSION_CURRENT, 0, &[], None, &[]);
+        assert_eq!(
+            VoteAccount::deserialize(&data).unwrap(),
+            VoteAccount::default()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_invalid() {
+        // 0.23.5 version
+        let data = vote_account_data(0, 0, &[1], None, &[]);
+        assert_eq!(
+            VoteAccount::deserialize(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let data = vote_account_data(VoteAccount::VERSION_CURRENT, 0, &[1], None, &[(1, 2, 0)]);
+        // cut in the epoch credits
+        assert_eq!(
+            VoteAccount::deserialize(&data[..data.len() - 20]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_credits_in_epoch() {
+        let vote_account = VoteAccount {
+            epoch_credits: vec![(5, 1000, 0), (6, 2500, 1000)],
+            ..Default::default()
+        };
+        assert_eq!(vote_account.credits_in_epoch(5), 1000);
+        assert_eq!(vote_account.credits_in_epoch(6), 1500);
+        assert_eq!(vote_account.credits_in_epoch(7), 0);
+    }
+}